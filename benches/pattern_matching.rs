@@ -0,0 +1,83 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tisu::{
+    filter::{ApplyOptions, Filter, FilterCollection},
+    map::Map,
+    tile_index::TileIndex,
+};
+
+/// Creates a square map of the given size, where most fields hold common tiles
+/// (0-3) and about 1% of the fields hold rare tiles (4-43).
+fn create_map(size: u32, rng: &mut StdRng) -> Map<u32> {
+    let mut map = Map::new((size, size).into());
+    for field in map.mut_data() {
+        *field = if rng.random_bool(0.01) {
+            rng.random_range(4..44)
+        } else {
+            rng.random_range(0..4)
+        };
+    }
+    map
+}
+
+/// Creates a collection of 40 filters with 3x3 patterns, each containing a
+/// rare tile surrounded by wildcards and common tiles.
+fn create_filter_collection(rng: &mut StdRng) -> FilterCollection<u32> {
+    let wildcard = u32::MAX;
+    let filters: Vec<_> = (4..44)
+        .map(|rare_tile| {
+            let mut pattern = Map::new((3, 3).into());
+            for field in pattern.mut_data() {
+                *field = if rng.random_bool(0.5) {
+                    wildcard
+                } else {
+                    rng.random_range(0..4)
+                };
+            }
+            pattern.set((1, 1).into(), rare_tile).unwrap();
+            let mut substitute = Map::new((3, 3).into());
+            substitute.mut_data().fill(wildcard);
+            substitute.set((1, 1).into(), 0).unwrap();
+            Filter::new(pattern, substitute, wildcard).unwrap()
+        })
+        .collect();
+    FilterCollection::new(&filters)
+}
+
+fn bench_pattern_matching(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let filter_collection = create_filter_collection(&mut rng);
+    let mut group = c.benchmark_group("apply_filter_collection");
+    group.sample_size(10);
+    for size in [64, 256, 512] {
+        let map = create_map(size, &mut rng);
+        group.bench_with_input(BenchmarkId::new("scan", size), &map, |b, map| {
+            b.iter(|| {
+                let mut destination = map.clone();
+                filter_collection
+                    .apply_with_rng(map, &mut destination, &mut StdRng::seed_from_u64(0))
+                    .unwrap();
+                destination
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("indexed", size), &map, |b, map| {
+            b.iter(|| {
+                let mut destination = map.clone();
+                let index = TileIndex::new(map);
+                let mut rng = StdRng::seed_from_u64(0);
+                let options = &mut ApplyOptions {
+                    index: Some(&index),
+                    ..ApplyOptions::new(&mut rng)
+                };
+                filter_collection
+                    .apply_with(map, &mut destination, options)
+                    .unwrap();
+                destination
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_pattern_matching);
+criterion_main!(benches);
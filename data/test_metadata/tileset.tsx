@@ -0,0 +1,30 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" tiledversion="1.11.0" name="metadata" tilewidth="16" tileheight="16" tilecount="6" columns="2">
+ <image source="../tileset.png" width="32" height="48"/>
+ <tile id="0" type="wall">
+  <properties>
+   <property name="solid" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="1" type="floor">
+  <properties>
+   <property name="solid" type="bool" value="false"/>
+  </properties>
+ </tile>
+ <tile id="2" type="wall">
+  <properties>
+   <property name="biome" value="cave"/>
+   <property name="solid" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="3">
+  <properties>
+   <property name="match_class" value="wall"/>
+  </properties>
+ </tile>
+ <tile id="4">
+  <properties>
+   <property name="match_solid" type="bool" value="false"/>
+  </properties>
+ </tile>
+</tileset>
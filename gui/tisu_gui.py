@@ -163,6 +163,7 @@ class TisuGui:
     def _get_cmd(self):
         return [
             self.app_state.paths[ParameterType.TISU.value],
+            "apply",
             "--input",
             self.app_state.paths[ParameterType.INPUT.value],
             "--filters",
@@ -0,0 +1,95 @@
+use std::ops::AddAssign;
+use std::time::Duration;
+
+/// Statistics of applying a filter to a map.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FilterStats {
+    /// Number of positions where the pattern was tested
+    pub positions_tested: usize,
+    /// Number of positions where the pattern matched
+    pub matches: usize,
+    /// Number of pattern matches where the substitute wasn't applied because
+    /// of the filter probability
+    pub skipped: usize,
+    /// Number of fields changed by substitutions (a field changed by several
+    /// substitutions is counted each time)
+    pub changes: usize,
+    /// Wall-clock time spent applying the filter
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_seconds"))]
+    pub duration: Duration,
+}
+
+impl AddAssign for FilterStats {
+    fn add_assign(&mut self, other: Self) {
+        self.positions_tested += other.positions_tested;
+        self.matches += other.matches;
+        self.skipped += other.skipped;
+        self.changes += other.changes;
+        self.duration += other.duration;
+    }
+}
+
+/// Statistics of applying a filter collection to a map, with the statistics
+/// of each of its filters (summed over all iterations).
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FilterCollectionStats {
+    /// Statistics of the filters, in the order of the filter collection
+    pub filters: Vec<FilterStats>,
+    /// Wall-clock time spent applying the filter collection
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_seconds"))]
+    pub duration: Duration,
+}
+
+impl FilterCollectionStats {
+    /// Creates empty statistics for a filter collection with the given number
+    /// of filters.
+    pub fn new(filter_count: usize) -> Self {
+        Self {
+            filters: vec![FilterStats::default(); filter_count],
+            duration: Duration::ZERO,
+        }
+    }
+
+    /// Returns the statistics of all the filters added together (with the
+    /// duration of the whole filter collection).
+    pub fn total(&self) -> FilterStats {
+        let mut total = FilterStats::default();
+        for stats in &self.filters {
+            total += *stats;
+        }
+        total.duration = self.duration;
+        total
+    }
+
+    /// Returns the number of fields changed by the filters.
+    pub fn changes(&self) -> usize {
+        self.filters.iter().map(|stats| stats.changes).sum()
+    }
+}
+
+impl AddAssign<&FilterCollectionStats> for FilterCollectionStats {
+    /// Adds the statistics of another application of the same filter
+    /// collection.
+    fn add_assign(&mut self, other: &FilterCollectionStats) {
+        if self.filters.len() < other.filters.len() {
+            self.filters
+                .resize(other.filters.len(), FilterStats::default());
+        }
+        for (stats, other_stats) in self.filters.iter_mut().zip(&other.filters) {
+            *stats += *other_stats;
+        }
+        self.duration += other.duration;
+    }
+}
+
+/// Serializes a duration as a number of seconds (for use with
+/// `serialize_with`).
+#[cfg(feature = "serde")]
+pub fn serialize_seconds<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_f64(duration.as_secs_f64())
+}
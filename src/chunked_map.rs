@@ -0,0 +1,426 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use rand::Rng;
+
+use crate::apply_stats::{FilterCollectionStats, FilterStats};
+use crate::filter::{ApplyMode, FilterCollection, MaybeSync, PatternMatching};
+use crate::map::Map;
+use crate::map_storage::MapStorage;
+use crate::rect2::Rect2u;
+use crate::tisu_error::TisuError;
+use crate::vector2::Vector2u;
+
+/// A sparse map for huge, mostly empty worlds. The map is split into chunks
+/// of the same size, and only the chunks containing fields that differ from
+/// the fill value are allocated. Fields of chunks that aren't allocated have
+/// the fill value.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ChunkedMap<T> {
+    /// Size of the map
+    size: Vector2u,
+    /// Size of each chunk (chunks at the right and the bottom edges can
+    /// exceed map bounds)
+    chunk_size: Vector2u,
+    /// Value of the fields of chunks that aren't allocated
+    fill: T,
+    /// Allocated chunks by their chunk coordinates
+    chunks: HashMap<(u32, u32), Map<T>>,
+}
+
+impl<T> ChunkedMap<T> {
+    /// Creates a map of the given size with all fields set to the given fill
+    /// value, split into chunks of the given size.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the given chunk size is zero.
+    pub fn new(size: Vector2u, chunk_size: Vector2u, fill: T) -> Result<Self, TisuError> {
+        if chunk_size.x == 0 || chunk_size.y == 0 {
+            return Err(TisuError::InvalidArgument);
+        }
+        Ok(Self {
+            size,
+            chunk_size,
+            fill,
+            chunks: HashMap::new(),
+        })
+    }
+
+    /// Creates a map with the fields of the given dense map, split into
+    /// chunks of the given size. Only the chunks containing fields that differ
+    /// from the given fill value are allocated.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the given chunk size is zero.
+    pub fn from_map(map: &Map<T>, chunk_size: Vector2u, fill: T) -> Result<Self, TisuError>
+    where
+        T: Clone + PartialEq,
+    {
+        let mut result = Self::new(map.size(), chunk_size, fill)?;
+        result.paste(map, Vector2u::default())?;
+        Ok(result)
+    }
+
+    /// Returns the size of the map.
+    pub fn size(&self) -> Vector2u {
+        self.size
+    }
+
+    /// Returns the size of the chunks.
+    pub fn chunk_size(&self) -> Vector2u {
+        self.chunk_size
+    }
+
+    /// Returns the value of the fields of chunks that aren't allocated.
+    pub fn fill(&self) -> &T {
+        &self.fill
+    }
+
+    /// Returns the number of allocated chunks.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Returns the rectangles of all the chunks of the map (allocated or not),
+    /// limited to map bounds, column by column.
+    pub fn chunk_rects(&self) -> Vec<Rect2u> {
+        let columns = self.size.x.div_ceil(self.chunk_size.x);
+        let rows = self.size.y.div_ceil(self.chunk_size.y);
+        let mut result = Vec::with_capacity(columns as usize * rows as usize);
+        for column in 0..columns {
+            for row in 0..rows {
+                let position = Vector2u::new(column * self.chunk_size.x, row * self.chunk_size.y);
+                let size = Vector2u::new(
+                    self.chunk_size.x.min(self.size.x - position.x),
+                    self.chunk_size.y.min(self.size.y - position.y),
+                );
+                if let Ok(rect) = Rect2u::new(position, size) {
+                    result.push(rect);
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns the part of the map inside the given rectangle as a dense map.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the given rectangle exceeds map bounds.
+    pub fn crop(&self, rect: Rect2u) -> Result<Map<T>, TisuError>
+    where
+        T: Clone,
+    {
+        let end = self.end_of(rect.position(), rect.size())?;
+        Ok(self.copy_area(rect.position(), end))
+    }
+
+    /// Copies the given dense map onto this map, with its top left field at
+    /// the given position. Chunks are only allocated for fields that differ
+    /// from the fill value. Returns the number of fields whose value changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pasted map exceeds map bounds.
+    pub fn paste(&mut self, map: &Map<T>, position: Vector2u) -> Result<usize, TisuError>
+    where
+        T: Clone + PartialEq,
+    {
+        let end = self.end_of(position, map.size())?;
+        let mut changes = 0;
+        for key in self.chunk_keys(position, end) {
+            let chunk_position = self.chunk_position(key);
+            let (start, chunk_end) = overlap(position, end, chunk_position, self.chunk_size);
+            let only_fill = (start.y..chunk_end.y).all(|y| {
+                (start.x..chunk_end.x).all(|x| map[Vector2u::new(x, y) - position] == self.fill)
+            });
+            if only_fill && !self.chunks.contains_key(&key) {
+                continue;
+            }
+            let chunk = self
+                .chunks
+                .entry(key)
+                .or_insert_with(|| Map::filled(self.chunk_size, self.fill.clone()));
+            for y in start.y..chunk_end.y {
+                for x in start.x..chunk_end.x {
+                    let point = Vector2u::new(x, y);
+                    let field = &map[point - position];
+                    let chunk_field = &mut chunk[point - chunk_position];
+                    if chunk_field != field {
+                        *chunk_field = field.clone();
+                        changes += 1;
+                    }
+                }
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Returns the whole map as a dense map.
+    pub fn to_map(&self) -> Map<T>
+    where
+        T: Clone,
+    {
+        self.copy_area(Vector2u::default(), self.size)
+    }
+
+    /// Frees the chunks whose fields all have the fill value.
+    pub fn compact(&mut self)
+    where
+        T: PartialEq,
+    {
+        self.chunks
+            .retain(|_, chunk| chunk.data().iter().any(|field| field != &self.fill));
+    }
+
+    /// Returns the fields between the given start (inclusive) and end
+    /// (exclusive) as a dense map. The area must be inside map bounds.
+    fn copy_area(&self, start: Vector2u, end: Vector2u) -> Map<T>
+    where
+        T: Clone,
+    {
+        let mut result = Map::filled(end - start, self.fill.clone());
+        for key in self.chunk_keys(start, end) {
+            let Some(chunk) = self.chunks.get(&key) else {
+                continue;
+            };
+            let chunk_position = self.chunk_position(key);
+            let (area_start, area_end) = overlap(start, end, chunk_position, self.chunk_size);
+            for y in area_start.y..area_end.y {
+                for x in area_start.x..area_end.x {
+                    let point = Vector2u::new(x, y);
+                    result[point - start] = chunk[point - chunk_position].clone();
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns the end (exclusive) of the area at the given position and of
+    /// the given size.
+    fn end_of(&self, position: Vector2u, size: Vector2u) -> Result<Vector2u, TisuError> {
+        position
+            .checked_add(size)
+            .filter(|end| end.x <= self.size.x && end.y <= self.size.y)
+            .ok_or(TisuError::InvalidArgument)
+    }
+
+    /// Returns the coordinates of the chunks overlapping the area between the
+    /// given start (inclusive) and end (exclusive).
+    fn chunk_keys(&self, start: Vector2u, end: Vector2u) -> Vec<(u32, u32)> {
+        if start.x >= end.x || start.y >= end.y {
+            return vec![];
+        }
+        let first = (start.x / self.chunk_size.x, start.y / self.chunk_size.y);
+        let last = (
+            (end.x - 1) / self.chunk_size.x,
+            (end.y - 1) / self.chunk_size.y,
+        );
+        (first.0..=last.0)
+            .flat_map(|x| (first.1..=last.1).map(move |y| (x, y)))
+            .collect()
+    }
+
+    /// Checks if any allocated chunk overlaps the area between the given
+    /// start (inclusive) and end (exclusive).
+    fn has_chunks_in(&self, start: Vector2u, end: Vector2u) -> bool {
+        self.chunk_keys(start, end)
+            .iter()
+            .any(|key| self.chunks.contains_key(key))
+    }
+
+    /// Returns the position of the top left field of the chunk with the given
+    /// coordinates.
+    fn chunk_position(&self, key: (u32, u32)) -> Vector2u {
+        Vector2u::new(key.0 * self.chunk_size.x, key.1 * self.chunk_size.y)
+    }
+
+    /// Returns the chunk containing the given position and the position within
+    /// the chunk.
+    fn locate(&self, point: Vector2u) -> ((u32, u32), Vector2u) {
+        let key = (point.x / self.chunk_size.x, point.y / self.chunk_size.y);
+        (key, point - self.chunk_position(key))
+    }
+}
+
+impl<T> MapStorage<T> for ChunkedMap<T>
+where
+    T: Clone + PartialEq,
+{
+    fn size(&self) -> Vector2u {
+        self.size
+    }
+
+    fn get(&self, point: Vector2u) -> Result<&T, TisuError> {
+        if point.x >= self.size.x || point.y >= self.size.y {
+            return Err(TisuError::OutOfBounds);
+        }
+        let (key, chunk_point) = self.locate(point);
+        match self.chunks.get(&key) {
+            Some(chunk) => chunk.get(chunk_point),
+            None => Ok(&self.fill),
+        }
+    }
+
+    /// Sets the field at the given position to the given value. Setting a
+    /// field of a chunk that isn't allocated to the fill value doesn't
+    /// allocate the chunk.
+    fn set(&mut self, point: Vector2u, value: T) -> Result<(), TisuError> {
+        if point.x >= self.size.x || point.y >= self.size.y {
+            return Err(TisuError::OutOfBounds);
+        }
+        let (key, chunk_point) = self.locate(point);
+        if !self.chunks.contains_key(&key) && value == self.fill {
+            return Ok(());
+        }
+        self.chunks
+            .entry(key)
+            .or_insert_with(|| Map::filled(self.chunk_size, self.fill.clone()))
+            .set(chunk_point, value)
+    }
+}
+
+/// Returns the start (inclusive) and the end (exclusive) of the part of the
+/// area between the given start and end that's inside the chunk at the given
+/// position.
+fn overlap(
+    start: Vector2u,
+    end: Vector2u,
+    chunk_position: Vector2u,
+    chunk_size: Vector2u,
+) -> (Vector2u, Vector2u) {
+    (
+        Vector2u::new(start.x.max(chunk_position.x), start.y.max(chunk_position.y)),
+        Vector2u::new(
+            end.x.min(chunk_position.x + chunk_size.x),
+            end.y.min(chunk_position.y + chunk_size.y),
+        ),
+    )
+}
+
+/// Applies the given filter collections to the given chunked map one after
+/// another, like `FilterCollection::apply_with_rng` (each filter collection
+/// uses the given source map). The positions of each filter are visited in
+/// the same order as on a dense map, column by column, with each column split
+/// at chunk borders: for each part, only a slab as wide as the pattern and as
+/// tall as the chunk plus the pattern overhang is copied into a dense map, the
+/// filter is applied to it and the slab is copied back. Parts whose
+/// surroundings aren't allocated are skipped if the pattern doesn't match the
+/// fill value.
+///
+/// As substitutes are applied in the same order as on a dense map, the output
+/// is the same as the one of applying the filters to a dense map, overlapping
+/// substitutes and `destination` pattern matching included. Objects emitted
+/// by the filters are discarded. Returns the statistics of each filter
+/// collection.
+///
+/// # Errors
+///
+/// Returns an error if the maps aren't of the same size, if a filter can't be
+/// applied to the map, or if a filter collection uses markov mode, control
+/// flow properties, a region operation, a connectivity step (which work on
+/// the whole map), distance constraints (whose targets can be outside of the
+/// slab) or is restricted to a region (whose mask would be a dense map).
+pub fn apply_chunked<T, R>(
+    filter_collections: &[FilterCollection<T>],
+    source: &ChunkedMap<T>,
+    destination: &mut ChunkedMap<T>,
+    rng: &mut R,
+) -> Result<Vec<FilterCollectionStats>, TisuError>
+where
+    T: Clone + PartialEq + MaybeSync,
+    R: Rng + ?Sized,
+{
+    if filter_collections.iter().any(|filter_collection| {
+        let properties = &filter_collection.properties;
+        properties.mode == ApplyMode::Markov
+            || properties.when.is_some()
+            || properties.until.is_some()
+            || properties.sentinel
+            || properties.region.is_some()
+            || filter_collection.region_op.is_some()
+            || filter_collection.connectivity.is_some()
+            || filter_collection
+                .filters
+                .iter()
+                .any(|filter| !filter.distance_constraints().is_empty())
+    }) {
+        return Err(TisuError::InvalidArgument);
+    }
+    if source.size() != destination.size() {
+        return Err(TisuError::InvalidMapSize);
+    }
+    let size = source.size();
+    if filter_collections
+        .iter()
+        .flat_map(|filter_collection| &filter_collection.filters)
+        .any(|filter| filter.pattern().size().x > size.x || filter.pattern().size().y > size.y)
+    {
+        return Err(TisuError::InvalidMapSize);
+    }
+
+    let mut result = vec![];
+    for filter_collection in filter_collections {
+        let start = Instant::now();
+        let mut stats = FilterCollectionStats::new(filter_collection.filters.len());
+        for _ in 0..filter_collection.properties.iterations {
+            for (idx, filter) in filter_collection.filters.iter().enumerate() {
+                if filter.properties().ignore {
+                    continue;
+                }
+                let filter_start = Instant::now();
+                let pattern_size = filter.pattern().size();
+                let fill_matches = filter.pattern_matches(
+                    &Map::filled(pattern_size, source.fill().clone()),
+                    Vector2u::default(),
+                );
+                let rows = size.y.div_ceil(source.chunk_size().y);
+                let mut filter_stats = FilterStats::default();
+                for x in 0..=size.x - pattern_size.x {
+                    for row in 0..rows {
+                        let y = row * source.chunk_size().y;
+                        let end = Vector2u::new(
+                            x + pattern_size.x,
+                            (y + source.chunk_size().y + pattern_size.y - 1).min(size.y),
+                        );
+                        if end.y - y < pattern_size.y {
+                            break;
+                        }
+                        // Only the fields of the map the pattern is matched
+                        // against matter
+                        let matched = match filter.properties().pattern_matching {
+                            PatternMatching::Source => source,
+                            PatternMatching::Destination => &*destination,
+                        };
+                        if !fill_matches && !matched.has_chunks_in(Vector2u::new(x, y), end) {
+                            continue;
+                        }
+
+                        let slab = Rect2u::new(Vector2u::new(x, y), end - Vector2u::new(x, y))?;
+                        let source_slab = source.crop(slab)?;
+                        let mut destination_slab = destination.crop(slab)?;
+                        let positions =
+                            Rect2u::new(Vector2u::default(), (1, source.chunk_size().y).into())?;
+                        let slab_stats = filter.apply_in_rect(
+                            &source_slab,
+                            &mut destination_slab,
+                            positions,
+                            rng,
+                        )?;
+                        if slab_stats.changes > 0 {
+                            destination.paste(&destination_slab, slab.position())?;
+                        }
+                        filter_stats += slab_stats;
+                    }
+                }
+                filter_stats.duration = filter_start.elapsed();
+                stats.filters[idx] += filter_stats;
+            }
+        }
+        stats.duration = start.elapsed();
+        result.push(stats);
+    }
+    Ok(result)
+}
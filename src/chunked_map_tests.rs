@@ -0,0 +1,301 @@
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    chunked_map::{self, ChunkedMap},
+    filter::{ApplyMode, Filter, FilterCollection, FilterProperties, PatternMatching},
+    map::Map,
+    map_segmenter,
+    map_storage::MapStorage,
+    rect2::Rect2u,
+    tisu_error::TisuError,
+};
+
+fn create_map() -> Map<u32> {
+    Map::from_data([
+        [0, 0, 0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0],
+        [1, 0, 0, 0, 0, 1, 0],
+        [0, 0, 0, 0, 0, 0, 0],
+    ])
+    .unwrap()
+}
+
+fn create_filter_collections() -> Vec<FilterCollection<u32>> {
+    let stamp = Filter::new(
+        Map::from_data([[1, 9], [9, 9]]).unwrap(),
+        Map::from_data([[2, 3], [4, 5]]).unwrap(),
+        9,
+    )
+    .unwrap();
+    // Substitutes of neighboring matches overlap
+    let paint = Filter::new_with_properties(
+        Map::from_data([[0, 0]]).unwrap(),
+        Map::from_data([[7, 8]]).unwrap(),
+        9,
+        FilterProperties {
+            probability: 0.5,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    vec![
+        FilterCollection::new(&[stamp]),
+        FilterCollection::new(&[paint]),
+    ]
+}
+
+#[test]
+fn test_new_failure() {
+    assert_eq!(
+        ChunkedMap::new((4, 4).into(), (0, 2).into(), 0).err(),
+        Some(TisuError::InvalidArgument)
+    );
+}
+
+#[test]
+fn test_get_set() {
+    let mut map = ChunkedMap::new((5, 5).into(), (2, 2).into(), 0).unwrap();
+
+    // Setting the fill value doesn't allocate chunks
+    assert_eq!(map.set((3, 3).into(), 0), Ok(()));
+    assert_eq!(map.chunk_count(), 0);
+    assert_eq!(map.set((3, 3).into(), 7), Ok(()));
+    assert_eq!(map.chunk_count(), 1);
+
+    assert_eq!(map.get((3, 3).into()), Ok(&7));
+    assert_eq!(map.get((2, 2).into()), Ok(&0));
+    assert_eq!(map.get((0, 0).into()), Ok(&0));
+    assert_eq!(map.get((5, 0).into()), Err(TisuError::OutOfBounds));
+    assert_eq!(map.set((0, 5).into(), 1), Err(TisuError::OutOfBounds));
+}
+
+#[test]
+fn test_from_map() {
+    let map = create_map();
+
+    let chunked = ChunkedMap::from_map(&map, (3, 2).into(), 0).unwrap();
+
+    // The chunks of the bottom row and the right column only hold the fill value
+    assert_eq!(chunked.chunk_count(), 4);
+    assert_eq!(chunked.size(), map.size());
+    assert_eq!(chunked.to_map(), map);
+}
+
+#[test]
+fn test_chunk_rects() {
+    let map = ChunkedMap::new((5, 3).into(), (3, 2).into(), 0).unwrap();
+
+    assert_eq!(
+        map.chunk_rects(),
+        [
+            Rect2u::try_from((0, 0, 3, 2)).unwrap(),
+            Rect2u::try_from((0, 2, 3, 1)).unwrap(),
+            Rect2u::try_from((3, 0, 2, 2)).unwrap(),
+            Rect2u::try_from((3, 2, 2, 1)).unwrap(),
+        ]
+    );
+}
+
+#[test]
+fn test_crop() {
+    let chunked = ChunkedMap::from_map(&create_map(), (3, 2).into(), 0).unwrap();
+
+    assert_eq!(
+        chunked.crop(Rect2u::try_from((1, 0, 4, 2)).unwrap()),
+        Map::from_data([[0, 0, 0, 1], [0, 1, 0, 0]])
+    );
+    assert_eq!(
+        chunked.crop(Rect2u::try_from((4, 4, 4, 1)).unwrap()),
+        Err(TisuError::InvalidArgument)
+    );
+}
+
+#[test]
+fn test_paste() {
+    let mut chunked = ChunkedMap::new((6, 4).into(), (2, 2).into(), 0).unwrap();
+
+    let result = chunked.paste(
+        &Map::from_data([[0, 0, 0], [0, 2, 2]]).unwrap(),
+        (0, 1).into(),
+    );
+
+    // The chunks of the top row only get fill values, so they aren't allocated
+    assert_eq!(result, Ok(2));
+    assert_eq!(chunked.chunk_count(), 2);
+    assert_eq!(
+        chunked.to_map(),
+        Map::from_data([
+            [0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0],
+            [0, 2, 2, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0],
+        ])
+        .unwrap()
+    );
+    assert_eq!(
+        chunked.paste(&Map::from_data([[1, 1]]).unwrap(), (5, 0).into()),
+        Err(TisuError::InvalidArgument)
+    );
+}
+
+#[test]
+fn test_compact() {
+    let mut chunked = ChunkedMap::from_map(&create_map(), (3, 2).into(), 0).unwrap();
+    chunked.set((0, 3).into(), 0).unwrap();
+
+    chunked.compact();
+
+    assert_eq!(chunked.chunk_count(), 3);
+    assert_eq!(chunked.get((0, 3).into()), Ok(&0));
+}
+
+#[test]
+fn test_apply_filter() {
+    let source = ChunkedMap::from_map(&create_map(), (3, 2).into(), 0).unwrap();
+    let mut destination = source.clone();
+    let filter = Filter::new(
+        Map::from_data([[1]]).unwrap(),
+        Map::from_data([[8]]).unwrap(),
+        9,
+    )
+    .unwrap();
+
+    let stats = filter.apply(&source, &mut destination).unwrap();
+
+    assert_eq!(stats.matches, 4);
+    assert_eq!(destination.get((2, 1).into()), Ok(&8));
+    assert_eq!(destination.get((5, 3).into()), Ok(&8));
+}
+
+#[test]
+fn test_extract_segments() {
+    let chunked = ChunkedMap::from_map(&create_map(), (3, 2).into(), 0).unwrap();
+
+    assert_eq!(
+        map_segmenter::extract_segments(&chunked, &0),
+        map_segmenter::extract_segments(&create_map(), &0)
+    );
+}
+
+#[test]
+fn test_apply_chunked() {
+    let map = create_map();
+    let filter_collections = create_filter_collections();
+    let mut expected = map.clone();
+    let mut rng = StdRng::seed_from_u64(0);
+    for filter_collection in &filter_collections {
+        filter_collection
+            .apply_with_rng(&map, &mut expected, &mut rng)
+            .unwrap();
+    }
+    let source = ChunkedMap::from_map(&map, (3, 2).into(), 0).unwrap();
+    let mut destination = source.clone();
+
+    let result = chunked_map::apply_chunked(
+        &filter_collections,
+        &source,
+        &mut destination,
+        &mut StdRng::seed_from_u64(0),
+    );
+
+    // Substitutes crossing chunk borders and overlapping substitutes are
+    // applied like on a dense map
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap()[0].filters[0].matches, 4);
+    assert_eq!(destination.to_map(), expected);
+}
+
+#[test]
+fn test_apply_chunked_destination() {
+    let map = create_map();
+    let mut filter_collections = create_filter_collections();
+    // Each substitute affects the matches of the following positions
+    filter_collections[1].filters[0].set_properties(FilterProperties {
+        probability: 0.5,
+        pattern_matching: PatternMatching::Destination,
+        ..Default::default()
+    });
+    let mut expected = map.clone();
+    let mut rng = StdRng::seed_from_u64(1);
+    for filter_collection in &filter_collections {
+        filter_collection
+            .apply_with_rng(&map, &mut expected, &mut rng)
+            .unwrap();
+    }
+    let source = ChunkedMap::from_map(&map, (3, 2).into(), 0).unwrap();
+    let mut destination = source.clone();
+
+    let result = chunked_map::apply_chunked(
+        &filter_collections,
+        &source,
+        &mut destination,
+        &mut StdRng::seed_from_u64(1),
+    );
+
+    assert!(result.is_ok());
+    assert_eq!(destination.to_map(), expected);
+}
+
+#[test]
+fn test_apply_chunked_sparse() {
+    let mut source = ChunkedMap::new((4096, 4096).into(), (64, 64).into(), 0).unwrap();
+    source.set((100, 100).into(), 1).unwrap();
+    let mut destination = source.clone();
+    let filter = Filter::new(
+        Map::from_data([[1]]).unwrap(),
+        Map::from_data([[2]]).unwrap(),
+        9,
+    )
+    .unwrap();
+
+    let result = chunked_map::apply_chunked(
+        &[FilterCollection::new(&[filter])],
+        &source,
+        &mut destination,
+        &mut StdRng::seed_from_u64(0),
+    );
+
+    // Only the allocated chunk is searched, as the pattern doesn't match the
+    // fill value
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap()[0].filters[0].positions_tested, 64 * 64);
+    assert_eq!(destination.chunk_count(), 1);
+    assert_eq!(destination.get((100, 100).into()), Ok(&2));
+}
+
+#[test]
+fn test_apply_chunked_failure() {
+    let source = ChunkedMap::from_map(&create_map(), (3, 2).into(), 0).unwrap();
+    let mut filter_collections = create_filter_collections();
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let mut destination = ChunkedMap::new((7, 4).into(), (3, 2).into(), 0).unwrap();
+    assert_eq!(
+        chunked_map::apply_chunked(&filter_collections, &source, &mut destination, &mut rng),
+        Err(TisuError::InvalidMapSize)
+    );
+
+    let mut destination = source.clone();
+    filter_collections[1].properties.mode = ApplyMode::Markov;
+    assert_eq!(
+        chunked_map::apply_chunked(&filter_collections, &source, &mut destination, &mut rng),
+        Err(TisuError::InvalidArgument)
+    );
+    filter_collections[1].properties.mode = ApplyMode::All;
+    filter_collections[1].properties.region = Some(String::from("!Spawn"));
+    assert_eq!(
+        chunked_map::apply_chunked(&filter_collections, &source, &mut destination, &mut rng),
+        Err(TisuError::InvalidArgument)
+    );
+    assert_eq!(destination, source);
+}
+
+#[test]
+fn test_field_count() {
+    // The number of fields of a huge map exceeds u32
+    let map = ChunkedMap::new((100_000, 100_000).into(), (256, 256).into(), 0).unwrap();
+
+    assert_eq!(map.chunk_rects().len(), 391 * 391);
+    assert_eq!(map.get((99_999, 99_999).into()), Ok(&0));
+}
@@ -0,0 +1,229 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::apply_stats::FilterStats;
+use crate::map::Map;
+use crate::regions::{self, Neighborhood};
+use crate::tisu_error::TisuError;
+use crate::vector2::Vector2u;
+
+/// Checks if the fields that satisfy the given predicate form at most one
+/// connected region.
+pub fn is_connected<T, W>(map: &Map<T>, neighborhood: Neighborhood, walkable: W) -> bool
+where
+    W: Fn(&T) -> bool,
+{
+    regions::label(map, neighborhood, walkable).regions.len() <= 1
+}
+
+/// Finds the shortest paths through breakable fields that connect all the
+/// walkable fields into one region. Starting with the region of the given
+/// start field (or the first region, row by row), the nearest region that
+/// isn't connected yet is joined by the path with the fewest breakable
+/// fields, until all regions are connected. Returns the breakable fields of
+/// each path, which become walkable once carved. Paths are listed in the
+/// order they're found, starting next to the connected fields.
+///
+/// # Errors
+///
+/// Returns an error if the start field isn't walkable or if a region can't be
+/// reached through breakable fields.
+pub fn find_paths<T, W, B>(
+    map: &Map<T>,
+    neighborhood: Neighborhood,
+    start: Option<Vector2u>,
+    walkable: W,
+    breakable: B,
+) -> Result<Vec<Vec<Vector2u>>, TisuError>
+where
+    W: Fn(&T) -> bool,
+    B: Fn(&T) -> bool,
+{
+    let size = map.size();
+    let labels = regions::label(map, neighborhood, walkable);
+    if labels.regions.is_empty() {
+        return Ok(vec![]);
+    }
+    let start_label = match start {
+        Some(start) => labels
+            .labels
+            .get(start)?
+            .ok_or(TisuError::InvalidArgument)?,
+        None => 0,
+    };
+
+    let mut connected = vec![false; labels.regions.len()];
+    connected[start_label] = true;
+    let mut carved = vec![false; map.data().len()];
+    let mut paths = vec![];
+    while connected.contains(&false) {
+        // Search breakable fields, starting from all the connected fields
+        let is_source = |idx: usize| {
+            carved[idx] || labels.labels.data()[idx].is_some_and(|label| connected[label])
+        };
+        let mut visited: Vec<bool> = (0..map.data().len()).map(is_source).collect();
+        let mut previous = vec![None; map.data().len()];
+        let mut queue: VecDeque<usize> =
+            (0..map.data().len()).filter(|idx| visited[*idx]).collect();
+        let mut end = None;
+        'search: while let Some(idx) = queue.pop_front() {
+            for neighbor in neighborhood.neighbors(map.field_position(idx), size) {
+                let neighbor_idx = map.field_index(neighbor);
+                if visited[neighbor_idx] {
+                    continue;
+                }
+                visited[neighbor_idx] = true;
+                previous[neighbor_idx] = Some(idx);
+                if labels.labels.data()[neighbor_idx].is_some() {
+                    end = Some(neighbor_idx);
+                    break 'search;
+                }
+                if breakable(&map.data()[neighbor_idx]) {
+                    queue.push_back(neighbor_idx);
+                }
+            }
+        }
+
+        let end = end.ok_or(TisuError::NotFound)?;
+        let mut path = vec![];
+        let mut idx = previous[end];
+        while let Some(path_idx) = idx.filter(|idx| !is_source(*idx)) {
+            path.push(map.field_position(path_idx));
+            idx = previous[path_idx];
+        }
+        path.reverse();
+        for point in &path {
+            carved[map.field_index(*point)] = true;
+        }
+        if let Some(label) = labels.labels.data()[end] {
+            connected[label] = true;
+        }
+        // Regions next to earlier paths are connected without carving
+        if !path.is_empty() {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+/// A step that guarantees that the walkable fields of a map form one
+/// connected region, applied as a step of a filter sequence in place of
+/// filters (see `FilterCollection::connectivity`). Start and goal tiles are
+/// walkable too, and at least one of each must be on the map if any are
+/// given. If the walkable fields aren't connected, the shortest paths through
+/// breakable fields are carved with the carve tile (see `find_paths`), or the
+/// step fails if there's no carve tile.
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Connectivity<T> {
+    /// Neighborhood that defines which fields are connected
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub neighborhood: Neighborhood,
+    /// Tiles of the walkable fields
+    pub walkable: Vec<T>,
+    /// Tiles of the fields that can be carved
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub breakable: Vec<T>,
+    /// Tiles of the start fields, the regions are connected to the first one
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub start: Vec<T>,
+    /// Tiles of the goal fields
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub goal: Vec<T>,
+    /// Tile of the carved fields
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub carve: Option<T>,
+}
+
+impl<T> Connectivity<T> {
+    /// Creates a connectivity check of the given walkable tiles, with a
+    /// 4-neighborhood, without start and goal tiles and without carving.
+    pub fn new(walkable: Vec<T>) -> Self {
+        Self {
+            neighborhood: Neighborhood::Four,
+            walkable,
+            breakable: vec![],
+            start: vec![],
+            goal: vec![],
+            carve: None,
+        }
+    }
+
+    /// Connects the walkable fields of the given map, carving only the
+    /// breakable fields set in the mask (if given). Returns statistics where
+    /// every field counts as a tested position and every carved path as a
+    /// match.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mask has a different size, if start or goal
+    /// tiles are given but missing from the map, or if the walkable fields
+    /// aren't connected and can't be (or there's no carve tile).
+    pub fn apply(
+        &self,
+        map: &mut Map<T>,
+        mask: Option<&Map<bool>>,
+    ) -> Result<FilterStats, TisuError>
+    where
+        T: Clone + PartialEq,
+    {
+        if mask.is_some_and(|mask| mask.size() != map.size()) {
+            return Err(TisuError::InvalidMapSize);
+        }
+
+        let start_time = Instant::now();
+        let find = |tiles: &[T]| -> Result<Option<Vector2u>, TisuError> {
+            if tiles.is_empty() {
+                return Ok(None);
+            }
+            let idx = map
+                .data()
+                .iter()
+                .position(|field| tiles.contains(field))
+                .ok_or(TisuError::NotFound)?;
+            Ok(Some(map.field_position(idx)))
+        };
+        let start = find(&self.start)?;
+        find(&self.goal)?;
+
+        let mut candidates =
+            map.map(|field| (self.is_walkable(field), self.breakable.contains(field)));
+        if let Some(mask) = mask {
+            for (candidate, in_mask) in candidates.mut_data().iter_mut().zip(mask.data()) {
+                candidate.1 &= *in_mask;
+            }
+        }
+        let paths = find_paths(
+            &candidates,
+            self.neighborhood,
+            start,
+            |(walkable, _)| *walkable,
+            |(_, breakable)| *breakable,
+        )?;
+
+        let mut stats = FilterStats {
+            positions_tested: map.data().len(),
+            matches: paths.len(),
+            ..Default::default()
+        };
+        if !paths.is_empty() {
+            let carve = self.carve.as_ref().ok_or(TisuError::NotFound)?;
+            for point in paths.iter().flatten() {
+                map.set(*point, carve.clone())?;
+                stats.changes += 1;
+            }
+        }
+        stats.duration = start_time.elapsed();
+        Ok(stats)
+    }
+
+    fn is_walkable(&self, field: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.walkable.contains(field) || self.start.contains(field) || self.goal.contains(field)
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{
+    connectivity::{self, Connectivity},
+    filter::FilterCollection,
+    map::Map,
+    regions::Neighborhood,
+    tisu_error::TisuError,
+    vector2::Vector2u,
+};
+
+// 0 - wall (breakable), 1 - floor (walkable), 2 - rock, 3 - start, 4 - goal,
+// 5 - carved floor
+fn create_map() -> Map<u32> {
+    Map::from_data([[3, 1, 0, 0, 1, 1], [1, 1, 0, 0, 0, 0], [2, 2, 2, 0, 0, 4]]).unwrap()
+}
+
+fn create_connectivity() -> Connectivity<u32> {
+    Connectivity {
+        breakable: vec![0],
+        start: vec![3],
+        goal: vec![4],
+        carve: Some(5),
+        ..Connectivity::new(vec![1])
+    }
+}
+
+fn is_walkable(field: &u32) -> bool {
+    [1, 3, 4, 5].contains(field)
+}
+
+#[test]
+fn test_is_connected() {
+    let map = create_map();
+
+    assert!(!connectivity::is_connected(
+        &map,
+        Neighborhood::Four,
+        is_walkable
+    ));
+    assert!(connectivity::is_connected(
+        &map,
+        Neighborhood::Four,
+        |field| { *field == 2 }
+    ));
+}
+
+#[test]
+fn test_find_paths() {
+    let result = connectivity::find_paths(
+        &create_map(),
+        Neighborhood::Four,
+        Some(Vector2u::new(0, 0)),
+        is_walkable,
+        |field| *field == 0,
+    );
+
+    assert_eq!(
+        result,
+        Ok(vec![
+            vec![(2, 0).into(), (3, 0).into()],
+            vec![(5, 1).into()],
+        ])
+    );
+}
+
+#[test]
+fn test_find_paths_eight_neighborhood() {
+    let map = Map::from_data([[1, 2, 2], [2, 0, 2], [2, 2, 1]]).unwrap();
+
+    let result = connectivity::find_paths(
+        &map,
+        Neighborhood::Eight,
+        None,
+        |field| *field == 1,
+        |field| *field == 0,
+    );
+
+    assert_eq!(result, Ok(vec![vec![(1, 1).into()]]));
+}
+
+#[test]
+fn test_find_paths_connected() {
+    let map = Map::from_data([[1, 1, 0], [0, 1, 1]]).unwrap();
+
+    let result = connectivity::find_paths(
+        &map,
+        Neighborhood::Four,
+        None,
+        |field| *field == 1,
+        |field| *field == 0,
+    );
+
+    assert_eq!(result, Ok(vec![]));
+}
+
+#[test]
+fn test_find_paths_failure() {
+    let map = create_map();
+
+    let result = connectivity::find_paths(&map, Neighborhood::Four, None, is_walkable, |field| {
+        *field == 5
+    });
+    assert_eq!(result, Err(TisuError::NotFound));
+
+    let result = connectivity::find_paths(
+        &map,
+        Neighborhood::Four,
+        Some(Vector2u::new(2, 0)),
+        is_walkable,
+        |field| *field == 0,
+    );
+    assert_eq!(result, Err(TisuError::InvalidArgument));
+}
+
+#[test]
+fn test_apply() {
+    let mut map = create_map();
+
+    let result = create_connectivity().apply(&mut map, None);
+
+    let stats = result.unwrap();
+    assert_eq!(stats.positions_tested, 18);
+    assert_eq!(stats.matches, 2);
+    assert_eq!(stats.changes, 3);
+    assert_eq!(
+        map,
+        Map::from_data([[3, 1, 5, 5, 1, 1], [1, 1, 0, 0, 0, 5], [2, 2, 2, 0, 0, 4]]).unwrap()
+    );
+}
+
+#[test]
+fn test_apply_with_mask() {
+    let mut map = create_map();
+    // Only the walls of the bottom rows can be carved
+    let mask = Map::from_data([
+        [false, false, false, false, false, false],
+        [false, false, true, true, true, true],
+        [false, false, false, true, true, true],
+    ])
+    .unwrap();
+
+    let result = create_connectivity().apply(&mut map, Some(&mask));
+
+    assert_eq!(result.unwrap().changes, 4);
+    assert_eq!(
+        map,
+        Map::from_data([[3, 1, 0, 0, 1, 1], [1, 1, 5, 5, 5, 5], [2, 2, 2, 0, 0, 4]]).unwrap()
+    );
+}
+
+#[test]
+fn test_apply_check() {
+    let mut map = create_map();
+    let connectivity = Connectivity {
+        carve: None,
+        ..create_connectivity()
+    };
+
+    // Without a carve tile, disconnected maps are a failure
+    assert_eq!(connectivity.apply(&mut map, None), Err(TisuError::NotFound));
+    assert_eq!(map, create_map());
+
+    let mut map = Map::from_data([[3, 1, 4], [0, 0, 0]]).unwrap();
+    let result = connectivity.apply(&mut map, None);
+    assert_eq!(result.unwrap().changes, 0);
+}
+
+#[test]
+fn test_apply_failure() {
+    // The goal is missing
+    let mut map = Map::from_data([[3, 1, 1]]).unwrap();
+    assert_eq!(
+        create_connectivity().apply(&mut map, None),
+        Err(TisuError::NotFound)
+    );
+
+    let mut map = create_map();
+    let mask = Map::new(Vector2u::new(2, 2));
+    assert_eq!(
+        create_connectivity().apply(&mut map, Some(&mask)),
+        Err(TisuError::InvalidMapSize)
+    );
+}
+
+#[test]
+fn test_apply_filter_collection_connectivity() {
+    let input = create_map();
+    let mut output = input.clone();
+    let mut filter_collection = FilterCollection::<u32>::new(&[]);
+    filter_collection.connectivity = Some(create_connectivity());
+
+    let result = filter_collection.apply(&input, &mut output).unwrap();
+
+    assert_eq!(result.filters.len(), 1);
+    assert_eq!(result.changes(), 3);
+    assert_eq!(output.get((5, 1).into()), Ok(&5));
+}
@@ -0,0 +1,224 @@
+use crate::map::Map;
+use crate::map_storage::MapStorage;
+use crate::vector2::{Vector2, Vector2u};
+
+/// Metric of distances between fields
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum DistanceMetric {
+    /// Number of horizontal and vertical steps (BFS distance in a
+    /// 4-neighborhood)
+    #[default]
+    Manhattan,
+    /// Number of horizontal, vertical and diagonal steps (BFS distance in an
+    /// 8-neighborhood)
+    Chebyshev,
+    /// 3-4 chamfer distance, which approximates the Euclidean distance (a
+    /// diagonal step counts as 4/3)
+    Chamfer,
+}
+
+impl TryFrom<&String> for DistanceMetric {
+    type Error = ();
+    fn try_from(value: &String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "manhattan" => Ok(DistanceMetric::Manhattan),
+            "chebyshev" => Ok(DistanceMetric::Chebyshev),
+            "chamfer" => Ok(DistanceMetric::Chamfer),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<&DistanceMetric> for String {
+    fn from(value: &DistanceMetric) -> Self {
+        match value {
+            DistanceMetric::Manhattan => String::from("manhattan"),
+            DistanceMetric::Chebyshev => String::from("chebyshev"),
+            DistanceMetric::Chamfer => String::from("chamfer"),
+        }
+    }
+}
+
+impl DistanceMetric {
+    /// Returns the distance between two fields that are the given number of
+    /// fields apart horizontally and vertically.
+    pub fn distance(self, dx: u32, dy: u32) -> f32 {
+        let (orthogonal, diagonal, scale) = self.weights();
+        let (short, long) = (dx.min(dy), dx.max(dy));
+        let cost = diagonal * short + orthogonal * (long - short);
+        cost as f32 / scale as f32
+    }
+
+    /// Returns the integer costs of an orthogonal and a diagonal step, and
+    /// the cost of a distance of 1.
+    fn weights(self) -> (u32, u32, u32) {
+        match self {
+            DistanceMetric::Manhattan => (1, 2, 1),
+            DistanceMetric::Chebyshev => (1, 1, 1),
+            DistanceMetric::Chamfer => (3, 4, 3),
+        }
+    }
+}
+
+/// Computes the distance from each field of the given map to the nearest
+/// field that satisfies the given predicate (0 for those fields, infinite if
+/// there are none).
+pub fn distance_field<T, P>(map: &Map<T>, metric: DistanceMetric, predicate: P) -> Map<f32>
+where
+    P: Fn(&T) -> bool,
+{
+    let size = map.size();
+    let (orthogonal, diagonal, scale) = metric.weights();
+    let mut costs: Vec<u32> = map
+        .data()
+        .iter()
+        .map(|field| if predicate(field) { 0 } else { u32::MAX })
+        .collect();
+    let index = |x: u32, y: u32| map.field_index(Vector2u::new(x, y));
+    let relax = |costs: &mut [u32], x: u32, y: u32, dx: i64, dy: i64, weight: u32| {
+        let (Ok(nx), Ok(ny)) = (u32::try_from(x as i64 + dx), u32::try_from(y as i64 + dy)) else {
+            return;
+        };
+        if nx < size.x && ny < size.y {
+            let candidate = costs[index(nx, ny)].saturating_add(weight);
+            let cost = &mut costs[index(x, y)];
+            *cost = (*cost).min(candidate);
+        }
+    };
+
+    // Two raster passes propagate the costs from the fields visited before
+    for y in 0..size.y {
+        for x in 0..size.x {
+            relax(&mut costs, x, y, -1, 0, orthogonal);
+            relax(&mut costs, x, y, 0, -1, orthogonal);
+            relax(&mut costs, x, y, -1, -1, diagonal);
+            relax(&mut costs, x, y, 1, -1, diagonal);
+        }
+    }
+    for y in (0..size.y).rev() {
+        for x in (0..size.x).rev() {
+            relax(&mut costs, x, y, 1, 0, orthogonal);
+            relax(&mut costs, x, y, 0, 1, orthogonal);
+            relax(&mut costs, x, y, 1, 1, diagonal);
+            relax(&mut costs, x, y, -1, 1, diagonal);
+        }
+    }
+
+    let mut result = Map::new(size);
+    for (distance, cost) in result.mut_data().iter_mut().zip(costs) {
+        *distance = if cost == u32::MAX {
+            f32::INFINITY
+        } else {
+            cost as f32 / scale as f32
+        };
+    }
+    result
+}
+
+/// Returns the distance from the nearest field of the given area (at the
+/// given position and of the given size) to the nearest field of the map that
+/// satisfies the given predicate, if it's at most the given limit. Only the
+/// fields within the limit are searched, so this is cheaper than computing the
+/// whole distance field for small limits.
+pub fn nearest_distance<T, M, P>(
+    map: &M,
+    position: Vector2u,
+    size: Vector2u,
+    metric: DistanceMetric,
+    predicate: P,
+    limit: f32,
+) -> Option<f32>
+where
+    M: MapStorage<T> + ?Sized,
+    P: Fn(&T) -> bool,
+{
+    if limit < 0.0 {
+        return None;
+    }
+    // No metric is shorter than the Chebyshev distance
+    let radius = limit.floor().min(u32::MAX as f32) as u32;
+    let start = Vector2::new(
+        position.x.saturating_sub(radius),
+        position.y.saturating_sub(radius),
+    );
+    let end = Vector2::new(
+        (position.x + size.x)
+            .saturating_add(radius)
+            .min(map.size().x),
+        (position.y + size.y)
+            .saturating_add(radius)
+            .min(map.size().y),
+    );
+    let offset = |value: u32, start: u32, length: u32| {
+        if value < start {
+            start - value
+        } else {
+            value.saturating_sub(start + length - 1)
+        }
+    };
+
+    let mut nearest: Option<f32> = None;
+    for y in start.y..end.y {
+        for x in start.x..end.x {
+            let Ok(field) = map.get(Vector2::new(x, y)) else {
+                continue;
+            };
+            if !predicate(field) {
+                continue;
+            }
+            let distance = metric.distance(
+                offset(x, position.x, size.x.max(1)),
+                offset(y, position.y, size.y.max(1)),
+            );
+            if distance <= limit && nearest.is_none_or(|nearest| distance < nearest) {
+                nearest = Some(distance);
+            }
+        }
+    }
+    nearest
+}
+
+/// A condition on the distance from a pattern match to the nearest field with
+/// any of the given tiles (e.g. the tiles of a class). The distance is
+/// measured from the nearest field covered by the pattern.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct DistanceConstraint<T> {
+    /// Tiles the distance is measured to
+    pub tiles: Vec<T>,
+    /// Minimum distance (if any)
+    pub min: Option<f32>,
+    /// Maximum distance (if any), a map without any of the tiles never
+    /// satisfies it
+    pub max: Option<f32>,
+}
+
+impl<T> DistanceConstraint<T> {
+    /// Checks if the area at the given position and of the given size
+    /// satisfies the constraint.
+    pub fn is_satisfied<M>(
+        &self,
+        map: &M,
+        position: Vector2u,
+        size: Vector2u,
+        metric: DistanceMetric,
+    ) -> bool
+    where
+        T: PartialEq,
+        M: MapStorage<T> + ?Sized,
+    {
+        let is_target = |field: &T| self.tiles.contains(field);
+        let min_satisfied = self.min.is_none_or(|min| {
+            nearest_distance(map, position, size, metric, is_target, min)
+                .is_none_or(|distance| distance >= min)
+        });
+        min_satisfied
+            && self.max.is_none_or(|max| {
+                nearest_distance(map, position, size, metric, is_target, max).is_some()
+            })
+    }
+}
@@ -0,0 +1,215 @@
+use crate::{
+    distance::{self, DistanceConstraint, DistanceMetric},
+    filter::{Filter, FilterProperties, PatternMatching},
+    map::Map,
+    vector2::Vector2u,
+};
+
+fn create_map() -> Map<u32> {
+    Map::from_data([[1, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0]]).unwrap()
+}
+
+fn create_torch_filter(pattern_matching: PatternMatching) -> Filter<u32> {
+    let mut filter = Filter::new_with_properties(
+        Map::from_data([[0]]).unwrap(),
+        Map::from_data([[1]]).unwrap(),
+        9,
+        FilterProperties {
+            pattern_matching,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    // Torches are at least 3 fields apart and at most 5 fields from a wall
+    filter.set_distance_constraints(vec![
+        DistanceConstraint {
+            tiles: vec![1],
+            min: Some(3.0),
+            max: None,
+        },
+        DistanceConstraint {
+            tiles: vec![2],
+            min: None,
+            max: Some(5.0),
+        },
+    ]);
+    filter
+}
+
+#[test]
+fn test_distance_metric_conversion() {
+    assert_eq!(
+        DistanceMetric::try_from(&String::from("chamfer")),
+        Ok(DistanceMetric::Chamfer)
+    );
+    assert_eq!(
+        DistanceMetric::try_from(&String::from("euclidean")),
+        Err(())
+    );
+    assert_eq!(String::from(&DistanceMetric::Chebyshev), "chebyshev");
+}
+
+#[test]
+fn test_distance() {
+    assert_eq!(DistanceMetric::Manhattan.distance(2, 3), 5.0);
+    assert_eq!(DistanceMetric::Chebyshev.distance(2, 3), 3.0);
+    assert_eq!(DistanceMetric::Chamfer.distance(2, 3), 11.0 / 3.0);
+}
+
+#[test]
+fn test_distance_field() {
+    let map = create_map();
+
+    assert_eq!(
+        distance::distance_field(&map, DistanceMetric::Manhattan, |field| *field == 1),
+        Map::from_data([
+            [0.0, 1.0, 2.0, 3.0],
+            [1.0, 2.0, 3.0, 4.0],
+            [2.0, 3.0, 4.0, 5.0],
+        ])
+        .unwrap()
+    );
+    assert_eq!(
+        distance::distance_field(&map, DistanceMetric::Chebyshev, |field| *field == 1),
+        Map::from_data([
+            [0.0, 1.0, 2.0, 3.0],
+            [1.0, 1.0, 2.0, 3.0],
+            [2.0, 2.0, 2.0, 3.0],
+        ])
+        .unwrap()
+    );
+    assert_eq!(
+        distance::distance_field(&map, DistanceMetric::Chamfer, |field| *field == 1),
+        Map::from_data([
+            [0.0, 1.0, 2.0, 3.0],
+            [1.0, 4.0 / 3.0, 7.0 / 3.0, 10.0 / 3.0],
+            [2.0, 7.0 / 3.0, 8.0 / 3.0, 11.0 / 3.0],
+        ])
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_distance_field_without_targets() {
+    let field = distance::distance_field(&create_map(), DistanceMetric::Manhattan, |field| {
+        *field == 2
+    });
+
+    assert!(field
+        .data()
+        .iter()
+        .all(|distance| *distance == f32::INFINITY));
+}
+
+#[test]
+fn test_nearest_distance() {
+    let map = create_map();
+    let is_target = |field: &u32| *field == 1;
+    let position = Vector2u::new(2, 1);
+    let size = Vector2u::new(2, 1);
+
+    // Measured from the nearest field of the area
+    assert_eq!(
+        distance::nearest_distance(
+            &map,
+            position,
+            size,
+            DistanceMetric::Manhattan,
+            is_target,
+            3.0
+        ),
+        Some(3.0)
+    );
+    assert_eq!(
+        distance::nearest_distance(
+            &map,
+            position,
+            size,
+            DistanceMetric::Manhattan,
+            is_target,
+            2.5
+        ),
+        None
+    );
+    assert_eq!(
+        distance::nearest_distance(
+            &map,
+            position,
+            size,
+            DistanceMetric::Chebyshev,
+            is_target,
+            3.0
+        ),
+        Some(2.0)
+    );
+    assert_eq!(
+        distance::nearest_distance(
+            &map,
+            Vector2u::new(0, 0),
+            size,
+            DistanceMetric::Chebyshev,
+            is_target,
+            0.0
+        ),
+        Some(0.0)
+    );
+}
+
+#[test]
+fn test_distance_constraint() {
+    let map = create_map();
+    let size = Vector2u::new(1, 1);
+    let constraint = DistanceConstraint {
+        tiles: vec![1],
+        min: Some(2.0),
+        max: Some(3.0),
+    };
+
+    let satisfied =
+        |x, y| constraint.is_satisfied(&map, Vector2u::new(x, y), size, DistanceMetric::Manhattan);
+    assert!(!satisfied(1, 0));
+    assert!(satisfied(2, 0));
+    assert!(satisfied(1, 2));
+    assert!(!satisfied(3, 1));
+
+    // A maximum distance is never satisfied without targets
+    let constraint = DistanceConstraint {
+        tiles: vec![2],
+        min: None,
+        max: Some(10.0),
+    };
+    assert!(!constraint.is_satisfied(&map, Vector2u::new(0, 0), size, DistanceMetric::Manhattan));
+}
+
+#[test]
+fn test_pattern_matches_distance() {
+    let map = Map::from_data([[2, 0, 0, 0, 0, 0, 0, 0]]).unwrap();
+    let filter = create_torch_filter(PatternMatching::Source);
+
+    assert!(filter.pattern_matches(&map, Vector2u::new(5, 0)));
+    assert!(!filter.pattern_matches(&map, Vector2u::new(6, 0)));
+}
+
+#[test]
+fn test_apply_distance_source() {
+    let map = Map::from_data([[2, 0, 0, 0, 0, 0, 0, 0]]).unwrap();
+    let mut output = map.clone();
+    let filter = create_torch_filter(PatternMatching::Source);
+
+    filter.apply(&map, &mut output).unwrap();
+
+    // Distances are measured on the source, which has no torches
+    assert_eq!(output, Map::from_data([[2, 1, 1, 1, 1, 1, 0, 0]]).unwrap());
+}
+
+#[test]
+fn test_apply_distance_destination() {
+    let map = Map::from_data([[2, 0, 0, 0, 0, 0, 0, 0]]).unwrap();
+    let mut output = map.clone();
+    let filter = create_torch_filter(PatternMatching::Destination);
+
+    filter.apply(&map, &mut output).unwrap();
+
+    // Each torch keeps the next ones away
+    assert_eq!(output, Map::from_data([[2, 1, 0, 0, 1, 0, 0, 0]]).unwrap());
+}
@@ -1,14 +1,29 @@
+use std::time::Instant;
+
 use rand::Rng;
 
 use tiled::{Properties, PropertyValue};
 
+use crate::apply_stats::{FilterCollectionStats, FilterStats};
+use crate::connectivity::Connectivity;
+use crate::distance::{DistanceConstraint, DistanceMetric};
 use crate::map::Map;
+use crate::map_object::MapObject;
+use crate::map_storage::MapStorage;
+use crate::rect2::Rect2u;
+use crate::regions::RegionOp;
+use crate::tile_index::TileIndex;
 use crate::tisu_error::TisuError;
 use crate::vector2::Vector2u;
 
 /// Filter property that defines where the filter will be applied (source or
 /// destination)
 #[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum PatternMatching {
     #[default]
     Source,
@@ -26,8 +41,125 @@ impl TryFrom<&String> for PatternMatching {
     }
 }
 
+impl From<&PatternMatching> for String {
+    fn from(value: &PatternMatching) -> Self {
+        match value {
+            PatternMatching::Source => String::from("source"),
+            PatternMatching::Destination => String::from("destination"),
+        }
+    }
+}
+
+/// Filter property that defines whether pattern matching compares the
+/// orientations of fields (e.g. flipped tiles)
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum MatchFlips {
+    #[default]
+    Exact,
+    Ignore,
+}
+
+impl TryFrom<&String> for MatchFlips {
+    type Error = ();
+    fn try_from(value: &String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "exact" => Ok(MatchFlips::Exact),
+            "ignore" => Ok(MatchFlips::Ignore),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<&MatchFlips> for String {
+    fn from(value: &MatchFlips) -> Self {
+        match value {
+            MatchFlips::Exact => String::from("exact"),
+            MatchFlips::Ignore => String::from("ignore"),
+        }
+    }
+}
+
+/// Filter property that defines the orientation of substituted fields (their
+/// own orientation, or the one of the fields they replace)
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum SubstituteFlips {
+    #[default]
+    Keep,
+    Copy,
+}
+
+impl TryFrom<&String> for SubstituteFlips {
+    type Error = ();
+    fn try_from(value: &String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "keep" => Ok(SubstituteFlips::Keep),
+            "copy" => Ok(SubstituteFlips::Copy),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<&SubstituteFlips> for String {
+    fn from(value: &SubstituteFlips) -> Self {
+        match value {
+            SubstituteFlips::Keep => String::from("keep"),
+            SubstituteFlips::Copy => String::from("copy"),
+        }
+    }
+}
+
+/// Filter collection property that defines how the filters are applied: at
+/// all the pattern matches (all), or at one random match at a time until there
+/// are no matches left (markov)
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum ApplyMode {
+    #[default]
+    All,
+    Markov,
+}
+
+impl TryFrom<&String> for ApplyMode {
+    type Error = ();
+    fn try_from(value: &String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "all" => Ok(ApplyMode::All),
+            "markov" => Ok(ApplyMode::Markov),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<&ApplyMode> for String {
+    fn from(value: &ApplyMode) -> Self {
+        match value {
+            ApplyMode::All => String::from("all"),
+            ApplyMode::Markov => String::from("markov"),
+        }
+    }
+}
+
 /// Filter properties.
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct FilterProperties {
     /// Probability of the filter being applied on each pattern match (clamped
     /// to range [0..1]).
@@ -39,35 +171,296 @@ pub struct FilterProperties {
     /// If true, the filter will not be applied (this does not result in an
     /// error)
     pub ignore: bool,
+    /// Name of the region of the input map that the filter is restricted to
+    /// (an object, an object layer or a tile layer of the input map), or the
+    /// name prefixed with `!` to exclude the region
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub region: Option<String>,
+    /// Defines whether pattern matching compares field orientations (exact)
+    /// or not (ignore).
+    pub match_flips: MatchFlips,
+    /// Defines whether substituted fields keep their own orientation (keep)
+    /// or take the orientation of the fields they replace (copy).
+    pub substitute_flips: SubstituteFlips,
+    /// Defines whether the filter collection is applied at all the pattern
+    /// matches (all) or at one random match per step (markov).
+    pub mode: ApplyMode,
+    /// Maximum number of steps of a filter collection in markov mode (the
+    /// number of fields of the map if not set)
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub max_steps: Option<u32>,
+    /// Name of a filter collection or group that has to change the map for
+    /// the filter collection to be applied (see `sequence::apply_sequence`)
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub when: Option<String>,
+    /// Condition on which a repeatedly applied filter collection stops:
+    /// `unchanged` (a run changes no fields), or the name of a sentinel
+    /// filter collection (its pattern matches)
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub until: Option<String>,
+    /// Maximum number of runs of a filter collection repeated with `until`
+    /// (`sequence::DEFAULT_MAX_RUNS` if not set)
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub max_runs: Option<u32>,
+    /// If true, the filter collection isn't applied, its patterns are matched
+    /// to stop the sequence of filter collections instead
+    pub sentinel: bool,
+    /// Tile class that has to be at least `min_distance` away from the
+    /// fields covered by the pattern
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub min_distance_to: Option<String>,
+    /// Minimum distance to the `min_distance_to` tile class
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub min_distance: Option<f32>,
+    /// Tile class that has to be at most `max_distance` away from the fields
+    /// covered by the pattern
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub max_distance_to: Option<String>,
+    /// Maximum distance to the `max_distance_to` tile class
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub max_distance: Option<f32>,
+    /// Metric of the distances to tile classes (manhattan, chebyshev or
+    /// chamfer).
+    pub distance_metric: DistanceMetric,
 }
 
+impl FilterProperties {
+    /// Sets the filter property with the given name to the given Tiled
+    /// property value. Returns `false` if there's no filter property with the
+    /// name.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem if the value has the wrong type or
+    /// is invalid, leaving the filter property unchanged.
+    pub(crate) fn parse_property(
+        &mut self,
+        name: &str,
+        value: &PropertyValue,
+    ) -> Result<bool, String> {
+        let non_empty = |p: &String| Some(p.clone()).filter(|p| !p.is_empty());
+        match name {
+            "probability" => match value {
+                PropertyValue::FloatValue(p) if (0.0..=1.0).contains(p) => self.probability = *p,
+                PropertyValue::FloatValue(p) => {
+                    return Err(format!("'probability' {} is not in range [0..1]", p))
+                }
+                _ => return Err(String::from("'probability' must be a float")),
+            },
+            "pattern_matching" => {
+                self.pattern_matching = enum_property(name, value, "'source' or 'destination'")?
+            }
+            "iterations" => self.iterations = count_property(name, value)?,
+            "ignore" => self.ignore = bool_property(name, value)?,
+            "region" => self.region = non_empty(string_property(name, value)?),
+            "match_flips" => self.match_flips = enum_property(name, value, "'exact' or 'ignore'")?,
+            "substitute_flips" => {
+                self.substitute_flips = enum_property(name, value, "'keep' or 'copy'")?
+            }
+            "mode" => self.mode = enum_property(name, value, "'all' or 'markov'")?,
+            "max_steps" => self.max_steps = Some(count_property(name, value)?),
+            "when" => self.when = non_empty(string_property(name, value)?),
+            "until" => self.until = non_empty(string_property(name, value)?),
+            "max_runs" => self.max_runs = Some(count_property(name, value)?),
+            "sentinel" => self.sentinel = bool_property(name, value)?,
+            "min_distance_to" => self.min_distance_to = non_empty(string_property(name, value)?),
+            "min_distance" => self.min_distance = Some(distance_property(name, value)?),
+            "max_distance_to" => self.max_distance_to = non_empty(string_property(name, value)?),
+            "max_distance" => self.max_distance = Some(distance_property(name, value)?),
+            "distance_metric" => {
+                self.distance_metric =
+                    enum_property(name, value, "'manhattan', 'chebyshev' or 'chamfer'")?
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// Reads filter properties from Tiled properties. Unknown and invalid
+/// properties are ignored (see `tiled_filter_checker` to find them).
 impl From<&Properties> for FilterProperties {
     fn from(value: &Properties) -> Self {
-        let probability = match value.get("probability") {
-            Some(PropertyValue::FloatValue(p)) => *p,
-            _ => 1.0,
-        };
-        let pattern_matching: PatternMatching = match value.get("pattern_matching") {
-            Some(PropertyValue::StringValue(p)) => {
-                p.try_into().unwrap_or(PatternMatching::default())
-            }
-            _ => PatternMatching::default(),
-        };
-        let iterations = match value.get("iterations") {
-            Some(PropertyValue::IntValue(p)) => *p as u32,
-            _ => 1u32,
-        };
-        let ignore = match value.get("ignore") {
-            Some(PropertyValue::BoolValue(p)) => *p,
-            _ => false,
-        };
+        let mut properties = Self::default();
+        for (name, value) in value {
+            _ = properties.parse_property(name, value);
+        }
+        properties
+    }
+}
 
-        Self {
-            probability,
-            pattern_matching,
-            iterations,
-            ignore,
+/// Returns the value of the Tiled string property with the given name, or a
+/// description of the problem.
+pub(crate) fn string_property<'a>(
+    name: &str,
+    value: &'a PropertyValue,
+) -> Result<&'a String, String> {
+    match value {
+        PropertyValue::StringValue(p) => Ok(p),
+        _ => Err(format!("'{}' must be a string", name)),
+    }
+}
+
+/// Returns the value of the Tiled bool property with the given name, or a
+/// description of the problem.
+pub(crate) fn bool_property(name: &str, value: &PropertyValue) -> Result<bool, String> {
+    match value {
+        PropertyValue::BoolValue(p) => Ok(*p),
+        _ => Err(format!("'{}' must be a bool", name)),
+    }
+}
+
+/// Returns the value of the Tiled int property with the given name, which
+/// mustn't be negative, or a description of the problem.
+pub(crate) fn count_property(name: &str, value: &PropertyValue) -> Result<u32, String> {
+    match value {
+        PropertyValue::IntValue(p) => {
+            u32::try_from(*p).map_err(|_| format!("'{}' {} is negative", name, p))
         }
+        _ => Err(format!("'{}' must be an int", name)),
+    }
+}
+
+/// Returns the value of the Tiled string property with the given name
+/// converted to the given type, or a description of the problem mentioning
+/// the expected values.
+pub(crate) fn enum_property<E>(
+    name: &str,
+    value: &PropertyValue,
+    expected: &str,
+) -> Result<E, String>
+where
+    E: for<'a> TryFrom<&'a String>,
+{
+    let p = string_property(name, value)?;
+    E::try_from(p).map_err(|_| format!("'{}' must be {}, found '{}'", name, expected, p))
+}
+
+/// Returns the value of the Tiled float (or int) property with the given
+/// name, which mustn't be negative, or a description of the problem.
+fn distance_property(name: &str, value: &PropertyValue) -> Result<f32, String> {
+    match value {
+        PropertyValue::FloatValue(p) if *p >= 0.0 => Ok(*p),
+        PropertyValue::IntValue(p) if *p >= 0 => Ok(*p as f32),
+        PropertyValue::FloatValue(p) => Err(format!("'{}' {} is negative", name, p)),
+        PropertyValue::IntValue(p) => Err(format!("'{}' {} is negative", name, p)),
+        _ => Err(format!("'{}' must be a float", name)),
+    }
+}
+
+impl From<&FilterProperties> for Properties {
+    fn from(value: &FilterProperties) -> Self {
+        let default = FilterProperties::default();
+        let mut result = Properties::new();
+        if value.probability != default.probability {
+            result.insert(
+                String::from("probability"),
+                PropertyValue::FloatValue(value.probability),
+            );
+        }
+        if value.pattern_matching != default.pattern_matching {
+            result.insert(
+                String::from("pattern_matching"),
+                PropertyValue::StringValue(String::from(&value.pattern_matching)),
+            );
+        }
+        if value.iterations != default.iterations {
+            result.insert(
+                String::from("iterations"),
+                PropertyValue::IntValue(value.iterations as i32),
+            );
+        }
+        if value.ignore != default.ignore {
+            result.insert(
+                String::from("ignore"),
+                PropertyValue::BoolValue(value.ignore),
+            );
+        }
+        if let Some(region) = &value.region {
+            result.insert(
+                String::from("region"),
+                PropertyValue::StringValue(region.clone()),
+            );
+        }
+        if value.match_flips != default.match_flips {
+            result.insert(
+                String::from("match_flips"),
+                PropertyValue::StringValue(String::from(&value.match_flips)),
+            );
+        }
+        if value.substitute_flips != default.substitute_flips {
+            result.insert(
+                String::from("substitute_flips"),
+                PropertyValue::StringValue(String::from(&value.substitute_flips)),
+            );
+        }
+        if value.mode != default.mode {
+            result.insert(
+                String::from("mode"),
+                PropertyValue::StringValue(String::from(&value.mode)),
+            );
+        }
+        if let Some(max_steps) = value.max_steps {
+            result.insert(
+                String::from("max_steps"),
+                PropertyValue::IntValue(max_steps as i32),
+            );
+        }
+        if let Some(when) = &value.when {
+            result.insert(
+                String::from("when"),
+                PropertyValue::StringValue(when.clone()),
+            );
+        }
+        if let Some(until) = &value.until {
+            result.insert(
+                String::from("until"),
+                PropertyValue::StringValue(until.clone()),
+            );
+        }
+        if let Some(max_runs) = value.max_runs {
+            result.insert(
+                String::from("max_runs"),
+                PropertyValue::IntValue(max_runs as i32),
+            );
+        }
+        if value.sentinel != default.sentinel {
+            result.insert(
+                String::from("sentinel"),
+                PropertyValue::BoolValue(value.sentinel),
+            );
+        }
+        if let Some(min_distance_to) = &value.min_distance_to {
+            result.insert(
+                String::from("min_distance_to"),
+                PropertyValue::StringValue(min_distance_to.clone()),
+            );
+        }
+        if let Some(min_distance) = value.min_distance {
+            result.insert(
+                String::from("min_distance"),
+                PropertyValue::FloatValue(min_distance),
+            );
+        }
+        if let Some(max_distance_to) = &value.max_distance_to {
+            result.insert(
+                String::from("max_distance_to"),
+                PropertyValue::StringValue(max_distance_to.clone()),
+            );
+        }
+        if let Some(max_distance) = value.max_distance {
+            result.insert(
+                String::from("max_distance"),
+                PropertyValue::FloatValue(max_distance),
+            );
+        }
+        if value.distance_metric != default.distance_metric {
+            result.insert(
+                String::from("distance_metric"),
+                PropertyValue::StringValue(String::from(&value.distance_metric)),
+            );
+        }
+        result
     }
 }
 
@@ -78,12 +471,166 @@ impl Default for FilterProperties {
             pattern_matching: PatternMatching::default(),
             iterations: 1,
             ignore: false,
+            region: None,
+            match_flips: MatchFlips::default(),
+            substitute_flips: SubstituteFlips::default(),
+            mode: ApplyMode::default(),
+            max_steps: None,
+            when: None,
+            until: None,
+            max_runs: None,
+            sentinel: false,
+            min_distance_to: None,
+            min_distance: None,
+            max_distance_to: None,
+            max_distance: None,
+            distance_metric: DistanceMetric::default(),
+        }
+    }
+}
+
+/// Bound on the field types of filters that's `Sync` when the `parallel`
+/// feature is enabled (so pattern matches can be searched for in parallel) and
+/// is implemented by all types otherwise.
+#[cfg(feature = "parallel")]
+pub trait MaybeSync: Sync {}
+
+#[cfg(feature = "parallel")]
+impl<T: Sync> MaybeSync for T {}
+
+/// Bound on the field types of filters that's `Sync` when the `parallel`
+/// feature is enabled (so pattern matches can be searched for in parallel) and
+/// is implemented by all types otherwise.
+#[cfg(not(feature = "parallel"))]
+pub trait MaybeSync {}
+
+#[cfg(not(feature = "parallel"))]
+impl<T> MaybeSync for T {}
+
+/// Orientation of field values (e.g. flipped tiles), which the `match_flips`
+/// and `substitute_flips` filter properties work with. Filters only use it
+/// after `Filter::set_field_orientation` (or the filter collection method of
+/// the same name) has been called, which the filter importers do for Tiled
+/// tiles. Field types without orientations don't need to implement it.
+pub trait FieldOrientation {
+    /// Checks if the given fields are equal when ignoring their orientations.
+    fn eq_ignoring_orientation(&self, other: &Self) -> bool
+    where
+        Self: PartialEq,
+    {
+        self == other
+    }
+
+    /// Returns a copy of the field with the orientation of the given field.
+    fn with_orientation_of(&self, _other: &Self) -> Self
+    where
+        Self: Clone,
+    {
+        self.clone()
+    }
+}
+
+macro_rules! impl_field_orientation {
+    ($($t:ty),*) => {
+        $(impl FieldOrientation for $t {})*
+    };
+}
+
+impl_field_orientation!(
+    bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, String
+);
+
+/// Orientation functions of the field type of a filter (see
+/// `FieldOrientation`), if set. They describe the field type rather than the
+/// filter, so filters compare as equal regardless of them.
+struct Orientation<T> {
+    eq_ignoring_orientation: Option<fn(&T, &T) -> bool>,
+    with_orientation_of: Option<fn(&T, &T) -> T>,
+}
+
+impl<T> Default for Orientation<T> {
+    fn default() -> Self {
+        Self {
+            eq_ignoring_orientation: None,
+            with_orientation_of: None,
+        }
+    }
+}
+
+impl<T> Clone for Orientation<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Orientation<T> {}
+
+impl<T> PartialEq for Orientation<T> {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl<T> std::fmt::Debug for Orientation<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Orientation")
+    }
+}
+
+/// Callback of `ApplyOptions`, called with the filter index and the
+/// destination map
+pub type ApplyCallback<'a, T> = dyn FnMut(usize, &Map<T>) + 'a;
+
+/// Options of applying filters (see `Filter::apply_with` and
+/// `FilterCollection::apply_with`). Only the random number generator is
+/// required, the other options can be set on top of `ApplyOptions::new`.
+pub struct ApplyOptions<'a, T, R: ?Sized> {
+    /// Random number generator deciding whether a substitution happens on a
+    /// pattern match
+    pub rng: &'a mut R,
+    /// Mask of the fields the filters are restricted to (e.g. of a region, see
+    /// `FilterProperties::region`). Positions where the pattern isn't
+    /// completely inside the mask are skipped.
+    pub mask: Option<&'a Map<bool>>,
+    /// Objects emitted by the substitutions are added to these, and discarded
+    /// if not given
+    pub objects: Option<&'a mut Vec<MapObject>>,
+    /// Index of the source map, used to find pattern matches (see
+    /// `Filter::find_matches_indexed`). Filters with `destination` pattern
+    /// matching are applied without it.
+    pub index: Option<&'a TileIndex<T>>,
+    /// Called with the filter index and the destination map after each filter
+    /// application (or markov step) of a filter collection
+    pub callback: Option<&'a mut ApplyCallback<'a, T>>,
+}
+
+impl<'a, T, R: ?Sized> ApplyOptions<'a, T, R> {
+    /// Creates options with the given random number generator, without a
+    /// mask, objects, an index or a callback.
+    pub fn new(rng: &'a mut R) -> Self {
+        Self {
+            rng,
+            mask: None,
+            objects: None,
+            index: None,
+            callback: None,
         }
     }
 }
 
 /// Map filter
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        try_from = "RawFilter<T>",
+        bound(
+            serialize = "T: Clone + serde::Serialize",
+            deserialize = "T: Default + serde::Deserialize<'de>"
+        )
+    )
+)]
 pub struct Filter<T> {
     /// Defines to which fields the substitute will be applied to.
     pattern: Map<T>,
@@ -95,6 +642,39 @@ pub struct Filter<T> {
     /// Filter properties that can affect pattern matching or substitute
     /// application.
     properties: FilterProperties,
+    /// Objects emitted whenever the substitute is applied, positioned relative
+    /// to the substitute.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            skip_serializing_if = "Vec::is_empty",
+            serialize_with = "serialize_unsupported"
+        )
+    )]
+    objects: Vec<MapObject>,
+    /// Pattern positions that match any of the given fields instead of the
+    /// pattern field at the position.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            skip_serializing_if = "Vec::is_empty",
+            serialize_with = "serialize_unsupported"
+        )
+    )]
+    alternatives: Vec<(Vector2u, Vec<T>)>,
+    /// Conditions on the distances from pattern matches to given fields.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            skip_serializing_if = "Vec::is_empty",
+            serialize_with = "serialize_unsupported"
+        )
+    )]
+    distance_constraints: Vec<DistanceConstraint<T>>,
+    /// Orientation functions of the field type, used by the `match_flips` and
+    /// `substitute_flips` properties.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    orientation: Orientation<T>,
 }
 
 impl<T> Filter<T> {
@@ -114,6 +694,10 @@ impl<T> Filter<T> {
                 substitute,
                 wildcard,
                 properties: FilterProperties::default(),
+                objects: vec![],
+                alternatives: vec![],
+                distance_constraints: vec![],
+                orientation: Orientation::default(),
             })
         }
     }
@@ -139,6 +723,10 @@ impl<T> Filter<T> {
                 substitute,
                 wildcard,
                 properties,
+                objects: vec![],
+                alternatives: vec![],
+                distance_constraints: vec![],
+                orientation: Orientation::default(),
             })
         }
     }
@@ -153,22 +741,97 @@ impl<T> Filter<T> {
         &self.substitute
     }
 
+    /// Returns the filter wildcard value.
+    pub fn wildcard(&self) -> &T {
+        &self.wildcard
+    }
+
+    /// Returns the filter properties.
+    pub fn properties(&self) -> &FilterProperties {
+        &self.properties
+    }
+
+    /// Sets the filter properties.
+    pub fn set_properties(&mut self, properties: FilterProperties) {
+        self.properties = properties;
+    }
+
+    /// Makes the filter use the orientations of its field type (see
+    /// `FieldOrientation`). Without them, `match_flips = ignore` compares
+    /// fields exactly and `substitute_flips = copy` keeps the substitute
+    /// fields as they are. The filter importers call this for Tiled tiles.
+    pub fn set_field_orientation(&mut self)
+    where
+        T: FieldOrientation + Clone + PartialEq,
+    {
+        self.orientation = Orientation {
+            eq_ignoring_orientation: Some(T::eq_ignoring_orientation),
+            with_orientation_of: Some(T::with_orientation_of),
+        };
+    }
+
+    /// Returns the objects emitted by the filter.
+    pub fn objects(&self) -> &[MapObject] {
+        &self.objects
+    }
+
+    /// Sets the objects emitted whenever the substitute is applied. Object
+    /// positions are relative to the substitute position (e.g. `(0.5, 0.5)`
+    /// is the center of its top left field).
+    pub fn set_objects(&mut self, objects: Vec<MapObject>) {
+        self.objects = objects;
+    }
+
+    /// Returns the pattern positions that match alternative fields (see
+    /// `set_alternatives`).
+    pub fn alternatives(&self) -> &[(Vector2u, Vec<T>)] {
+        &self.alternatives
+    }
+
+    /// Makes the given pattern position match any of the given fields instead
+    /// of the pattern field at the position (e.g. any tile of a class). An
+    /// empty list of fields matches nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the position is out of pattern bounds.
+    pub fn set_alternatives(
+        &mut self,
+        position: Vector2u,
+        fields: Vec<T>,
+    ) -> Result<(), TisuError> {
+        self.pattern.get(position)?;
+        self.alternatives.retain(|(other, _)| *other != position);
+        self.alternatives.push((position, fields));
+        Ok(())
+    }
+
+    /// Returns the conditions on the distances from pattern matches to given
+    /// fields (see `set_distance_constraints`).
+    pub fn distance_constraints(&self) -> &[DistanceConstraint<T>] {
+        &self.distance_constraints
+    }
+
+    /// Sets conditions on the distances from pattern matches to given fields
+    /// (e.g. the tiles of a class), measured with the `distance_metric`
+    /// property. The pattern only matches where all of them are satisfied.
+    pub fn set_distance_constraints(&mut self, constraints: Vec<DistanceConstraint<T>>) {
+        self.distance_constraints = constraints;
+    }
+
     /// Checks if the filter pattern matches at the given position in the given
-    /// input map. Optionally, an application map can be used which defines the
-    /// fields where the filter has already been applied.
-    pub fn pattern_matches(&self, input: &Map<T>, position: Vector2u) -> bool
+    /// input map.
+    pub fn pattern_matches<M>(&self, input: &M, position: Vector2u) -> bool
     where
         T: PartialEq,
+        M: MapStorage<T> + ?Sized,
     {
-        if rand::rng().random_range(0.0..1.0) > self.properties.probability {
-            return false;
-        }
         for x in 0..self.pattern.size().x {
             for y in 0..self.pattern.size().y {
                 let point = Vector2u::new(x, y);
                 if let Ok(input_field) = input.get(position + point) {
                     if let Ok(pattern_field) = self.pattern.get(point) {
-                        if !self.fields_match(input_field, pattern_field) {
+                        if !self.fields_match(input_field, pattern_field, point) {
                             return false;
                         }
                     }
@@ -178,55 +841,347 @@ impl<T> Filter<T> {
             }
         }
 
-        true
+        // Distances are measured on the matched map, so with destination
+        // pattern matching they take earlier substitutions into account
+        self.distance_constraints.iter().all(|constraint| {
+            constraint.is_satisfied(
+                input,
+                position,
+                self.pattern.size(),
+                self.properties.distance_metric,
+            )
+        })
+    }
+
+    /// Returns the positions where the filter pattern matches the given input
+    /// map, in the order in which they're visited when applying the filter
+    /// (column by column). With the `parallel` feature, the columns are
+    /// searched in parallel.
+    pub fn find_matches<M>(&self, input: &M) -> Vec<Vector2u>
+    where
+        T: PartialEq + MaybeSync,
+        M: MapStorage<T> + MaybeSync + ?Sized,
+    {
+        self.find_matches_in(input, self.position_range(input.size(), None))
+    }
+
+    /// Returns the positions between the given start (inclusive) and end
+    /// (exclusive) where the filter pattern matches the given input map, like
+    /// `find_matches`.
+    fn find_matches_in<M>(&self, input: &M, (start, end): (Vector2u, Vector2u)) -> Vec<Vector2u>
+    where
+        T: PartialEq + MaybeSync,
+        M: MapStorage<T> + MaybeSync + ?Sized,
+    {
+        let find_column_matches = |x: u32| {
+            (start.y..end.y)
+                .map(move |y| Vector2u::new(x, y))
+                .filter(|point| self.pattern_matches(input, *point))
+        };
+        let columns = start.x..end.x;
+
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            columns
+                .into_par_iter()
+                .flat_map_iter(find_column_matches)
+                .collect()
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            columns.flat_map(find_column_matches).collect()
+        }
     }
 
-    fn fields_match(&self, input_field: &T, pattern_field: &T) -> bool
+    /// Returns the positions where the filter pattern matches the given input
+    /// map, like `find_matches`. The given index of the input map is used to
+    /// only test the positions where the least frequent field of the pattern
+    /// occurs, instead of all the positions of the map.
+    pub fn find_matches_indexed(&self, input: &Map<T>, index: &TileIndex<T>) -> Vec<Vector2u>
+    where
+        T: PartialEq + MaybeSync,
+    {
+        if input.size().x < self.pattern.size().x || input.size().y < self.pattern.size().y {
+            return vec![];
+        }
+        match self.index_candidates(input.size(), index) {
+            Some(candidates) => candidates
+                .into_iter()
+                .filter(|point| self.pattern_matches(input, *point))
+                .collect(),
+            None => self.find_matches(input),
+        }
+    }
+
+    /// Returns the positions where the pattern may match a map of the given
+    /// size (at least as large as the pattern), column by column, found with
+    /// the given index of the map: the positions where the least frequent
+    /// field of the pattern occurs. Returns `None` if the index can't be used
+    /// and all the positions have to be tested.
+    fn index_candidates(&self, size: Vector2u, index: &TileIndex<T>) -> Option<Vec<Vector2u>>
     where
         T: PartialEq,
     {
-        input_field == pattern_field || pattern_field == &self.wildcard
+        if self.properties.match_flips == MatchFlips::Ignore
+            && self.orientation.eq_ignoring_orientation.is_some()
+        {
+            // The index only contains fields in their exact orientations
+            return None;
+        }
+
+        // Anchor the search on the least frequent field of the pattern
+        // (positions matching alternative fields can't be looked up)
+        let mut anchor: Option<(Vector2u, &[Vector2u])> = None;
+        for x in 0..self.pattern.size().x {
+            for y in 0..self.pattern.size().y {
+                let point = Vector2u::new(x, y);
+                let Ok(field) = self.pattern.get(point) else {
+                    continue;
+                };
+                if field == &self.wildcard
+                    || self
+                        .alternatives
+                        .iter()
+                        .any(|(position, _)| *position == point)
+                {
+                    continue;
+                }
+                let positions = index.positions(field);
+                if anchor
+                    .is_none_or(|(_, anchor_positions)| positions.len() < anchor_positions.len())
+                {
+                    anchor = Some((point, positions));
+                }
+            }
+        }
+        // Patterns made of wildcards and alternatives have to be matched at
+        // every position
+        let (offset, positions) = anchor?;
+
+        // Moving all the positions by the same offset keeps them in order
+        let last = size - self.pattern.size();
+        Some(
+            positions
+                .iter()
+                .filter(|position| position.x >= offset.x && position.y >= offset.y)
+                .map(|position| *position - offset)
+                .filter(|point| point.x <= last.x && point.y <= last.y)
+                .collect(),
+        )
+    }
+
+    /// Returns the number of positions the pattern can be placed at in a map
+    /// of the given size.
+    fn position_count(&self, size: Vector2u) -> usize {
+        let (start, end) = self.position_range(size, None);
+        (end.x - start.x) as usize * (end.y - start.y) as usize
+    }
+
+    /// Returns the start (inclusive) and the end (exclusive) of the positions
+    /// the pattern can be placed at in a map of the given size, optionally
+    /// restricted to the given rectangle of positions.
+    fn position_range(&self, size: Vector2u, positions: Option<Rect2u>) -> (Vector2u, Vector2u) {
+        let pattern_size = self.pattern.size();
+        let mut start = Vector2u::default();
+        let mut end = Vector2u::new(
+            (size.x + 1).saturating_sub(pattern_size.x),
+            (size.y + 1).saturating_sub(pattern_size.y),
+        );
+        if let Some(positions) = positions {
+            start = positions.position();
+            end.x = end.x.min(start.x.saturating_add(positions.size().x));
+            end.y = end.y.min(start.y.saturating_add(positions.size().y));
+        }
+        // An empty range starts where it ends
+        (Vector2u::new(start.x.min(end.x), start.y.min(end.y)), end)
+    }
+
+    fn fields_match(&self, input_field: &T, pattern_field: &T, point: Vector2u) -> bool
+    where
+        T: PartialEq,
+    {
+        match self
+            .alternatives
+            .iter()
+            .find(|(position, _)| *position == point)
+        {
+            Some((_, fields)) => fields.contains(input_field),
+            None if pattern_field == &self.wildcard => true,
+            None => match (
+                &self.properties.match_flips,
+                self.orientation.eq_ignoring_orientation,
+            ) {
+                (MatchFlips::Ignore, Some(eq_ignoring_orientation)) => {
+                    eq_ignoring_orientation(input_field, pattern_field)
+                }
+                _ => input_field == pattern_field,
+            },
+        }
     }
 
     /// Applies the filter substitute to the given input map at the given
     /// position. Optionally, an application map can be used to mark the fields
-    /// where the substitute has been applied.
-    pub fn apply_substitute(&self, input: &mut Map<T>, position: Vector2u)
+    /// where the substitute has been applied. With `substitute_flips` set to
+    /// `copy`, substituted fields take the orientation of the fields they
+    /// replace. Returns the number of fields whose value changed.
+    pub fn apply_substitute<M>(&self, input: &mut M, position: Vector2u) -> usize
     where
         T: Clone + PartialEq,
+        M: MapStorage<T> + ?Sized,
     {
+        let mut changes = 0;
         for x in 0..self.pattern.size().x {
             for y in 0..self.pattern.size().y {
                 let point = Vector2u::new(x, y);
                 if let Ok(substitute_field) = self.substitute.get(point) {
-                    self.substitute_field(input, position + point, substitute_field);
+                    if self.substitute_field(input, position + point, substitute_field) {
+                        changes += 1;
+                    }
                 }
             }
         }
+        changes
     }
 
-    fn substitute_field(&self, input: &mut Map<T>, position: Vector2u, substitute_field: &T)
+    fn substitute_field<M>(&self, input: &mut M, position: Vector2u, substitute_field: &T) -> bool
     where
         T: Clone + PartialEq,
+        M: MapStorage<T> + ?Sized,
     {
-        if substitute_field != &self.wildcard {
-            _ = input.set(position, substitute_field.clone());
+        if substitute_field == &self.wildcard {
+            return false;
+        }
+        let Ok(replaced_field) = input.get(position) else {
+            return false;
+        };
+        let field = match (
+            &self.properties.substitute_flips,
+            self.orientation.with_orientation_of,
+        ) {
+            (SubstituteFlips::Copy, Some(with_orientation_of)) => {
+                with_orientation_of(substitute_field, replaced_field)
+            }
+            _ => substitute_field.clone(),
+        };
+        if &field == replaced_field {
+            return false;
         }
+        input.set(position, field).is_ok()
     }
 
-    /// Applies the filter to the given map.
+    /// Applies the filter to the given map. Returns statistics of the
+    /// application, like the number of pattern matches and changed fields.
     ///
     /// # Errors
     ///
     /// Returns an error if map size is smaller than that of the pattern or
     /// substitute maps.
-    pub fn apply(&self, source: &Map<T>, destination: &mut Map<T>) -> Result<(), TisuError>
+    pub fn apply<M>(&self, source: &M, destination: &mut M) -> Result<FilterStats, TisuError>
     where
-        Map<T>: Clone,
-        T: Clone + PartialEq,
+        T: Clone + PartialEq + MaybeSync,
+        M: MapStorage<T> + MaybeSync + ?Sized,
+    {
+        self.apply_with_rng(source, destination, &mut rand::rng())
+    }
+
+    /// Applies the filter to the given map, using the given random number
+    /// generator to decide whether a substitution happens on a pattern match.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if map size is smaller than that of the pattern or
+    /// substitute maps.
+    pub fn apply_with_rng<M, R>(
+        &self,
+        source: &M,
+        destination: &mut M,
+        rng: &mut R,
+    ) -> Result<FilterStats, TisuError>
+    where
+        T: Clone + PartialEq + MaybeSync,
+        M: MapStorage<T> + MaybeSync + ?Sized,
+        R: Rng + ?Sized,
+    {
+        self.apply_with(source, destination, &mut ApplyOptions::new(rng))
+    }
+
+    /// Applies the filter to the given map with the given options: restricted
+    /// to a mask, adding the emitted objects to the given objects and finding
+    /// pattern matches with an index of the source map (see `ApplyOptions`).
+    /// The callback is only called by filter collections.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if map size is smaller than that of the pattern or
+    /// substitute maps, or if the mask or the index isn't of the same size as
+    /// the source map.
+    pub fn apply_with<M, R>(
+        &self,
+        source: &M,
+        destination: &mut M,
+        options: &mut ApplyOptions<T, R>,
+    ) -> Result<FilterStats, TisuError>
+    where
+        T: Clone + PartialEq + MaybeSync,
+        M: MapStorage<T> + MaybeSync + ?Sized,
+        R: Rng + ?Sized,
+    {
+        if options
+            .mask
+            .is_some_and(|mask| mask.size() != source.size())
+            || options
+                .index
+                .is_some_and(|index| index.size() != source.size())
+        {
+            return Err(TisuError::InvalidMapSize);
+        }
+        self.apply_in_mask(source, destination, None, options)
+    }
+
+    /// Applies the filter to the given map like `apply_with_rng`, only at the
+    /// positions (of the top left field of the pattern) inside the given
+    /// rectangle. Substitutes at these positions can still change fields
+    /// outside of it. Used to apply a filter to a chunked map piece by piece.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if map size is smaller than that of the pattern or
+    /// substitute maps.
+    pub(crate) fn apply_in_rect<M, R>(
+        &self,
+        source: &M,
+        destination: &mut M,
+        positions: Rect2u,
+        rng: &mut R,
+    ) -> Result<FilterStats, TisuError>
+    where
+        T: Clone + PartialEq + MaybeSync,
+        M: MapStorage<T> + MaybeSync + ?Sized,
+        R: Rng + ?Sized,
+    {
+        self.apply_in_mask(
+            source,
+            destination,
+            Some(positions),
+            &mut ApplyOptions::new(rng),
+        )
+    }
+
+    fn apply_in_mask<M, R>(
+        &self,
+        source: &M,
+        destination: &mut M,
+        positions: Option<Rect2u>,
+        options: &mut ApplyOptions<T, R>,
+    ) -> Result<FilterStats, TisuError>
+    where
+        T: Clone + PartialEq + MaybeSync,
+        M: MapStorage<T> + MaybeSync + ?Sized,
+        R: Rng + ?Sized,
     {
         if self.properties.ignore {
-            return Ok(());
+            return Ok(FilterStats::default());
         }
 
         if source.size() != destination.size()
@@ -235,34 +1190,293 @@ impl<T> Filter<T> {
         {
             Err(TisuError::InvalidMapSize)
         } else {
-            for x in 0..=source.size().x - self.pattern().size().x {
-                for y in 0..=source.size().y - self.pattern().size().y {
-                    let point = Vector2u::new(x, y);
-                    match self.properties.pattern_matching {
-                        PatternMatching::Destination => {
+            let start = Instant::now();
+            let (mask, index, rng) = (options.mask, options.index, &mut *options.rng);
+            let mut discarded = vec![];
+            let objects = options.objects.as_deref_mut().unwrap_or(&mut discarded);
+            let in_mask =
+                |point: Vector2u| mask.is_none_or(|mask| self.pattern_in_mask(mask, point));
+            let range = self.position_range(source.size(), positions);
+            let mut stats = match self.properties.pattern_matching {
+                PatternMatching::Destination => {
+                    // Substitutions affect the following matches, so matching
+                    // and substituting have to be interleaved
+                    let mut stats = FilterStats::default();
+                    for x in range.0.x..range.1.x {
+                        for y in range.0.y..range.1.y {
+                            let point = Vector2u::new(x, y);
+                            if !in_mask(point) {
+                                continue;
+                            }
+                            stats.positions_tested += 1;
                             if self.pattern_matches(destination, point) {
-                                self.apply_substitute(destination, point);
+                                stats.matches += 1;
+                                if self.roll_probability(rng) {
+                                    stats.changes += self.apply_substitute(destination, point);
+                                    self.emit_objects(point, objects);
+                                } else {
+                                    stats.skipped += 1;
+                                }
                             }
                         }
-                        PatternMatching::Source => {
-                            if self.pattern_matches(source, point) {
-                                self.apply_substitute(destination, point);
-                            }
+                    }
+                    stats
+                }
+                PatternMatching::Source => {
+                    let candidates =
+                        index.and_then(|index| self.index_candidates(source.size(), index));
+                    let (matches, positions_tested) = match candidates {
+                        Some(mut candidates) => {
+                            candidates.retain(|point| {
+                                point.x >= range.0.x
+                                    && point.y >= range.0.y
+                                    && point.x < range.1.x
+                                    && point.y < range.1.y
+                                    && in_mask(*point)
+                            });
+                            let matches = candidates
+                                .iter()
+                                .filter(|point| self.pattern_matches(source, **point))
+                                .copied()
+                                .collect();
+                            (matches, candidates.len())
+                        }
+                        None => {
+                            let mut matches = self.find_matches_in(source, range);
+                            matches.retain(|point| in_mask(*point));
+                            // Like with destination pattern matching, positions
+                            // outside of the mask aren't counted as tested
+                            let positions_tested = match mask {
+                                Some(_) => (range.0.x..range.1.x)
+                                    .flat_map(|x| {
+                                        (range.0.y..range.1.y).map(move |y| Vector2u::new(x, y))
+                                    })
+                                    .filter(|point| in_mask(*point))
+                                    .count(),
+                                None => {
+                                    (range.1.x - range.0.x) as usize
+                                        * (range.1.y - range.0.y) as usize
+                                }
+                            };
+                            (matches, positions_tested)
                         }
+                    };
+                    let mut stats = self.apply_matches(&matches, destination, objects, rng);
+                    stats.positions_tested = positions_tested;
+                    stats
+                }
+            };
+            stats.duration = start.elapsed();
+            Ok(stats)
+        }
+    }
+
+    /// Checks if all the fields covered by the pattern at the given position
+    /// are set in the given mask.
+    fn pattern_in_mask(&self, mask: &Map<bool>, position: Vector2u) -> bool {
+        (0..self.pattern.size().x).all(|x| {
+            (0..self.pattern.size().y).all(|y| mask.get(position + (x, y).into()) == Ok(&true))
+        })
+    }
+
+    /// Applies the substitute at the given pattern match positions, each with
+    /// the filter probability. Substitutions are applied in the order of the
+    /// matches, so the random number generator is used the same way however
+    /// the matches were found. Returns the statistics of the substitutions
+    /// (without the tested positions and the duration).
+    pub(crate) fn apply_matches<M, R>(
+        &self,
+        matches: &[Vector2u],
+        destination: &mut M,
+        objects: &mut Vec<MapObject>,
+        rng: &mut R,
+    ) -> FilterStats
+    where
+        T: Clone + PartialEq,
+        M: MapStorage<T> + ?Sized,
+        R: Rng + ?Sized,
+    {
+        let mut stats = FilterStats {
+            matches: matches.len(),
+            ..Default::default()
+        };
+        for point in matches {
+            if self.roll_probability(rng) {
+                stats.changes += self.apply_substitute(destination, *point);
+                self.emit_objects(*point, objects);
+            } else {
+                stats.skipped += 1;
+            }
+        }
+        stats
+    }
+
+    /// Tests the positions whose pattern overlaps the given rectangle of
+    /// rewritten fields again, and adds them to or removes them from the given
+    /// matches (sorted column by column). Positions where the pattern isn't
+    /// completely inside the given mask never match. Returns the number of
+    /// tested positions.
+    fn update_matches(
+        &self,
+        input: &Map<T>,
+        rewritten: Rect2u,
+        mask: Option<&Map<bool>>,
+        matches: &mut Vec<(u32, u32)>,
+    ) -> usize
+    where
+        T: PartialEq,
+    {
+        let pattern_size = self.pattern.size();
+        let first = Vector2u::new(
+            (rewritten.position().x + 1).saturating_sub(pattern_size.x),
+            (rewritten.position().y + 1).saturating_sub(pattern_size.y),
+        );
+        let positions = Rect2u::new(first, rewritten.position() + rewritten.size() - first);
+        let Ok(positions) = positions else {
+            return 0;
+        };
+        let (start, end) = self.position_range(input.size(), Some(positions));
+        for x in start.x..end.x {
+            for y in start.y..end.y {
+                let point = Vector2u::new(x, y);
+                let matched = mask.is_none_or(|mask| self.pattern_in_mask(mask, point))
+                    && self.pattern_matches(input, point);
+                match (matches.binary_search(&(x, y)), matched) {
+                    (Ok(index), false) => {
+                        matches.remove(index);
                     }
+                    (Err(index), true) => matches.insert(index, (x, y)),
+                    _ => {}
                 }
             }
-            Ok(())
         }
+        (end.x - start.x) as usize * (end.y - start.y) as usize
+    }
+
+    /// Adds the objects of the filter, moved to the given substitute position,
+    /// to the given objects.
+    fn emit_objects(&self, position: Vector2u, objects: &mut Vec<MapObject>) {
+        let offset = (position.x as f32, position.y as f32);
+        objects.extend(self.objects.iter().map(|object| object.translated(offset)));
+    }
+
+    pub(crate) fn roll_probability<R>(&self, rng: &mut R) -> bool
+    where
+        R: Rng + ?Sized,
+    {
+        rng.random_range(0.0..1.0) < self.properties.probability
+    }
+}
+
+/// Fails to serialize a filter field that the serialized filters don't
+/// represent (objects, alternative fields and distance constraints), so they
+/// aren't lost silently. Empty fields are skipped instead.
+#[cfg(feature = "serde")]
+fn serialize_unsupported<V, S>(_value: &V, _serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    Err(serde::ser::Error::custom(
+        "filters with objects, alternatives or distance constraints can't be serialized",
+    ))
+}
+
+/// A filter as it's deserialized, before its pattern and substitute sizes are
+/// validated. If the properties are omitted, the filter inherits the
+/// properties of its filter collection.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(bound(deserialize = "T: Default + serde::Deserialize<'de>"))]
+struct RawFilter<T> {
+    pattern: Map<T>,
+    substitute: Map<T>,
+    #[serde(default)]
+    wildcard: T,
+    properties: Option<FilterProperties>,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<RawFilter<T>> for Filter<T> {
+    type Error = TisuError;
+
+    fn try_from(value: RawFilter<T>) -> Result<Self, Self::Error> {
+        Filter::new_with_properties(
+            value.pattern,
+            value.substitute,
+            value.wildcard,
+            value.properties.unwrap_or_default(),
+        )
+    }
+}
+
+/// A filter collection as it's deserialized.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(bound(deserialize = "T: Default + serde::Deserialize<'de>"))]
+struct RawFilterCollection<T> {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    filters: Vec<RawFilter<T>>,
+    #[serde(default)]
+    properties: FilterProperties,
+    #[serde(default)]
+    region_op: Option<RegionOp<T>>,
+    #[serde(default)]
+    connectivity: Option<Connectivity<T>>,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<RawFilterCollection<T>> for FilterCollection<T> {
+    type Error = TisuError;
+
+    fn try_from(value: RawFilterCollection<T>) -> Result<Self, Self::Error> {
+        let mut filters = vec![];
+        for mut filter in value.filters {
+            filter.properties = filter.properties.or_else(|| Some(value.properties.clone()));
+            filters.push(Filter::try_from(filter)?);
+        }
+        Ok(Self {
+            name: value.name,
+            filters,
+            properties: value.properties,
+            region_op: value.region_op,
+            connectivity: value.connectivity,
+        })
     }
 }
 
 /// A collection of map filters
-#[derive(Default)]
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        try_from = "RawFilterCollection<T>",
+        bound(
+            serialize = "T: Clone + serde::Serialize",
+            deserialize = "T: Default + serde::Deserialize<'de>"
+        )
+    )
+)]
 pub struct FilterCollection<T> {
+    /// Name of the filter collection
+    pub name: String,
     /// Vector containing the filters
     pub filters: Vec<Filter<T>>,
     pub properties: FilterProperties,
+    /// Region operation applied in place of the filters (see `RegionOp`)
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub region_op: Option<RegionOp<T>>,
+    /// Connectivity step applied in place of the filters (see `Connectivity`)
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub connectivity: Option<Connectivity<T>>,
 }
 
 impl<T> FilterCollection<T> {
@@ -272,8 +1486,11 @@ impl<T> FilterCollection<T> {
         Filter<T>: Clone,
     {
         Self {
+            name: String::new(),
             filters: filters.into(),
             properties: FilterProperties::default(),
+            region_op: None,
+            connectivity: None,
         }
     }
 
@@ -284,30 +1501,315 @@ impl<T> FilterCollection<T> {
         Filter<T>: Clone,
     {
         Self {
+            name: String::new(),
             filters: filters.into(),
             properties,
+            region_op: None,
+            connectivity: None,
         }
     }
 
-    /// Applies all the filters from the collection to the given map.
+    /// Applies all the filters from the collection to the given map. Returns
+    /// statistics of the application, with the statistics of each filter (see
+    /// `Filter::apply`).
     ///
     /// # Errors
     ///
     /// Returns an error if any of the filters from the collection can't be
-    /// applied to the map.
-    pub fn apply(&self, source: &Map<T>, destination: &mut Map<T>) -> Result<(), TisuError>
+    /// applied to the map, or if the collection is restricted to a region
+    /// (see `FilterProperties::region`) and no mask is given.
+    pub fn apply(
+        &self,
+        source: &Map<T>,
+        destination: &mut Map<T>,
+    ) -> Result<FilterCollectionStats, TisuError>
     where
-        T: Clone + PartialEq,
+        T: Clone + PartialEq + MaybeSync,
     {
+        self.apply_with_rng(source, destination, &mut rand::rng())
+    }
+
+    /// Applies all the filters from the collection to the given map, using the
+    /// given random number generator.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the filters from the collection can't be
+    /// applied to the map, or if the collection is restricted to a region
+    /// (see `FilterProperties::region`) and no mask is given.
+    pub fn apply_with_rng<R>(
+        &self,
+        source: &Map<T>,
+        destination: &mut Map<T>,
+        rng: &mut R,
+    ) -> Result<FilterCollectionStats, TisuError>
+    where
+        T: Clone + PartialEq + MaybeSync,
+        R: Rng + ?Sized,
+    {
+        self.apply_with(source, destination, &mut ApplyOptions::new(rng))
+    }
+
+    /// Applies the collection with the given options (see `ApplyOptions` and
+    /// `Filter::apply_with`): as a connectivity step, a region operation or in
+    /// markov mode if it's set up as one, or by applying its filters
+    /// `iterations` times otherwise. An index of the source map speeds up
+    /// finding pattern matches of large maps and collections with many
+    /// filters, with identical results.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the filters from the collection can't be
+    /// applied to the map, or if the collection is restricted to a region
+    /// (see `FilterProperties::region`) and no mask is given.
+    pub fn apply_with<R>(
+        &self,
+        source: &Map<T>,
+        destination: &mut Map<T>,
+        options: &mut ApplyOptions<T, R>,
+    ) -> Result<FilterCollectionStats, TisuError>
+    where
+        T: Clone + PartialEq + MaybeSync,
+        R: Rng + ?Sized,
+    {
+        self.check_region(options.mask)?;
+        if let Some(connectivity) = &self.connectivity {
+            return self.apply_connectivity(connectivity, destination, options.mask);
+        }
+        if let Some(region_op) = &self.region_op {
+            return self.apply_region_op(region_op, source, destination, options.mask, options.rng);
+        }
+        if self.properties.mode == ApplyMode::Markov {
+            if options
+                .mask
+                .is_some_and(|mask| mask.size() != source.size())
+            {
+                return Err(TisuError::InvalidMapSize);
+            }
+            return self.apply_markov(destination, options);
+        }
+        let start = Instant::now();
+        let mut stats = FilterCollectionStats::new(self.filters.len());
         for _ in 0..self.properties.iterations {
-            for filter in &self.filters {
-                filter.apply(source, destination)?;
+            for (idx, filter) in self.filters.iter().enumerate() {
+                stats.filters[idx] += filter.apply_with(source, destination, options)?;
+                if let Some(callback) = options.callback.as_mut() {
+                    callback(idx, destination);
+                }
             }
         }
 
+        stats.duration = start.elapsed();
+        Ok(stats)
+    }
+
+    /// Checks that a mask is given if the collection is restricted to a
+    /// region, as regions are resolved to masks by the caller (see
+    /// `region_mask::resolve`).
+    fn check_region(&self, mask: Option<&Map<bool>>) -> Result<(), TisuError> {
+        if self.properties.region.is_some() && mask.is_none() {
+            return Err(TisuError::InvalidArgument);
+        }
         Ok(())
     }
 
+    /// Applies the filters in markov mode: each step searches the destination
+    /// map for the pattern matches of all the filters, and applies the
+    /// substitute at one of them, chosen at random. Steps are repeated until
+    /// there are no matches left or `max_steps` is reached (by default, the
+    /// number of fields of the map). The matches are searched once, and after
+    /// each step only the positions whose pattern overlaps the substitute are
+    /// tested again. Filter probabilities and the number of iterations are not
+    /// used. The callback of the options is called with the filter index and
+    /// the destination map after each step. Filter statistics add up the
+    /// pattern matches of all the steps.
+    fn apply_markov<R>(
+        &self,
+        destination: &mut Map<T>,
+        options: &mut ApplyOptions<T, R>,
+    ) -> Result<FilterCollectionStats, TisuError>
+    where
+        T: Clone + PartialEq + MaybeSync,
+        R: Rng + ?Sized,
+    {
+        let mask = options.mask;
+        let filters: Vec<(usize, &Filter<T>)> = self
+            .filters
+            .iter()
+            .enumerate()
+            .filter(|(_, filter)| !filter.properties.ignore)
+            .collect();
+        for (_, filter) in &filters {
+            if destination.size().x < filter.pattern.size().x
+                || destination.size().y < filter.pattern.size().y
+            {
+                return Err(TisuError::InvalidMapSize);
+            }
+        }
+
+        // Substitutes that keep matching would never run out of matches, so
+        // the steps are limited to the number of fields by default
+        let max_steps = self.properties.max_steps.unwrap_or_else(|| {
+            let size = destination.size();
+            u32::try_from(u64::from(size.x) * u64::from(size.y)).unwrap_or(u32::MAX)
+        });
+        let start = Instant::now();
+        let mut stats = FilterCollectionStats::new(self.filters.len());
+        // Pattern matches of each filter, column by column like the positions
+        // visited by `find_matches`
+        let mut matches = Vec::with_capacity(filters.len());
+        for (idx, filter) in &filters {
+            let filter_start = Instant::now();
+            let filter_matches: Vec<(u32, u32)> = filter
+                .find_matches(destination)
+                .into_iter()
+                .filter(|point| mask.is_none_or(|mask| filter.pattern_in_mask(mask, *point)))
+                .map(|point| (point.x, point.y))
+                .collect();
+            let filter_stats = &mut stats.filters[*idx];
+            filter_stats.positions_tested += filter.position_count(destination.size());
+            filter_stats.duration += filter_start.elapsed();
+            matches.push(filter_matches);
+        }
+
+        let mut steps = 0;
+        while steps < max_steps {
+            for ((idx, _), filter_matches) in filters.iter().zip(&matches) {
+                stats.filters[*idx].matches += filter_matches.len();
+            }
+            let count: usize = matches.iter().map(Vec::len).sum();
+            if count == 0 {
+                break;
+            }
+
+            let mut choice = options.rng.random_range(0..count);
+            let mut chosen = 0;
+            while choice >= matches[chosen].len() {
+                choice -= matches[chosen].len();
+                chosen += 1;
+            }
+            let (idx, filter) = filters[chosen];
+            let point = Vector2u::from(matches[chosen][choice]);
+            let changes = filter.apply_substitute(destination, point);
+            stats.filters[idx].changes += changes;
+            if let Some(objects) = options.objects.as_deref_mut() {
+                filter.emit_objects(point, objects);
+            }
+            if let Some(callback) = options.callback.as_mut() {
+                callback(idx, destination);
+            }
+            steps += 1;
+
+            // Only the positions whose pattern overlaps the substitute can
+            // match differently than before
+            if changes == 0 {
+                continue;
+            }
+            let rewritten = Rect2u::new(point, filter.substitute.size())?;
+            for ((idx, filter), filter_matches) in filters.iter().zip(&mut matches) {
+                let filter_start = Instant::now();
+                let filter_stats = &mut stats.filters[*idx];
+                filter_stats.positions_tested +=
+                    filter.update_matches(destination, rewritten, mask, filter_matches);
+                filter_stats.duration += filter_start.elapsed();
+            }
+        }
+
+        stats.duration = start.elapsed();
+        Ok(stats)
+    }
+
+    /// Applies the region operation of the collection `iterations` times. The
+    /// regions are found on the source or the destination map, depending on
+    /// the `pattern_matching` property. The statistics contain a single entry
+    /// for the region operation.
+    fn apply_region_op<R>(
+        &self,
+        region_op: &RegionOp<T>,
+        source: &Map<T>,
+        destination: &mut Map<T>,
+        mask: Option<&Map<bool>>,
+        rng: &mut R,
+    ) -> Result<FilterCollectionStats, TisuError>
+    where
+        T: Clone + PartialEq,
+        R: Rng + ?Sized,
+    {
+        let start = Instant::now();
+        let mut stats = FilterCollectionStats::new(1);
+        if self.properties.ignore {
+            return Ok(stats);
+        }
+        for _ in 0..self.properties.iterations {
+            stats.filters[0] += match self.properties.pattern_matching {
+                PatternMatching::Source => {
+                    region_op.apply_with_rng(source, destination, mask, rng)?
+                }
+                PatternMatching::Destination => {
+                    let matched = destination.clone();
+                    region_op.apply_with_rng(&matched, destination, mask, rng)?
+                }
+            };
+        }
+
+        stats.duration = start.elapsed();
+        Ok(stats)
+    }
+
+    /// Applies the connectivity step of the collection to the destination map.
+    /// The statistics contain a single entry for the connectivity step.
+    fn apply_connectivity(
+        &self,
+        connectivity: &Connectivity<T>,
+        destination: &mut Map<T>,
+        mask: Option<&Map<bool>>,
+    ) -> Result<FilterCollectionStats, TisuError>
+    where
+        T: Clone + PartialEq,
+    {
+        let start = Instant::now();
+        let mut stats = FilterCollectionStats::new(1);
+        if self.properties.ignore {
+            return Ok(stats);
+        }
+        stats.filters[0] = connectivity.apply(destination, mask)?;
+
+        stats.duration = start.elapsed();
+        Ok(stats)
+    }
+
+    /// Checks if any filter of the collection emits objects.
+    pub fn has_objects(&self) -> bool {
+        self.filters.iter().any(|filter| !filter.objects.is_empty())
+    }
+
+    /// Checks if any filter of the collection has alternative fields (see
+    /// `Filter::set_alternatives`).
+    pub fn has_alternatives(&self) -> bool {
+        self.filters
+            .iter()
+            .any(|filter| !filter.alternatives.is_empty())
+    }
+
+    /// Checks if any filter of the collection has distance constraints (see
+    /// `Filter::set_distance_constraints`).
+    pub fn has_distance_constraints(&self) -> bool {
+        self.filters
+            .iter()
+            .any(|filter| !filter.distance_constraints.is_empty())
+    }
+
+    /// Makes all filters of the collection use the orientations of their
+    /// field type (see `Filter::set_field_orientation`).
+    pub fn set_field_orientation(&mut self)
+    where
+        T: FieldOrientation + Clone + PartialEq,
+    {
+        for filter in &mut self.filters {
+            filter.set_field_orientation();
+        }
+    }
+
     pub fn push(&mut self, filter: Filter<T>) {
         self.filters.push(filter);
     }
@@ -0,0 +1,94 @@
+use rand::Rng;
+
+use crate::filter::{FilterCollection, MaybeSync};
+use crate::map::Map;
+use crate::sequence;
+use crate::tisu_error::TisuError;
+use crate::vector2::Vector2u;
+
+/// An example input map and the output map that is expected after applying
+/// filters to it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FilterExample<T> {
+    /// Name of the example
+    pub name: String,
+    /// Map that the filters are applied to
+    pub input: Map<T>,
+    /// Map expected after the filters have been applied
+    pub expected_output: Map<T>,
+}
+
+/// A field of the output map that differs from the expected output.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Mismatch<T> {
+    /// Position of the field
+    pub position: Vector2u,
+    /// Expected field value
+    pub expected: T,
+    /// Actual field value
+    pub actual: T,
+}
+
+/// Result of running a filter example.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FilterExampleResult<T> {
+    /// Map produced by applying the filters to the example input
+    pub output: Map<T>,
+    /// Fields of the output map that differ from the expected output
+    pub mismatches: Vec<Mismatch<T>>,
+}
+
+impl<T> FilterExampleResult<T> {
+    /// Checks if the output map matches the expected output.
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl<T> FilterExample<T> {
+    /// Applies the given filter collections to the example input (see
+    /// `sequence::apply_sequence`), using the given random number generator,
+    /// and compares the result against the
+    /// expected output.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the filters can't be applied to the input map
+    /// (including filter collections restricted to a region, as examples have
+    /// no regions) or if the input and expected output maps have different
+    /// sizes.
+    pub fn run<R>(
+        &self,
+        filter_collections: &[FilterCollection<T>],
+        rng: &mut R,
+    ) -> Result<FilterExampleResult<T>, TisuError>
+    where
+        T: Clone + PartialEq + MaybeSync,
+        R: Rng + ?Sized,
+    {
+        if self.input.size() != self.expected_output.size() {
+            return Err(TisuError::InvalidMapSize);
+        }
+
+        let mut output = self.input.clone();
+        sequence::apply_sequence_with_rng(filter_collections, &self.input, &mut output, rng)?;
+
+        let mut mismatches = vec![];
+        for y in 0..output.size().y {
+            for x in 0..output.size().x {
+                let position = Vector2u::new(x, y);
+                let expected = self.expected_output.get(position)?;
+                let actual = output.get(position)?;
+                if expected != actual {
+                    mismatches.push(Mismatch {
+                        position,
+                        expected: expected.clone(),
+                        actual: actual.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(FilterExampleResult { output, mismatches })
+    }
+}
@@ -0,0 +1,69 @@
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    filter::{Filter, FilterCollection},
+    filter_example::{FilterExample, Mismatch},
+    map::Map,
+    tisu_error::TisuError,
+};
+
+fn create_filter_collection() -> FilterCollection<u32> {
+    // 1 0
+    let pattern = Map::<u32>::from_data([[1, 0]]).unwrap();
+    // 0 1
+    let substitute = Map::<u32>::from_data([[0, 1]]).unwrap();
+    let filter = Filter::new(pattern, substitute, 42).unwrap();
+    FilterCollection::new(&[filter])
+}
+
+#[test]
+fn test_run_passed() {
+    let example = FilterExample {
+        name: String::from("passed"),
+        input: Map::<u32>::from_data([[1, 0, 1], [1, 1, 0]]).unwrap(),
+        expected_output: Map::<u32>::from_data([[0, 1, 1], [1, 0, 1]]).unwrap(),
+    };
+
+    let result = example.run(&[create_filter_collection()], &mut StdRng::seed_from_u64(0));
+
+    assert!(result.is_ok());
+    let result = result.unwrap();
+    assert!(result.passed());
+    assert_eq!(result.output, example.expected_output);
+}
+
+#[test]
+fn test_run_failed() {
+    let example = FilterExample {
+        name: String::from("failed"),
+        input: Map::<u32>::from_data([[1, 0, 1], [1, 1, 0]]).unwrap(),
+        expected_output: Map::<u32>::from_data([[0, 1, 1], [1, 1, 1]]).unwrap(),
+    };
+
+    let result = example.run(&[create_filter_collection()], &mut StdRng::seed_from_u64(0));
+
+    assert!(result.is_ok());
+    let result = result.unwrap();
+    assert!(!result.passed());
+    assert_eq!(
+        result.mismatches,
+        [Mismatch {
+            position: (1, 1).into(),
+            expected: 1,
+            actual: 0,
+        }]
+    );
+}
+
+#[test]
+fn test_run_failure() {
+    let example = FilterExample {
+        name: String::from("invalid"),
+        input: Map::<u32>::new((3, 2).into()),
+        expected_output: Map::<u32>::new((2, 3).into()),
+    };
+
+    let result = example.run(&[create_filter_collection()], &mut StdRng::seed_from_u64(0));
+
+    assert_eq!(result.err().unwrap(), TisuError::InvalidMapSize);
+}
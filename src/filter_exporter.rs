@@ -0,0 +1,14 @@
+use std::path::Path;
+
+use crate::{filter::FilterCollection, tisu_error::TisuError, vector2::Vector2u};
+
+pub trait FilterExporter {
+    type TileType;
+
+    fn save(
+        file: impl AsRef<Path>,
+        filter_collections: &[FilterCollection<Self::TileType>],
+        tile_size: Vector2u,
+        tileset_path: impl AsRef<Path>,
+    ) -> Result<(), TisuError>;
+}
@@ -0,0 +1,217 @@
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "serde")]
+use crate::structured_filter_importer::StructuredFilterImporter;
+use crate::{
+    filter::FilterCollection, text_filter_importer::TextFilterImporter,
+    tiled_filter_importer::TiledFilterImporter, tiled_tile::TiledTile, tisu_error::TisuError,
+};
+
+/// Format of a filter file.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FilterFormat {
+    /// Tiled map (`.tmx`)
+    Tiled,
+    /// Plain text (`.txt`)
+    Text,
+    /// JSON, TOML or YAML (`.json`, `.toml`, `.yaml`, `.yml`)
+    #[cfg(feature = "serde")]
+    Structured,
+}
+
+impl TryFrom<&Path> for FilterFormat {
+    type Error = TisuError;
+
+    fn try_from(value: &Path) -> Result<Self, Self::Error> {
+        match value.extension().and_then(|extension| extension.to_str()) {
+            Some("tmx") => Ok(FilterFormat::Tiled),
+            Some("txt") => Ok(FilterFormat::Text),
+            #[cfg(feature = "serde")]
+            Some("json") | Some("toml") | Some("yaml") | Some("yml") => {
+                Ok(FilterFormat::Structured)
+            }
+            _ => Err(TisuError::InvalidArgument),
+        }
+    }
+}
+
+impl FilterFormat {
+    /// Loads the filter collections of the given file, along with the paths of
+    /// the filter files it includes (relative to the given file).
+    fn load_file(
+        self,
+        file: &Path,
+    ) -> Result<(Vec<FilterCollection<TiledTile>>, Vec<PathBuf>), TisuError> {
+        match self {
+            FilterFormat::Tiled => TiledFilterImporter::load_file(file),
+            FilterFormat::Text => TextFilterImporter::load_file(file),
+            #[cfg(feature = "serde")]
+            FilterFormat::Structured => StructuredFilterImporter::load_file(file),
+        }
+    }
+}
+
+/// Loads the filter collections of the given filter file, using the format
+/// that matches the file extension. Included filter files are resolved (see
+/// `load_with_includes`).
+///
+/// # Errors
+///
+/// Returns an error if the file format isn't supported or if the file or one
+/// of its included files can't be loaded.
+pub fn load_filters(file: impl AsRef<Path>) -> Result<Vec<FilterCollection<TiledTile>>, TisuError> {
+    let format = FilterFormat::try_from(file.as_ref())?;
+    load_with_includes(file, format)
+}
+
+/// Loads the filter collections of the given filter file like `load_filters`,
+/// along with the canonical paths of all the loaded filter files (the given
+/// file followed by its included files).
+///
+/// # Errors
+///
+/// Returns an error if the file format isn't supported or if the file or one
+/// of its included files can't be loaded.
+pub fn load_filters_with_files(
+    file: impl AsRef<Path>,
+) -> Result<(Vec<FilterCollection<TiledTile>>, Vec<PathBuf>), TisuError> {
+    let format = FilterFormat::try_from(file.as_ref())?;
+    let mut result = vec![];
+    let mut files = vec![];
+    load_recursive(file.as_ref(), format, &mut vec![], &mut files, &mut result)?;
+    Ok((result, files))
+}
+
+/// Loads the filter collections of the given filter file in the given format,
+/// resolving included filter files. Include paths are relative to the
+/// including file and may point to files of any format. The filter
+/// collections of included files come before the filter collections of the
+/// including file, in the order of the includes.
+///
+/// # Errors
+///
+/// Returns an error if the file or one of its included files can't be loaded,
+/// or if the includes form a cycle.
+pub fn load_with_includes(
+    file: impl AsRef<Path>,
+    format: FilterFormat,
+) -> Result<Vec<FilterCollection<TiledTile>>, TisuError> {
+    let mut result = vec![];
+    load_recursive(file.as_ref(), format, &mut vec![], &mut vec![], &mut result)?;
+    Ok(result)
+}
+
+fn load_recursive(
+    file: &Path,
+    format: FilterFormat,
+    stack: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+    result: &mut Vec<FilterCollection<TiledTile>>,
+) -> Result<(), TisuError> {
+    let canonical = file
+        .canonicalize()
+        .map_err(|_| TisuError::InvalidArgument)?;
+    if stack.contains(&canonical) {
+        return Err(TisuError::InvalidArgument);
+    }
+
+    let (filter_collections, includes) = format.load_file(file)?;
+    if !files.contains(&canonical) {
+        files.push(canonical.clone());
+    }
+    stack.push(canonical);
+    let directory = file.parent().unwrap_or(Path::new(""));
+    for include in includes {
+        let include = directory.join(include);
+        let include_format = FilterFormat::try_from(include.as_path())?;
+        load_recursive(&include, include_format, stack, files, result)?;
+    }
+    stack.pop();
+
+    result.extend(filter_collections);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::filter_importer::FilterImporter;
+
+    use super::*;
+
+    fn test_file(name: &str) -> PathBuf {
+        PathBuf::from(format!(
+            "{}/data/test_include/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        ))
+    }
+
+    #[test]
+    fn test_filter_format() {
+        assert_eq!(
+            FilterFormat::try_from(Path::new("filters.tmx")),
+            Ok(FilterFormat::Tiled)
+        );
+        assert_eq!(
+            FilterFormat::try_from(Path::new("filters.txt")),
+            Ok(FilterFormat::Text)
+        );
+        assert_eq!(
+            FilterFormat::try_from(Path::new("filters")),
+            Err(TisuError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_load_filters() {
+        let result = load_filters(test_file("main.txt"));
+
+        assert!(result.is_ok());
+        let names: Vec<_> = result
+            .unwrap()
+            .into_iter()
+            .map(|filter_collection| filter_collection.name)
+            .collect();
+        assert_eq!(names, ["Base", "Layer 1", "Main"]);
+    }
+
+    #[test]
+    fn test_load_filters_importer() {
+        // Importers resolve includes as well
+        let result = TiledFilterImporter::load(test_file("tiled.tmx"));
+
+        assert!(result.is_ok());
+        let names: Vec<_> = result
+            .unwrap()
+            .into_iter()
+            .map(|filter_collection| filter_collection.name)
+            .collect();
+        assert_eq!(names, ["Base", "Layer 1"]);
+    }
+
+    #[test]
+    fn test_load_filters_with_files() {
+        let result = load_filters_with_files(test_file("main.txt"));
+
+        assert!(result.is_ok());
+        let (filter_collections, files) = result.unwrap();
+        assert_eq!(filter_collections.len(), 3);
+        let file_names: Vec<_> = files
+            .iter()
+            .map(|file| file.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(file_names, ["main.txt", "tiled.tmx", "base.txt"]);
+    }
+
+    #[test]
+    fn test_load_filters_failure() {
+        assert_eq!(
+            load_filters(test_file("cycle.txt")),
+            Err(TisuError::InvalidArgument)
+        );
+        assert_eq!(
+            load_filters(test_file("missing.txt")),
+            Err(TisuError::InvalidArgument)
+        );
+    }
+}
@@ -1,14 +1,22 @@
 use std::path::Path;
 
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
 use crate::{
-    filter::{Filter, FilterCollection},
+    filter::{
+        ApplyMode, ApplyOptions, Filter, FilterCollection, FilterProperties, MatchFlips,
+        PatternMatching, SubstituteFlips,
+    },
     filter_importer::FilterImporter,
     map::Map,
     map_importer::MapImporter,
+    map_object::MapObject,
+    tile_index::TileIndex,
     tiled_filter_importer::TiledFilterImporter,
     tiled_map_importer::TiledMapImporter,
     tiled_tile::TiledTile,
     tisu_error::TisuError,
+    vector2::Vector2u,
 };
 
 #[test]
@@ -234,6 +242,52 @@ fn test_apply_filter_collection_failure() {
     assert_eq!(result.err().unwrap(), TisuError::InvalidMapSize);
 }
 
+#[test]
+fn test_apply_filter_collection_region_failure() {
+    let map = Map::<u32>::from_data([[1, 0], [0, 1]]).unwrap();
+    let filter = Filter::new(
+        Map::from_data([[1]]).unwrap(),
+        Map::from_data([[2]]).unwrap(),
+        42,
+    )
+    .unwrap();
+    let mut filter_collection = FilterCollection::new(&[filter]);
+    filter_collection.properties.region = Some(String::from("Spawn"));
+    let mut destination = map.clone();
+
+    // The region can only be applied with its mask
+    assert_eq!(
+        filter_collection.apply(&map, &mut destination).err(),
+        Some(TisuError::InvalidArgument)
+    );
+    let index = TileIndex::new(&map);
+    let mut objects = vec![];
+    let mut rng = rand::rng();
+    let options = &mut ApplyOptions {
+        objects: Some(&mut objects),
+        index: Some(&index),
+        ..ApplyOptions::new(&mut rng)
+    };
+    assert_eq!(
+        filter_collection
+            .apply_with(&map, &mut destination, options)
+            .err(),
+        Some(TisuError::InvalidArgument)
+    );
+    assert_eq!(destination, map);
+
+    let mask = Map::from_data([[true, false], [false, false]]).unwrap();
+    let mut rng = rand::rng();
+    let options = &mut ApplyOptions {
+        mask: Some(&mask),
+        ..ApplyOptions::new(&mut rng)
+    };
+    let result = filter_collection.apply_with(&map, &mut destination, options);
+
+    assert!(result.is_ok());
+    assert_eq!(destination, Map::from_data([[2, 0], [0, 1]]).unwrap());
+}
+
 #[test]
 fn test_filter_collection_push() {
     let mut fc = FilterCollection::<u32>::default();
@@ -257,7 +311,7 @@ struct TestData {
 
 fn load_test_map(file_path: impl AsRef<Path>) -> Map<TiledTile> {
     let result = TiledMapImporter::load(file_path);
-    result.unwrap().map_layers[0].clone()
+    result.unwrap().map.top_tile_layer().unwrap().clone()
 }
 
 fn load_test_data(test_name: &str) -> TestData {
@@ -351,3 +405,782 @@ fn apply_filter_collection_pattern_matching() {
 
     assert_eq!(test_data.expected_output, destination);
 }
+
+#[test]
+fn test_find_matches() {
+    let input = Map::<u32>::from_data([[1, 2, 1], [2, 1, 2], [1, 2, 1]]).unwrap();
+    let pattern = Map::<u32>::from_data([[1, 0]]).unwrap();
+    let filter = Filter::new(pattern.clone(), pattern, 0).unwrap();
+
+    let matches = filter.find_matches(&input);
+
+    assert_eq!(
+        matches,
+        [(0, 0).into(), (0, 2).into(), (1, 1).into()] as [Vector2u; 3]
+    );
+}
+
+#[test]
+fn test_find_matches_small_map() {
+    let input = Map::<u32>::new((1, 1).into());
+    let pattern = Map::<u32>::new((2, 2).into());
+    let filter = Filter::new(pattern.clone(), pattern, 0).unwrap();
+
+    assert!(filter.find_matches(&input).is_empty());
+}
+
+#[test]
+fn test_apply_filter_source_deterministic() {
+    let input = Map::<u32>::from_data([[1; 16]; 16]).unwrap();
+    let pattern = Map::<u32>::from_data([[1, 1]]).unwrap();
+    let substitute = Map::<u32>::from_data([[2, 0]]).unwrap();
+    let filter = Filter::new_with_properties(
+        pattern,
+        substitute,
+        0,
+        FilterProperties {
+            probability: 0.5,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    // Substitutions are applied column by column, each match rolling the
+    // probability once
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut expected = input.clone();
+    for x in 0..15 {
+        for y in 0..16 {
+            if rng.random_range(0.0..1.0) < 0.5f32 {
+                expected.set((x, y).into(), 2).unwrap();
+            }
+        }
+    }
+    let mut output = input.clone();
+    let result = filter.apply_with_rng(&input, &mut output, &mut StdRng::seed_from_u64(7));
+
+    assert!(result.is_ok());
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn test_find_matches_indexed() {
+    let input = Map::<u32>::from_data([[1, 2, 1], [2, 1, 2], [1, 2, 1]]).unwrap();
+    let index = TileIndex::new(&input);
+
+    for data in [[[1, 0]], [[0, 2]], [[2, 1]], [[0, 0]], [[3, 1]]] {
+        let pattern = Map::<u32>::from_data(data).unwrap();
+        let filter = Filter::new(pattern.clone(), pattern, 0).unwrap();
+
+        assert_eq!(
+            filter.find_matches_indexed(&input, &index),
+            filter.find_matches(&input)
+        );
+    }
+}
+
+#[test]
+fn test_apply_filter_collection_indexed() {
+    let filter_collections = TiledFilterImporter::load(format!(
+        "{}/data/test_apply_filter_collection_pattern_matching/filter_collection.tmx",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap();
+    let input = TiledMapImporter::load(format!(
+        "{}/data/test_apply_filter_collection_pattern_matching/input.tmx",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap()
+    .map
+    .top_tile_layer()
+    .unwrap()
+    .clone();
+
+    let mut expected = input.clone();
+    let mut output = input.clone();
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut indexed_rng = StdRng::seed_from_u64(3);
+    let index = TileIndex::new(&input);
+    for filter_collection in &filter_collections {
+        filter_collection
+            .apply_with_rng(&input, &mut expected, &mut rng)
+            .unwrap();
+        let options = &mut ApplyOptions {
+            index: Some(&index),
+            ..ApplyOptions::new(&mut indexed_rng)
+        };
+        let result = filter_collection.apply_with(&input, &mut output, options);
+        assert!(result.is_ok());
+    }
+
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn test_apply_filter_with_index_failure() {
+    let filter = Filter::new(
+        Map::<u32>::new((1, 1).into()),
+        Map::<u32>::new((1, 1).into()),
+        0,
+    )
+    .unwrap();
+    let source = Map::<u32>::new((2, 2).into());
+    let mut destination = source.clone();
+    let index = TileIndex::new(&Map::<u32>::new((3, 3).into()));
+
+    let mut rng = rand::rng();
+    let options = &mut ApplyOptions {
+        index: Some(&index),
+        ..ApplyOptions::new(&mut rng)
+    };
+    let result = filter.apply_with(&source, &mut destination, options);
+
+    assert_eq!(result.err().unwrap(), TisuError::InvalidMapSize);
+}
+
+#[test]
+fn test_apply_filter_with_mask() {
+    let source = Map::<u32>::from_data([[1, 1, 1], [1, 1, 1]]).unwrap();
+    let mask = Map::from_data([[true, true, false], [false, true, true]]).unwrap();
+    for pattern_matching in [PatternMatching::Source, PatternMatching::Destination] {
+        let filter = Filter::new_with_properties(
+            Map::from_data([[1, 1]]).unwrap(),
+            Map::from_data([[2, 0]]).unwrap(),
+            0,
+            FilterProperties {
+                pattern_matching,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut destination = source.clone();
+
+        let mut rng = rand::rng();
+        let options = &mut ApplyOptions {
+            mask: Some(&mask),
+            ..ApplyOptions::new(&mut rng)
+        };
+        let result = filter.apply_with(&source, &mut destination, options);
+
+        assert!(result.is_ok());
+        assert_eq!(destination, Map::from_data([[2, 1, 1], [1, 2, 1]]).unwrap());
+        // Only the 2 of the 4 positions where the pattern is inside the mask
+        // are tested, with both pattern matching modes
+        let stats = result.unwrap();
+        assert_eq!(stats.positions_tested, 2);
+        assert_eq!(stats.matches, 2);
+    }
+}
+
+#[test]
+fn test_apply_filter_with_mask_failure() {
+    let filter = Filter::new(
+        Map::<u32>::new((1, 1).into()),
+        Map::<u32>::new((1, 1).into()),
+        0,
+    )
+    .unwrap();
+    let source = Map::<u32>::new((2, 2).into());
+    let mut destination = source.clone();
+
+    let mask = Map::new((3, 2).into());
+    let mut rng = rand::rng();
+    let options = &mut ApplyOptions {
+        mask: Some(&mask),
+        ..ApplyOptions::new(&mut rng)
+    };
+    let result = filter.apply_with(&source, &mut destination, options);
+
+    assert_eq!(result.err().unwrap(), TisuError::InvalidMapSize);
+}
+
+#[test]
+fn test_apply_filter_with_objects() {
+    let map = Map::<u32>::from_data([[1, 0, 1], [0, 0, 1]]).unwrap();
+    let pattern = Map::<u32>::from_data([[1]]).unwrap();
+    let substitute = Map::<u32>::from_data([[2]]).unwrap();
+    let mut filter = Filter::new(pattern, substitute, 42).unwrap();
+    let object = MapObject {
+        name: String::from("Light"),
+        position: (0.5, 0.25),
+        ..Default::default()
+    };
+    filter.set_objects(vec![object.clone()]);
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut destination = map.clone();
+    let mut objects = vec![];
+
+    let options = &mut ApplyOptions {
+        objects: Some(&mut objects),
+        ..ApplyOptions::new(&mut rng)
+    };
+    let result = filter.apply_with(&map, &mut destination, options);
+
+    assert!(result.is_ok());
+    assert_eq!(
+        destination,
+        Map::<u32>::from_data([[2, 0, 2], [0, 0, 2]]).unwrap()
+    );
+    assert_eq!(
+        objects,
+        [
+            object.translated((0.0, 0.0)),
+            object.translated((2.0, 0.0)),
+            object.translated((2.0, 1.0)),
+        ]
+    );
+}
+
+#[test]
+fn test_apply_filter_collection_with_objects_in_mask() {
+    let map = Map::<u32>::from_data([[1, 1], [1, 1]]).unwrap();
+    let pattern = Map::<u32>::from_data([[1]]).unwrap();
+    let substitute = Map::<u32>::from_data([[42]]).unwrap();
+    let mut filter = Filter::new(pattern, substitute, 42).unwrap();
+    filter.set_objects(vec![MapObject::default()]);
+    let filter_collection = FilterCollection::new(&[filter]);
+    let mask = Map::<bool>::from_data([[false, true], [false, false]]).unwrap();
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut destination = map.clone();
+    let mut objects = vec![];
+
+    let options = &mut ApplyOptions {
+        mask: Some(&mask),
+        objects: Some(&mut objects),
+        ..ApplyOptions::new(&mut rng)
+    };
+    let result = filter_collection.apply_with(&map, &mut destination, options);
+
+    assert!(result.is_ok());
+    assert!(filter_collection.has_objects());
+    assert_eq!(destination, map);
+    assert_eq!(objects, [MapObject::default().translated((1.0, 0.0))]);
+}
+
+#[test]
+fn test_pattern_matches_alternatives() {
+    let map = Map::<u32>::from_data([[1, 0], [2, 1], [3, 1]]).unwrap();
+    let pattern = Map::<u32>::from_data([[7, 1]]).unwrap();
+    let substitute = Map::<u32>::from_data([[0, 0]]).unwrap();
+    let mut filter = Filter::new(pattern, substitute, 42).unwrap();
+
+    assert!(filter.set_alternatives((0, 0).into(), vec![1, 2]).is_ok());
+
+    assert!(!filter.pattern_matches(&map, (0, 0).into()));
+    assert!(filter.pattern_matches(&map, (0, 1).into()));
+    assert!(!filter.pattern_matches(&map, (0, 2).into()));
+    let index = TileIndex::new(&map);
+    assert_eq!(filter.find_matches_indexed(&map, &index), [(0, 1).into()]);
+}
+
+#[test]
+fn test_set_alternatives_failure() {
+    let pattern = Map::<u32>::from_data([[7, 1]]).unwrap();
+    let substitute = Map::<u32>::from_data([[0, 0]]).unwrap();
+    let mut filter = Filter::new(pattern, substitute, 42).unwrap();
+
+    let result = filter.set_alternatives((2, 0).into(), vec![1]);
+
+    assert_eq!(result, Err(TisuError::OutOfBounds));
+    assert!(filter.alternatives().is_empty());
+}
+
+fn create_flipped_tile(index: u32, flip_h: bool, flip_v: bool) -> TiledTile {
+    TiledTile {
+        index: Some(index),
+        flip_h,
+        flip_v,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_apply_filter_match_flips() {
+    let map = Map::from_data([[
+        create_flipped_tile(1, false, false),
+        create_flipped_tile(1, true, false),
+        create_flipped_tile(2, true, false),
+    ]])
+    .unwrap();
+    let pattern = Map::from_data([[create_flipped_tile(1, false, false)]]).unwrap();
+    let substitute = Map::from_data([[create_flipped_tile(3, false, false)]]).unwrap();
+    let mut filter = Filter::new(pattern, substitute, TiledTile::default()).unwrap();
+    let index = TileIndex::new(&map);
+
+    assert_eq!(filter.find_matches(&map), [(0, 0).into()]);
+
+    filter.set_properties(FilterProperties {
+        match_flips: MatchFlips::Ignore,
+        ..Default::default()
+    });
+
+    // Without field orientations, fields are compared exactly
+    assert_eq!(filter.find_matches(&map), [(0, 0).into()]);
+
+    filter.set_field_orientation();
+
+    assert_eq!(filter.find_matches(&map), [(0, 0).into(), (1, 0).into()]);
+    assert_eq!(
+        filter.find_matches_indexed(&map, &index),
+        [(0, 0).into(), (1, 0).into()]
+    );
+}
+
+#[test]
+fn test_apply_filter_substitute_flips() {
+    let map = Map::from_data([[
+        create_flipped_tile(1, true, false),
+        create_flipped_tile(1, false, true),
+    ]])
+    .unwrap();
+    let pattern = Map::from_data([[create_flipped_tile(1, false, false)]]).unwrap();
+    let substitute = Map::from_data([[create_flipped_tile(3, true, true)]]).unwrap();
+    let mut filter = Filter::new_with_properties(
+        pattern,
+        substitute,
+        TiledTile::default(),
+        FilterProperties {
+            match_flips: MatchFlips::Ignore,
+            substitute_flips: SubstituteFlips::Copy,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    filter.set_field_orientation();
+    let mut destination = map.clone();
+
+    let result = filter.apply(&map, &mut destination);
+
+    assert!(result.is_ok());
+    assert_eq!(
+        destination,
+        Map::from_data([[
+            create_flipped_tile(3, true, false),
+            create_flipped_tile(3, false, true),
+        ]])
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_apply_filter_collection_markov() {
+    // Grows a trail to the right, one field per step
+    let input = Map::<u32>::from_data([[1, 0, 0, 0, 0], [0, 0, 1, 0, 0]]).unwrap();
+    let filter = Filter::new(
+        Map::from_data([[1, 0]]).unwrap(),
+        Map::from_data([[2, 1]]).unwrap(),
+        9,
+    )
+    .unwrap();
+    let filter_collection = FilterCollection::new_with_properties(
+        &[filter],
+        FilterProperties {
+            mode: ApplyMode::Markov,
+            ..Default::default()
+        },
+    );
+    let mut output = input.clone();
+    let mut steps = 0;
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let options = &mut ApplyOptions {
+        callback: Some(&mut |_, _| steps += 1),
+        ..ApplyOptions::new(&mut rng)
+    };
+    let result = filter_collection.apply_with(&input, &mut output, options);
+
+    assert!(result.is_ok());
+    assert_eq!(
+        output,
+        Map::from_data([[2, 2, 2, 2, 1], [0, 0, 2, 2, 1]]).unwrap()
+    );
+    assert_eq!(steps, 6);
+}
+
+#[test]
+fn test_apply_filter_collection_markov_max_steps() {
+    let input = Map::<u32>::new((4, 4).into());
+    let filter = Filter::new(
+        Map::from_data([[0]]).unwrap(),
+        Map::from_data([[1]]).unwrap(),
+        9,
+    )
+    .unwrap();
+    let filter_collection = FilterCollection::new_with_properties(
+        &[filter],
+        FilterProperties {
+            mode: ApplyMode::Markov,
+            max_steps: Some(5),
+            ..Default::default()
+        },
+    );
+    let mut output = input.clone();
+    let mut seeded_output = input.clone();
+
+    filter_collection
+        .apply_with_rng(&input, &mut output, &mut StdRng::seed_from_u64(3))
+        .unwrap();
+    filter_collection
+        .apply_with_rng(&input, &mut seeded_output, &mut StdRng::seed_from_u64(3))
+        .unwrap();
+
+    assert_eq!(output.data().iter().filter(|field| **field == 1).count(), 5);
+    assert_eq!(output, seeded_output);
+}
+
+#[test]
+fn test_apply_filter_collection_markov_default_max_steps() {
+    let input = Map::<u32>::new((3, 2).into());
+    // The substitute always matches again
+    let filter = Filter::new(
+        Map::from_data([[0]]).unwrap(),
+        Map::from_data([[0]]).unwrap(),
+        9,
+    )
+    .unwrap();
+    let filter_collection = FilterCollection::new_with_properties(
+        &[filter],
+        FilterProperties {
+            mode: ApplyMode::Markov,
+            ..Default::default()
+        },
+    );
+    let mut output = input.clone();
+    let mut steps = 0;
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let options = &mut ApplyOptions {
+        callback: Some(&mut |_, _| steps += 1),
+        ..ApplyOptions::new(&mut rng)
+    };
+    let result = filter_collection.apply_with(&input, &mut output, options);
+
+    // The steps stop at the number of fields
+    assert!(result.is_ok());
+    assert_eq!(steps, 6);
+}
+
+#[test]
+fn test_apply_filter_collection_markov_matches() {
+    let mut rng = StdRng::seed_from_u64(5);
+    let mut input = Map::<u32>::new((12, 9).into());
+    for field in input.mut_data() {
+        *field = rng.random_range(0..3);
+    }
+    // Substitutes create and remove matches of both filters
+    let filters = [
+        Filter::new(
+            Map::from_data([[0, 1], [9, 2]]).unwrap(),
+            Map::from_data([[1, 0], [9, 0]]).unwrap(),
+            9,
+        )
+        .unwrap(),
+        Filter::new(
+            Map::from_data([[1, 1, 0]]).unwrap(),
+            Map::from_data([[2, 0, 1]]).unwrap(),
+            9,
+        )
+        .unwrap(),
+    ];
+    let filter_collection = FilterCollection::new_with_properties(
+        &filters,
+        FilterProperties {
+            mode: ApplyMode::Markov,
+            ..Default::default()
+        },
+    );
+    // Searches the whole map for the matches of all the filters at each step
+    let mut expected = input.clone();
+    let mut expected_matches = [0, 0];
+    let mut rng = StdRng::seed_from_u64(0);
+    for _ in 0..input.size().x * input.size().y {
+        let mut matches = vec![];
+        for (idx, filter) in filters.iter().enumerate() {
+            let filter_matches = filter.find_matches(&expected);
+            expected_matches[idx] += filter_matches.len();
+            matches.extend(filter_matches.into_iter().map(|point| (idx, point)));
+        }
+        if matches.is_empty() {
+            break;
+        }
+        let (idx, point) = matches[rng.random_range(0..matches.len())];
+        filters[idx].apply_substitute(&mut expected, point);
+    }
+    let mut output = input.clone();
+
+    let result =
+        filter_collection.apply_with_rng(&input, &mut output, &mut StdRng::seed_from_u64(0));
+
+    assert!(result.is_ok());
+    let stats = result.unwrap();
+    assert_eq!(output, expected);
+    assert_ne!(output, input);
+    assert_eq!(stats.filters[0].matches, expected_matches[0]);
+    assert_eq!(stats.filters[1].matches, expected_matches[1]);
+}
+
+#[test]
+fn test_apply_filter_collection_markov_in_mask() {
+    let input = Map::<u32>::new((3, 2).into());
+    let mask = Map::from_data([[true, true, false], [false, true, false]]).unwrap();
+    let filter = Filter::new(
+        Map::from_data([[0]]).unwrap(),
+        Map::from_data([[1]]).unwrap(),
+        9,
+    )
+    .unwrap();
+    let filter_collection = FilterCollection::new_with_properties(
+        &[filter],
+        FilterProperties {
+            mode: ApplyMode::Markov,
+            ..Default::default()
+        },
+    );
+    let mut output = input.clone();
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let options = &mut ApplyOptions {
+        mask: Some(&mask),
+        ..ApplyOptions::new(&mut rng)
+    };
+    let result = filter_collection.apply_with(&input, &mut output, options);
+
+    assert!(result.is_ok());
+    assert_eq!(output, mask.map(|set| *set as u32));
+}
+
+#[test]
+fn test_apply_filter_changes() {
+    let input = Map::<u32>::from_data([[1, 1, 0], [1, 0, 0]]).unwrap();
+    let filter = |substitute| {
+        Filter::new(
+            Map::from_data([[1, 9]]).unwrap(),
+            Map::from_data([substitute]).unwrap(),
+            9,
+        )
+        .unwrap()
+    };
+    let mut output = input.clone();
+
+    // Matches at (0, 0), (1, 0) and (0, 1)
+    assert_eq!(
+        filter([2, 9]).apply(&input, &mut output).unwrap().changes,
+        3
+    );
+    // Fields that already have the substitute value aren't changes
+    assert_eq!(
+        filter([2, 2]).apply(&input, &mut output).unwrap().changes,
+        2
+    );
+    assert_eq!(
+        FilterCollection::new(&[filter([2, 9]), filter([2, 2])])
+            .apply(&input, &mut output)
+            .unwrap()
+            .changes(),
+        0
+    );
+}
+
+#[test]
+fn test_apply_filter_stats() {
+    let input = Map::<u32>::from_data([[1, 1, 0], [1, 0, 0], [0, 0, 1]]).unwrap();
+    let filter = |probability| {
+        Filter::new_with_properties(
+            Map::from_data([[1, 9]]).unwrap(),
+            Map::from_data([[2, 9]]).unwrap(),
+            9,
+            FilterProperties {
+                probability,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    };
+    let mut output = input.clone();
+
+    let stats = filter(1.0).apply(&input, &mut output).unwrap();
+
+    assert_eq!(stats.positions_tested, 6);
+    assert_eq!(stats.matches, 3);
+    assert_eq!(stats.skipped, 0);
+    assert_eq!(stats.changes, 3);
+
+    let stats = filter(0.0).apply(&input, &mut input.clone()).unwrap();
+
+    assert_eq!(stats.matches, 3);
+    assert_eq!(stats.skipped, 3);
+    assert_eq!(stats.changes, 0);
+}
+
+#[test]
+fn test_apply_filter_collection_stats() {
+    let input = Map::<u32>::from_data([[1, 0, 0], [0, 0, 0], [0, 0, 3]]).unwrap();
+    let rare = Filter::new(
+        Map::from_data([[3]]).unwrap(),
+        Map::from_data([[4]]).unwrap(),
+        9,
+    )
+    .unwrap();
+    let dead = Filter::new(
+        Map::from_data([[5]]).unwrap(),
+        Map::from_data([[6]]).unwrap(),
+        9,
+    )
+    .unwrap();
+    let filter_collection = FilterCollection::new_with_properties(
+        &[rare, dead],
+        FilterProperties {
+            iterations: 2,
+            ..Default::default()
+        },
+    );
+
+    let stats = filter_collection.apply(&input, &mut input.clone()).unwrap();
+    let index = TileIndex::new(&input);
+    let mut rng = rand::rng();
+    let options = &mut ApplyOptions {
+        index: Some(&index),
+        ..ApplyOptions::new(&mut rng)
+    };
+    let indexed_stats = filter_collection
+        .apply_with(&input, &mut input.clone(), options)
+        .unwrap();
+
+    assert_eq!(stats.filters.len(), 2);
+    assert_eq!(stats.filters[0].positions_tested, 18);
+    assert_eq!(stats.filters[0].matches, 2);
+    // The second iteration doesn't change the already substituted field
+    assert_eq!(stats.filters[0].changes, 1);
+    assert_eq!(stats.filters[1].matches, 0);
+    assert_eq!(stats.changes(), 1);
+    assert_eq!(stats.total().matches, 2);
+    // Only the positions of the pattern tiles are tested
+    assert_eq!(indexed_stats.filters[0].positions_tested, 2);
+    assert_eq!(indexed_stats.filters[1].positions_tested, 0);
+    assert_eq!(indexed_stats.filters[0].matches, 2);
+}
+
+#[test]
+fn test_apply_filter_float() {
+    let source = Map::<f32>::from_data([[0.5, 1.0], [1.0, 0.5]]).unwrap();
+    let mut destination = source.clone();
+    let filter = Filter::new(
+        Map::from_data([[0.5]]).unwrap(),
+        Map::from_data([[0.25]]).unwrap(),
+        -1.0,
+    )
+    .unwrap();
+
+    let result = filter.apply(&source, &mut destination);
+
+    assert!(result.is_ok());
+    assert_eq!(
+        destination,
+        Map::from_data([[0.25, 1.0], [1.0, 0.25]]).unwrap()
+    );
+}
+
+#[test]
+fn test_apply_filter_custom_field() {
+    #[derive(Clone, PartialEq, Debug)]
+    enum Terrain {
+        Any,
+        Grass,
+        Water,
+    }
+    let source = Map::from_data([[Terrain::Grass, Terrain::Water]]).unwrap();
+    let mut destination = source.clone();
+    let filter = Filter::new(
+        Map::from_data([[Terrain::Grass, Terrain::Water]]).unwrap(),
+        Map::from_data([[Terrain::Any, Terrain::Grass]]).unwrap(),
+        Terrain::Any,
+    )
+    .unwrap();
+
+    let result = FilterCollection::new(&[filter]).apply(&source, &mut destination);
+
+    assert!(result.is_ok());
+    assert_eq!(
+        destination,
+        Map::from_data([[Terrain::Grass, Terrain::Grass]]).unwrap()
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_filter() {
+    let mut filter = Filter::new_with_properties(
+        Map::from_data([[1, 2]]).unwrap(),
+        Map::from_data([[0, 3]]).unwrap(),
+        0,
+        FilterProperties {
+            probability: 0.5,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let json = serde_json::to_string(&filter).unwrap();
+
+    assert_eq!(serde_json::from_str::<Filter<i32>>(&json).unwrap(), filter);
+
+    // Alternatives, objects and distance constraints would be lost
+    let mut with_alternatives = filter.clone();
+    with_alternatives
+        .set_alternatives((0, 0).into(), vec![1, 4])
+        .unwrap();
+    assert!(serde_json::to_string(&with_alternatives).is_err());
+    let mut with_objects = filter.clone();
+    with_objects.set_objects(vec![MapObject::default()]);
+    assert!(serde_json::to_string(&with_objects).is_err());
+    filter.set_distance_constraints(vec![crate::distance::DistanceConstraint {
+        tiles: vec![1],
+        min: Some(1.0),
+        max: None,
+    }]);
+    assert!(serde_json::to_string(&filter).is_err());
+}
+
+#[test]
+fn test_filter_properties_from_tiled_properties() {
+    let properties: tiled::Properties = [
+        (
+            String::from("probability"),
+            tiled::PropertyValue::FloatValue(0.5),
+        ),
+        (
+            String::from("iterations"),
+            tiled::PropertyValue::IntValue(-1),
+        ),
+        (
+            String::from("mode"),
+            tiled::PropertyValue::StringValue(String::from("markov")),
+        ),
+        (String::from("sentinel"), tiled::PropertyValue::IntValue(1)),
+    ]
+    .into();
+
+    let filter_properties = FilterProperties::from(&properties);
+
+    // Invalid properties keep their defaults
+    assert_eq!(
+        filter_properties,
+        FilterProperties {
+            probability: 0.5,
+            mode: ApplyMode::Markov,
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        FilterProperties::default().parse_property("iterations", &properties["iterations"]),
+        Err(String::from("'iterations' -1 is negative"))
+    );
+    assert_eq!(
+        FilterProperties::default().parse_property("sentinel", &properties["sentinel"]),
+        Err(String::from("'sentinel' must be a bool"))
+    );
+    assert_eq!(
+        FilterProperties::default().parse_property("example", &properties["sentinel"]),
+        Ok(false)
+    );
+}
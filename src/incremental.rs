@@ -0,0 +1,674 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use rand::Rng;
+
+use crate::filter::{ApplyMode, Filter, FilterCollection, PatternMatching, SubstituteFlips};
+use crate::map::Map;
+use crate::map_storage::MapStorage;
+use crate::rect2::Rect2u;
+use crate::tisu_error::TisuError;
+use crate::vector2::Vector2u;
+
+/// Position of a filter, as a key ordered the way filters visit positions
+/// (column by column).
+type PositionKey = (u32, u32);
+
+/// Pattern matches of a filter, along with whether the substitute was applied
+/// (depending on the filter probability).
+type RecordedMatches = BTreeMap<PositionKey, bool>;
+
+/// The changes of the recorded matches of a filter: matches are replaced, or
+/// removed when the position doesn't match anymore.
+type MatchUpdates = Vec<(PositionKey, Option<bool>)>;
+
+/// Returns the filters applied by the given filter collections, in the order in
+/// which they're applied (including iterations and excluding ignored filters).
+fn applied_filters<T>(filter_collections: &[FilterCollection<T>]) -> Vec<&Filter<T>> {
+    filter_collections
+        .iter()
+        .flat_map(|filter_collection| {
+            (0..filter_collection.properties.iterations).flat_map(|_| &filter_collection.filters)
+        })
+        .filter(|filter| !filter.properties().ignore)
+        .collect()
+}
+
+/// Checks that the given filter collections can be applied incrementally (see
+/// `IncrementalApplication::new`).
+fn check_supported<T>(filter_collections: &[FilterCollection<T>]) -> Result<(), TisuError> {
+    if filter_collections.iter().any(|filter_collection| {
+        let properties = &filter_collection.properties;
+        properties.mode == ApplyMode::Markov
+            || properties.when.is_some()
+            || properties.until.is_some()
+            || properties.sentinel
+            || properties.region.is_some()
+            || filter_collection.region_op.is_some()
+            || filter_collection.connectivity.is_some()
+            || filter_collection
+                .filters
+                .iter()
+                .any(|filter| !filter.distance_constraints().is_empty())
+    }) {
+        return Err(TisuError::InvalidArgument);
+    }
+    Ok(())
+}
+
+/// Returns the last position a filter can be applied at on a map of the given
+/// size.
+fn last_position<T>(filter: &Filter<T>, size: Vector2u) -> Result<Vector2u, TisuError> {
+    let pattern_size = filter.pattern().size();
+    if size.x < pattern_size.x || size.y < pattern_size.y {
+        Err(TisuError::InvalidMapSize)
+    } else {
+        Ok(size - pattern_size)
+    }
+}
+
+/// The result of applying filter collections to a map (where each filter
+/// collection uses the input map as the source), along with the pattern
+/// matches of every filter. After small changes to the input map, the output
+/// can be updated by only testing the filter positions whose pattern overlaps
+/// changed fields (see `update`), instead of applying all the filters to the
+/// whole map again.
+///
+/// Updates are equal to applying the filters to the changed input map for
+/// filters with a probability of 1. Otherwise, positions that matched before
+/// keep their previous outcome and new matches use the given random number
+/// generator.
+///
+/// The application keeps its maps and matches instead of taking the previous
+/// input and output with each update (like `update_output`), as the output of
+/// a filter depends on the results of the earlier filters, which aren't part
+/// of the final output, and on the outcomes of the probability rolls. Updates only rebuild the output
+/// around the changed fields, by replaying the matches that reach them, unless
+/// `destination` pattern matching spreads the changes further, in which case
+/// all the matches are replayed.
+#[derive(Clone, PartialEq, Debug)]
+pub struct IncrementalApplication<T> {
+    input: Map<T>,
+    output: Map<T>,
+    /// Pattern matches of each applied filter
+    matches: Vec<RecordedMatches>,
+}
+
+impl<T> IncrementalApplication<T> {
+    /// Applies the given filter collections to the given input map, using the
+    /// given random number generator. The output is the same as the one of
+    /// applying the filter collections one after another with
+    /// `FilterCollection::apply_with_rng`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the filters can't be applied to the input map, or
+    /// if a filter collection uses markov mode (whose steps depend on all the
+    /// previous ones, so they can't be updated locally), control flow
+    /// properties (see `sequence::apply_sequence`), a region operation, a
+    /// connectivity step (whose regions can span the whole map), distance
+    /// constraints (whose targets can be far from the changed fields) or a
+    /// region (which would need the masks of the input map).
+    pub fn new<R>(
+        filter_collections: &[FilterCollection<T>],
+        input: Map<T>,
+        rng: &mut R,
+    ) -> Result<Self, TisuError>
+    where
+        T: Clone + PartialEq,
+        R: Rng + ?Sized,
+    {
+        check_supported(filter_collections)?;
+        let mut output = input.clone();
+        let mut matches = vec![];
+        for filter in applied_filters(filter_collections) {
+            let last = last_position(filter, input.size())?;
+            let mut filter_matches = RecordedMatches::new();
+            for x in 0..=last.x {
+                for y in 0..=last.y {
+                    let position = Vector2u::new(x, y);
+                    let matched = match filter.properties().pattern_matching {
+                        PatternMatching::Source => filter.pattern_matches(&input, position),
+                        PatternMatching::Destination => filter.pattern_matches(&output, position),
+                    };
+                    if matched {
+                        let applied = filter.roll_probability(rng);
+                        if applied {
+                            filter.apply_substitute(&mut output, position);
+                        }
+                        filter_matches.insert((x, y), applied);
+                    }
+                }
+            }
+            matches.push(filter_matches);
+        }
+
+        Ok(Self {
+            input,
+            output,
+            matches,
+        })
+    }
+
+    /// Returns the input map.
+    pub fn input(&self) -> &Map<T> {
+        &self.input
+    }
+
+    /// Returns the output map.
+    pub fn output(&self) -> &Map<T> {
+        &self.output
+    }
+
+    /// Sets the given fields of the input map and updates the output map. The
+    /// given filter collections have to be the ones the application was
+    /// created with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a position is out of map bounds or if the filter
+    /// collections differ from the ones the application was created with.
+    pub fn update<R>(
+        &mut self,
+        filter_collections: &[FilterCollection<T>],
+        changes: &[(Vector2u, T)],
+        rng: &mut R,
+    ) -> Result<&Map<T>, TisuError>
+    where
+        T: Clone + PartialEq,
+        R: Rng + ?Sized,
+    {
+        for (position, _) in changes {
+            self.input.get(*position)?;
+        }
+
+        let mut dirty = HashSet::new();
+        for (position, value) in changes {
+            if self.input.get(*position)? != value {
+                self.input.set(*position, value.clone())?;
+                dirty.insert((position.x, position.y));
+            }
+        }
+        self.reapply(filter_collections, dirty, rng)
+    }
+
+    /// Replaces the input map with the given map, which may only differ from
+    /// the previous input within the given rectangle, and updates the output
+    /// map. The given filter collections have to be the ones the application
+    /// was created with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the map size differs from the previous input, if
+    /// the rectangle exceeds map bounds or if the filter collections differ
+    /// from the ones the application was created with.
+    pub fn update_rect<R>(
+        &mut self,
+        filter_collections: &[FilterCollection<T>],
+        input: Map<T>,
+        dirty_rect: Rect2u,
+        rng: &mut R,
+    ) -> Result<&Map<T>, TisuError>
+    where
+        T: Clone + PartialEq,
+        R: Rng + ?Sized,
+    {
+        if input.size() != self.input.size() {
+            return Err(TisuError::InvalidMapSize);
+        }
+        if !Rect2u::from(&input).contains_rect(&dirty_rect) {
+            return Err(TisuError::OutOfBounds);
+        }
+
+        let mut dirty = HashSet::new();
+        for x in dirty_rect.position().x..dirty_rect.position().x + dirty_rect.size().x {
+            for y in dirty_rect.position().y..dirty_rect.position().y + dirty_rect.size().y {
+                dirty.insert((x, y));
+            }
+        }
+        self.input = input;
+        self.reapply(filter_collections, dirty, rng)
+    }
+
+    /// Applies the filters to the input map again, replaying the recorded
+    /// matches and only testing the positions whose pattern overlaps fields
+    /// that may differ from the previous application.
+    fn reapply<R>(
+        &mut self,
+        filter_collections: &[FilterCollection<T>],
+        input_dirty: HashSet<PositionKey>,
+        rng: &mut R,
+    ) -> Result<&Map<T>, TisuError>
+    where
+        T: Clone + PartialEq,
+        R: Rng + ?Sized,
+    {
+        let filters = applied_filters(filter_collections);
+        if filters.len() != self.matches.len() {
+            return Err(TisuError::InvalidArgument);
+        }
+        let input = &self.input;
+        let matches = &self.matches;
+        let reach = reach(&filters, &input_dirty, input.size(), |idx, position| {
+            matches[idx].contains_key(&(position.x, position.y))
+                != filters[idx].pattern_matches(input, position)
+        });
+        let Some(reach) = reach else {
+            return Ok(&self.output);
+        };
+
+        // Only the fields within reach of the changed fields are rebuilt, the
+        // other ones keep the previous output
+        for x in reach.position().x..reach.position().x + reach.size().x {
+            for y in reach.position().y..reach.position().y + reach.size().y {
+                let point = Vector2u::new(x, y);
+                self.output.set(point, self.input.get(point)?.clone())?;
+            }
+        }
+        let mut output = ClippedMap {
+            map: &mut self.output,
+            region: reach,
+        };
+        if let Some(updates) = Self::reapply_stages(
+            &filters,
+            &self.matches,
+            &self.input,
+            &input_dirty,
+            &mut output,
+            Some(reach),
+            rng,
+        )? {
+            self.update_matches(updates);
+            return Ok(&self.output);
+        }
+
+        let mut output = self.input.clone();
+        let updates = Self::reapply_stages(
+            &filters,
+            &self.matches,
+            &self.input,
+            &input_dirty,
+            &mut output,
+            None,
+            rng,
+        )?
+        .ok_or(TisuError::Unexpected)?;
+        self.output = output;
+        self.update_matches(updates);
+        Ok(&self.output)
+    }
+
+    /// Applies the given filters to the output map, one stage after another.
+    /// Returns the updates of the recorded matches, or `None` if the fields
+    /// that may differ from the previous application leave the given region.
+    fn reapply_stages<M, R>(
+        filters: &[&Filter<T>],
+        matches: &[RecordedMatches],
+        input: &Map<T>,
+        input_dirty: &HashSet<PositionKey>,
+        output: &mut M,
+        region: Option<Rect2u>,
+        rng: &mut R,
+    ) -> Result<Option<Vec<MatchUpdates>>, TisuError>
+    where
+        T: Clone + PartialEq,
+        M: MapStorage<T>,
+        R: Rng + ?Sized,
+    {
+        // Fields of the output that may differ from the previous application
+        // at the current stage
+        let mut output_dirty = input_dirty.clone();
+        let mut updates = Vec::with_capacity(filters.len());
+        for (filter, previous) in filters.iter().zip(matches) {
+            let stage = Stage {
+                filter,
+                last: last_position(filter, input.size())?,
+                previous,
+                region,
+            };
+            match stage.reapply(input, input_dirty, output, &mut output_dirty, rng) {
+                Some(stage_updates) => updates.push(stage_updates),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(updates))
+    }
+
+    fn update_matches(&mut self, updates: Vec<MatchUpdates>) {
+        for (matches, stage_updates) in self.matches.iter_mut().zip(updates) {
+            for (key, applied) in stage_updates {
+                match applied {
+                    Some(applied) => matches.insert(key, applied),
+                    None => matches.remove(&key),
+                };
+            }
+        }
+    }
+}
+
+/// Updates the output of applying the given filter collections to the given
+/// previous input (where each filter collection uses the input map as the
+/// source) after the given fields of the input map changed, without keeping
+/// any state between updates. Only the fields within reach of the changed
+/// fields are rebuilt, by applying the filters at the positions whose pattern
+/// overlaps them, and the other fields keep the previous output. Returns the
+/// updated output.
+///
+/// The output is equal to applying the filters to the changed input map for
+/// filters with a probability of 1. Otherwise, the probability of the matches
+/// overlapping the rebuilt fields is rolled again with the given random number
+/// generator, as the previous outcomes aren't known (`IncrementalApplication`
+/// records them). As the results of the earlier filters can't be recovered
+/// from the previous output, the filters are applied to the whole changed map
+/// if any of them uses `destination` pattern matching.
+///
+/// # Errors
+///
+/// Returns an error if the maps aren't of the same size, if a position is out
+/// of map bounds, if the filters can't be applied to the map or if a filter
+/// collection can't be applied incrementally (see `IncrementalApplication::new`).
+pub fn update_output<T, R>(
+    filter_collections: &[FilterCollection<T>],
+    previous_input: &Map<T>,
+    previous_output: &Map<T>,
+    changes: &[(Vector2u, T)],
+    rng: &mut R,
+) -> Result<Map<T>, TisuError>
+where
+    T: Clone + PartialEq,
+    R: Rng + ?Sized,
+{
+    check_supported(filter_collections)?;
+    if previous_output.size() != previous_input.size() {
+        return Err(TisuError::InvalidMapSize);
+    }
+    let filters = applied_filters(filter_collections);
+    for filter in &filters {
+        last_position(filter, previous_input.size())?;
+    }
+    let mut input = previous_input.clone();
+    let mut dirty = HashSet::new();
+    for (position, value) in changes {
+        if input.get(*position)? != value {
+            input.set(*position, value.clone())?;
+            dirty.insert((position.x, position.y));
+        }
+    }
+
+    if filters
+        .iter()
+        .any(|filter| filter.properties().pattern_matching == PatternMatching::Destination)
+    {
+        return Ok(IncrementalApplication::new(filter_collections, input, rng)?.output);
+    }
+
+    let mut output = previous_output.clone();
+    let reach = reach(&filters, &dirty, input.size(), |idx, position| {
+        filters[idx].pattern_matches(previous_input, position)
+            != filters[idx].pattern_matches(&input, position)
+    });
+    let Some(reach) = reach else {
+        return Ok(output);
+    };
+    let start = reach.position();
+    let end = reach.position() + reach.size();
+    for x in start.x..end.x {
+        for y in start.y..end.y {
+            let point = Vector2u::new(x, y);
+            output.set(point, input.get(point)?.clone())?;
+        }
+    }
+    let mut output_in_reach = ClippedMap {
+        map: &mut output,
+        region: reach,
+    };
+    for filter in filters {
+        let size = filter.pattern().size();
+        let last = last_position(filter, input.size())?;
+        for x in (start.x + 1).saturating_sub(size.x)..end.x.min(last.x + 1) {
+            for y in (start.y + 1).saturating_sub(size.y)..end.y.min(last.y + 1) {
+                let position = Vector2u::new(x, y);
+                if filter.pattern_matches(&input, position) && filter.roll_probability(rng) {
+                    filter.apply_substitute(&mut output_in_reach, position);
+                }
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// Returns the rectangle covering the fields that may differ from the previous
+/// application after the given fields of the input map changed, or `None` if
+/// no field changed. Starting from the bounding rectangle of the changed
+/// fields, the rectangle is propagated filter by filter: a filter with
+/// `source` pattern matching only extends it by the substitutes at the
+/// positions whose match changed (as told by the given function, with the
+/// index of the filter), while one with `destination` pattern matching extends
+/// it by its pattern size in every direction, as its matches depend on the
+/// fields changed by the earlier filters. Changes spreading further within a
+/// filter aren't covered.
+fn reach<T>(
+    filters: &[&Filter<T>],
+    dirty: &HashSet<PositionKey>,
+    size: Vector2u,
+    match_changed: impl Fn(usize, Vector2u) -> bool,
+) -> Option<Rect2u> {
+    let min_x = dirty.iter().map(|field| field.0).min()?;
+    let min_y = dirty.iter().map(|field| field.1).min()?;
+    let max_x = dirty.iter().map(|field| field.0).max()?;
+    let max_y = dirty.iter().map(|field| field.1).max()?;
+    let last_field = size - Vector2u::new(1, 1);
+    // Inclusive bounds of the changed input fields and of the fields that may
+    // differ after the current filter
+    let input_bounds = (Vector2u::new(min_x, min_y), Vector2u::new(max_x, max_y));
+    let mut bounds = input_bounds;
+    for (idx, filter) in filters.iter().enumerate() {
+        let margin = filter.pattern().size() - Vector2u::new(1, 1);
+        match filter.properties().pattern_matching {
+            PatternMatching::Source => {
+                let last = size - filter.pattern().size();
+                for x in input_bounds.0.x.saturating_sub(margin.x)..=input_bounds.1.x.min(last.x) {
+                    for y in
+                        input_bounds.0.y.saturating_sub(margin.y)..=input_bounds.1.y.min(last.y)
+                    {
+                        let position = Vector2u::new(x, y);
+                        if match_changed(idx, position) {
+                            bounds.0.x = bounds.0.x.min(x);
+                            bounds.0.y = bounds.0.y.min(y);
+                            bounds.1.x = bounds.1.x.max(x + margin.x);
+                            bounds.1.y = bounds.1.y.max(y + margin.y);
+                        }
+                    }
+                }
+            }
+            PatternMatching::Destination => {
+                bounds.0.x = bounds.0.x.saturating_sub(margin.x);
+                bounds.0.y = bounds.0.y.saturating_sub(margin.y);
+                bounds.1.x = bounds.1.x.saturating_add(margin.x).min(last_field.x);
+                bounds.1.y = bounds.1.y.saturating_add(margin.y).min(last_field.y);
+            }
+        }
+    }
+    Rect2u::new(bounds.0, bounds.1 - bounds.0 + Vector2u::new(1, 1)).ok()
+}
+
+/// An output map whose writes outside of a region are dropped, as the fields
+/// outside of it keep the previous output.
+struct ClippedMap<'a, T> {
+    map: &'a mut Map<T>,
+    region: Rect2u,
+}
+
+impl<T> MapStorage<T> for ClippedMap<'_, T> {
+    fn size(&self) -> Vector2u {
+        self.map.size()
+    }
+
+    fn get(&self, point: Vector2u) -> Result<&T, TisuError> {
+        self.map.get(point)
+    }
+
+    fn set(&mut self, point: Vector2u, value: T) -> Result<(), TisuError> {
+        if self.region.contains_point(point) {
+            self.map.set(point, value)
+        } else {
+            self.map.get(point).map(|_| ())
+        }
+    }
+}
+
+/// The application of a single filter during an update.
+struct Stage<'a, T> {
+    filter: &'a Filter<T>,
+    last: Vector2u,
+    /// Whether the substitute was applied at each previously matched position
+    previous: &'a RecordedMatches,
+    /// The fields that are rebuilt, or `None` for the whole map
+    region: Option<Rect2u>,
+}
+
+impl<T> Stage<'_, T> {
+    /// Adds the positions (after the given one) whose pattern covers the given
+    /// field to the pending positions.
+    fn add_covering_positions(
+        &self,
+        field: PositionKey,
+        after: Option<PositionKey>,
+        pending: &mut BTreeSet<PositionKey>,
+    ) {
+        let size = self.filter.pattern().size();
+        let min_x = field.0.saturating_sub(size.x - 1);
+        let min_y = field.1.saturating_sub(size.y - 1);
+        for x in min_x..=field.0.min(self.last.x) {
+            for y in min_y..=field.1.min(self.last.y) {
+                if after.is_none_or(|after| (x, y) > after) {
+                    pending.insert((x, y));
+                }
+            }
+        }
+    }
+
+    /// Returns the previously matched positions whose pattern overlaps the
+    /// rebuilt fields.
+    fn previous_positions(&self) -> BTreeSet<PositionKey> {
+        let Some(region) = self.region else {
+            return self.previous.keys().copied().collect();
+        };
+        let size = self.filter.pattern().size();
+        let start = region.position();
+        let end = region.position() + region.size();
+        let min_y = start.y.saturating_sub(size.y - 1);
+        self.previous
+            .range((start.x.saturating_sub(size.x - 1), 0)..(end.x, 0))
+            .map(|(key, _)| *key)
+            .filter(|key| key.1 >= min_y && key.1 < end.y)
+            .collect()
+    }
+
+    /// Checks if the pattern at the given position only covers rebuilt fields.
+    fn pattern_in_region(&self, position: PositionKey) -> bool {
+        self.region.is_none_or(|region| {
+            Rect2u::new(position.into(), self.filter.pattern().size())
+                .is_ok_and(|pattern| region.contains_rect(&pattern))
+        })
+    }
+
+    /// Checks if the given field is rebuilt.
+    fn field_in_region(&self, field: PositionKey) -> bool {
+        self.region
+            .is_none_or(|region| region.contains_point(field.into()))
+    }
+
+    fn pattern_overlaps(&self, position: PositionKey, dirty: &HashSet<PositionKey>) -> bool {
+        let size = self.filter.pattern().size();
+        (0..size.x).any(|x| (0..size.y).any(|y| dirty.contains(&(position.0 + x, position.1 + y))))
+    }
+
+    /// Applies the filter to the output map, which holds the result of the
+    /// previous filters, and updates the fields that may differ from the
+    /// previous application. Returns the updates of the pattern matches, or
+    /// `None` if a field that may differ is outside of the rebuilt fields.
+    fn reapply<M, R>(
+        &self,
+        input: &Map<T>,
+        input_dirty: &HashSet<PositionKey>,
+        output: &mut M,
+        output_dirty: &mut HashSet<PositionKey>,
+        rng: &mut R,
+    ) -> Option<MatchUpdates>
+    where
+        T: Clone + PartialEq,
+        M: MapStorage<T>,
+        R: Rng + ?Sized,
+    {
+        let pattern_matching = &self.filter.properties().pattern_matching;
+        let mut pending = self.previous_positions();
+        let read_dirty = match pattern_matching {
+            PatternMatching::Source => input_dirty,
+            PatternMatching::Destination => &*output_dirty,
+        };
+        for field in read_dirty {
+            self.add_covering_positions(*field, None, &mut pending);
+        }
+
+        let mut updates = vec![];
+        while let Some(key) = pending.pop_first() {
+            let position = Vector2u::new(key.0, key.1);
+            let previous = self.previous.get(&key).copied();
+
+            // Positions whose pattern only covers unchanged fields match the
+            // same way as before
+            let matched = match pattern_matching {
+                PatternMatching::Source if self.pattern_overlaps(key, input_dirty) => {
+                    self.filter.pattern_matches(input, position)
+                }
+                PatternMatching::Destination if self.pattern_overlaps(key, output_dirty) => {
+                    if !self.pattern_in_region(key) {
+                        return None;
+                    }
+                    self.filter.pattern_matches(output, position)
+                }
+                _ => previous.is_some(),
+            };
+            if !matched && previous.is_none() {
+                continue;
+            }
+            let applied = matched && previous.unwrap_or_else(|| self.filter.roll_probability(rng));
+            updates.push((key, matched.then_some(applied)));
+            if applied {
+                self.filter.apply_substitute(output, position);
+            }
+
+            // Fields written by both applications are equal, fields written by
+            // only one of them may differ
+            let previously_applied = previous == Some(true);
+            if !applied && !previously_applied {
+                continue;
+            }
+            let size = self.filter.pattern().size();
+            for x in 0..size.x {
+                for y in 0..size.y {
+                    let point = Vector2u::new(x, y);
+                    if self.filter.substitute().get(point) == Ok(self.filter.wildcard()) {
+                        continue;
+                    }
+                    let field = (key.0 + x, key.1 + y);
+                    if applied == previously_applied {
+                        // Copying orientations keeps the replaced fields'
+                        // differences
+                        if self.filter.properties().substitute_flips == SubstituteFlips::Keep {
+                            output_dirty.remove(&field);
+                        }
+                    } else if output_dirty.insert(field) {
+                        if !self.field_in_region(field) {
+                            return None;
+                        }
+                        if *pattern_matching == PatternMatching::Destination {
+                            self.add_covering_positions(field, Some(key), &mut pending);
+                        }
+                    }
+                }
+            }
+        }
+        Some(updates)
+    }
+}
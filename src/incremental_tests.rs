@@ -0,0 +1,396 @@
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    connectivity::Connectivity,
+    distance::DistanceConstraint,
+    filter::{
+        ApplyMode, Filter, FilterCollection, FilterProperties, MatchFlips, PatternMatching,
+        SubstituteFlips,
+    },
+    incremental::{self, IncrementalApplication},
+    map::Map,
+    regions::{RegionOp, RegionOpKind},
+    tiled_tile::TiledTile,
+    tisu_error::TisuError,
+};
+
+fn create_filter<const N: usize, const M: usize>(
+    pattern: [[u32; N]; M],
+    substitute: [[u32; N]; M],
+    pattern_matching: PatternMatching,
+) -> Filter<u32> {
+    Filter::new_with_properties(
+        Map::from_data(pattern).unwrap(),
+        Map::from_data(substitute).unwrap(),
+        0,
+        FilterProperties {
+            pattern_matching,
+            ..Default::default()
+        },
+    )
+    .unwrap()
+}
+
+/// Filter collections where the first one grows 1s to the right along rows
+/// (a destination cascade) and the second one marks 2x2 squares of 1s in the
+/// source.
+fn create_filter_collections() -> Vec<FilterCollection<u32>> {
+    let grow = create_filter([[1, 9]], [[0, 1]], PatternMatching::Destination);
+    let squares = create_filter([[1, 1], [1, 1]], [[5, 0], [0, 0]], PatternMatching::Source);
+    vec![
+        FilterCollection::new(&[grow]),
+        FilterCollection::new(&[squares]),
+    ]
+}
+
+fn create_input() -> Map<u32> {
+    Map::from_data([
+        [9, 9, 9, 9, 9, 9],
+        [1, 9, 9, 3, 9, 9],
+        [1, 9, 9, 9, 9, 9],
+        [9, 9, 9, 9, 9, 9],
+        [9, 9, 9, 9, 9, 9],
+    ])
+    .unwrap()
+}
+
+fn apply(filter_collections: &[FilterCollection<u32>], input: &Map<u32>) -> Map<u32> {
+    let mut output = input.clone();
+    for filter_collection in filter_collections {
+        filter_collection
+            .apply_with_rng(input, &mut output, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+    }
+    output
+}
+
+#[test]
+fn test_new() {
+    let filter_collections = create_filter_collections();
+    let input = create_input();
+
+    let result = IncrementalApplication::new(&filter_collections, input.clone(), &mut rand::rng());
+
+    assert!(result.is_ok());
+    let application = result.unwrap();
+    assert_eq!(application.input(), &input);
+    assert_eq!(application.output(), &apply(&filter_collections, &input));
+}
+
+#[test]
+fn test_new_markov_failure() {
+    let mut filter_collections = create_filter_collections();
+    filter_collections[1].properties.mode = ApplyMode::Markov;
+
+    let result = IncrementalApplication::new(&filter_collections, create_input(), &mut rand::rng());
+
+    assert_eq!(result.err().unwrap(), TisuError::InvalidArgument);
+}
+
+#[test]
+fn test_new_region_failure() {
+    let mut filter_collections = create_filter_collections();
+    filter_collections[0].properties.region = Some(String::from("Spawn"));
+
+    let result = IncrementalApplication::new(&filter_collections, create_input(), &mut rand::rng());
+
+    assert_eq!(result.err().unwrap(), TisuError::InvalidArgument);
+}
+
+#[test]
+fn test_new_region_op_failure() {
+    let mut filter_collections = create_filter_collections();
+    filter_collections[1].region_op = Some(RegionOp::new(RegionOpKind::Replace, vec![1], vec![2]));
+
+    let result = IncrementalApplication::new(&filter_collections, create_input(), &mut rand::rng());
+
+    assert_eq!(result.err().unwrap(), TisuError::InvalidArgument);
+}
+
+#[test]
+fn test_new_connectivity_failure() {
+    let mut filter_collections = create_filter_collections();
+    filter_collections[0].connectivity = Some(Connectivity::new(vec![1]));
+
+    let result = IncrementalApplication::new(&filter_collections, create_input(), &mut rand::rng());
+
+    assert_eq!(result.err().unwrap(), TisuError::InvalidArgument);
+}
+
+#[test]
+fn test_new_distance_constraints_failure() {
+    let mut filter_collections = create_filter_collections();
+    filter_collections[0].filters[0].set_distance_constraints(vec![DistanceConstraint {
+        tiles: vec![1],
+        min: Some(2.0),
+        max: None,
+    }]);
+
+    let result = IncrementalApplication::new(&filter_collections, create_input(), &mut rand::rng());
+
+    assert_eq!(result.err().unwrap(), TisuError::InvalidArgument);
+}
+
+#[test]
+fn test_update() {
+    let filter_collections = create_filter_collections();
+    let mut application =
+        IncrementalApplication::new(&filter_collections, create_input(), &mut rand::rng()).unwrap();
+
+    // Unblock the cascade in the second row, start a new one in the fourth
+    // row and complete a square in the source
+    let changes = [
+        ((3, 1).into(), 9),
+        ((0, 3).into(), 1),
+        ((1, 3).into(), 1),
+        ((0, 4).into(), 1),
+        ((1, 4).into(), 1),
+    ];
+    let result = application.update(&filter_collections, &changes, &mut rand::rng());
+
+    assert!(result.is_ok());
+    let mut input = create_input();
+    for (position, value) in changes {
+        input.set(position, value).unwrap();
+    }
+    let expected = apply(&filter_collections, &input);
+    assert_eq!(result.unwrap(), &expected);
+    assert_eq!(application.input(), &input);
+
+    // Undo the changes
+    let changes = changes.map(|(position, _)| (position, *create_input().get(position).unwrap()));
+    let result = application.update(&filter_collections, &changes, &mut rand::rng());
+
+    assert!(result.is_ok());
+    assert_eq!(
+        result.unwrap(),
+        &apply(&filter_collections, &create_input())
+    );
+}
+
+#[test]
+fn test_update_local() {
+    let filter_collections = create_filter_collections();
+    let mut input = Map::filled((30, 20).into(), 9);
+    input.set((2, 2).into(), 1).unwrap();
+    input.set((20, 15).into(), 1).unwrap();
+    input.set((25, 15).into(), 3).unwrap();
+    let mut application =
+        IncrementalApplication::new(&filter_collections, input.clone(), &mut rand::rng()).unwrap();
+
+    // Changes whose effects stay close to them (blocked cascades and a square
+    // in the source), followed by ones unblocking or removing cascades that
+    // reach the end of their rows
+    let changes = [
+        ((12, 10).into(), 3),
+        ((12, 11).into(), 3),
+        ((10, 10).into(), 1),
+        ((10, 11).into(), 1),
+        ((11, 10).into(), 1),
+        ((11, 11).into(), 1),
+        ((25, 15).into(), 9),
+        ((2, 2).into(), 9),
+    ];
+    for (position, value) in changes {
+        input.set(position, value).unwrap();
+        let result =
+            application.update(&filter_collections, &[(position, value)], &mut rand::rng());
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), &apply(&filter_collections, &input));
+    }
+}
+
+#[test]
+fn test_update_rect() {
+    let filter_collections = create_filter_collections();
+    let mut application =
+        IncrementalApplication::new(&filter_collections, create_input(), &mut rand::rng()).unwrap();
+    let mut input = create_input();
+    input.set((1, 1).into(), 1).unwrap();
+    input.set((1, 2).into(), 1).unwrap();
+    input.set((2, 2).into(), 3).unwrap();
+
+    let result = application.update_rect(
+        &filter_collections,
+        input.clone(),
+        (1, 1, 2, 2).try_into().unwrap(),
+        &mut rand::rng(),
+    );
+
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), &apply(&filter_collections, &input));
+}
+
+#[test]
+fn test_update_failure() {
+    let filter_collections = create_filter_collections();
+    let mut application =
+        IncrementalApplication::new(&filter_collections, create_input(), &mut rand::rng()).unwrap();
+
+    assert_eq!(
+        application
+            .update(&filter_collections, &[((6, 0).into(), 1)], &mut rand::rng())
+            .err()
+            .unwrap(),
+        TisuError::OutOfBounds
+    );
+    assert_eq!(
+        application
+            .update(&filter_collections[..1], &[], &mut rand::rng())
+            .err()
+            .unwrap(),
+        TisuError::InvalidArgument
+    );
+    assert_eq!(
+        application
+            .update_rect(
+                &filter_collections,
+                Map::new((2, 2).into()),
+                (0, 0, 1, 1).try_into().unwrap(),
+                &mut rand::rng()
+            )
+            .err()
+            .unwrap(),
+        TisuError::InvalidMapSize
+    );
+}
+
+#[test]
+fn test_update_substitute_flips() {
+    let tile = |index, flip_h| TiledTile {
+        index: Some(index),
+        flip_h,
+        ..Default::default()
+    };
+    // The first filter copies the orientation of the replaced fields, which
+    // the second filter matches exactly
+    let mut copy = Filter::new_with_properties(
+        Map::from_data([[tile(1, false)]]).unwrap(),
+        Map::from_data([[tile(2, false)]]).unwrap(),
+        TiledTile::default(),
+        FilterProperties {
+            match_flips: MatchFlips::Ignore,
+            substitute_flips: SubstituteFlips::Copy,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    copy.set_field_orientation();
+    let flipped = Filter::new_with_properties(
+        Map::from_data([[tile(2, true)]]).unwrap(),
+        Map::from_data([[tile(3, false)]]).unwrap(),
+        TiledTile::default(),
+        FilterProperties {
+            pattern_matching: PatternMatching::Destination,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let filter_collections = [
+        FilterCollection::new(&[copy]),
+        FilterCollection::new(&[flipped]),
+    ];
+    let input = Map::from_data([[tile(1, false), tile(1, true)]]).unwrap();
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut application =
+        IncrementalApplication::new(&filter_collections, input, &mut rng).unwrap();
+    assert_eq!(
+        application.output(),
+        &Map::from_data([[tile(2, false), tile(3, false)]]).unwrap()
+    );
+
+    let result = application.update(
+        &filter_collections,
+        &[((0, 0).into(), tile(1, true))],
+        &mut rng,
+    );
+
+    assert_eq!(
+        result,
+        Ok(&Map::from_data([[tile(3, false), tile(3, false)]]).unwrap())
+    );
+}
+
+#[test]
+fn test_update_output() {
+    let squares = create_filter([[1, 1], [1, 1]], [[5, 0], [0, 0]], PatternMatching::Source);
+    let diagonals = create_filter([[1, 9], [9, 1]], [[6, 0], [0, 0]], PatternMatching::Source);
+    let filter_collections = [
+        FilterCollection::new(&[squares]),
+        FilterCollection::new(&[diagonals]),
+    ];
+    let mut input = Map::filled((20, 10).into(), 9);
+    input.set((3, 3).into(), 1).unwrap();
+    input.set((4, 3).into(), 1).unwrap();
+    input.set((3, 4).into(), 1).unwrap();
+    let mut output = apply(&filter_collections, &input);
+    // Fields far from the changes keep the previous output
+    output.set((18, 8).into(), 7).unwrap();
+
+    let changes = [((4, 4).into(), 1), ((5, 5).into(), 1)];
+    let result = incremental::update_output(
+        &filter_collections,
+        &input,
+        &output,
+        &changes,
+        &mut rand::rng(),
+    );
+
+    assert!(result.is_ok());
+    for (position, value) in changes {
+        input.set(position, value).unwrap();
+    }
+    let mut expected = apply(&filter_collections, &input);
+    expected.set((18, 8).into(), 7).unwrap();
+    assert_eq!(result.unwrap(), expected);
+}
+
+#[test]
+fn test_update_output_destination() {
+    let filter_collections = create_filter_collections();
+    let input = create_input();
+    let output = apply(&filter_collections, &input);
+
+    let changes = [((3, 1).into(), 9), ((0, 3).into(), 1)];
+    let result = incremental::update_output(
+        &filter_collections,
+        &input,
+        &output,
+        &changes,
+        &mut rand::rng(),
+    );
+
+    let mut changed_input = input.clone();
+    for (position, value) in changes {
+        changed_input.set(position, value).unwrap();
+    }
+    assert_eq!(result, Ok(apply(&filter_collections, &changed_input)));
+}
+
+#[test]
+fn test_update_output_failure() {
+    let filter_collections = create_filter_collections();
+    let input = create_input();
+
+    assert_eq!(
+        incremental::update_output(
+            &filter_collections,
+            &input,
+            &Map::new((2, 2).into()),
+            &[],
+            &mut rand::rng()
+        ),
+        Err(TisuError::InvalidMapSize)
+    );
+    assert_eq!(
+        incremental::update_output(
+            &filter_collections,
+            &input,
+            &input,
+            &[((6, 0).into(), 1)],
+            &mut rand::rng()
+        ),
+        Err(TisuError::OutOfBounds)
+    );
+}
@@ -0,0 +1,204 @@
+use crate::map::Map;
+use crate::map_object::MapObject;
+use crate::vector2::Vector2u;
+
+/// Content of a layer of a layered map
+#[derive(Clone, PartialEq, Debug)]
+pub enum LayerContent<T> {
+    /// A map of tiles
+    Tiles(Map<T>),
+    /// Objects placed on the map
+    Objects(Vec<MapObject>),
+    /// Child layers, from bottom to top
+    Group(Vec<Layer<T>>),
+}
+
+/// A named layer of a layered map
+#[derive(Clone, PartialEq, Debug)]
+pub struct Layer<T> {
+    /// Name of the layer
+    pub name: String,
+    /// Offset of the layer in pixels
+    pub offset: (f32, f32),
+    /// Opacity of the layer, in range `[0..1]`
+    pub opacity: f32,
+    /// Whether the layer is shown
+    pub visible: bool,
+    /// Custom properties of the layer
+    pub properties: tiled::Properties,
+    /// Tiles, objects or child layers of the layer
+    pub content: LayerContent<T>,
+}
+
+impl<T> Layer<T> {
+    /// Creates a visible and opaque layer with the given name and content,
+    /// without offset and properties.
+    pub fn new(name: &str, content: LayerContent<T>) -> Self {
+        Self {
+            name: String::from(name),
+            offset: (0.0, 0.0),
+            opacity: 1.0,
+            visible: true,
+            properties: tiled::Properties::new(),
+            content,
+        }
+    }
+
+    /// Returns the tiles of the layer, if it's a tile layer.
+    pub fn tiles(&self) -> Option<&Map<T>> {
+        match &self.content {
+            LayerContent::Tiles(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Returns the mutable tiles of the layer, if it's a tile layer.
+    pub fn tiles_mut(&mut self) -> Option<&mut Map<T>> {
+        match &mut self.content {
+            LayerContent::Tiles(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Returns the child layers of the layer, if it's a group layer.
+    pub fn children(&self) -> Option<&[Layer<T>]> {
+        match &self.content {
+            LayerContent::Group(layers) => Some(layers),
+            _ => None,
+        }
+    }
+}
+
+/// A map made of named layers, ordered from bottom to top, which can be
+/// nested in groups. Layers are looked up by their path: their name, preceded
+/// by the names of the groups they're in, separated by `/` (e.g.
+/// `Dungeon/Walls`).
+#[derive(Clone, PartialEq, Debug)]
+pub struct LayeredMap<T> {
+    /// Size of the map in fields
+    pub size: Vector2u,
+    /// Layers, from bottom to top
+    pub layers: Vec<Layer<T>>,
+}
+
+impl<T> LayeredMap<T> {
+    /// Creates a layered map of the given size without layers.
+    pub fn new(size: Vector2u) -> Self {
+        Self {
+            size,
+            layers: vec![],
+        }
+    }
+
+    /// Creates a layered map with the given map as its only tile layer.
+    pub fn from_map(name: &str, map: Map<T>) -> Self {
+        Self {
+            size: map.size(),
+            layers: vec![Layer::new(name, LayerContent::Tiles(map))],
+        }
+    }
+
+    /// Returns the layer at the given path, if any.
+    pub fn layer(&self, path: &str) -> Option<&Layer<T>> {
+        let mut names = path.split('/');
+        let mut layer = find_layer(&self.layers, names.next()?)?;
+        for name in names {
+            layer = find_layer(layer.children()?, name)?;
+        }
+        Some(layer)
+    }
+
+    /// Returns the mutable layer at the given path, if any.
+    pub fn layer_mut(&mut self, path: &str) -> Option<&mut Layer<T>> {
+        let mut names = path.split('/');
+        let mut layer = find_layer_mut(&mut self.layers, names.next()?)?;
+        for name in names {
+            let LayerContent::Group(children) = &mut layer.content else {
+                return None;
+            };
+            layer = find_layer_mut(children, name)?;
+        }
+        Some(layer)
+    }
+
+    /// Returns the paths of all the layers, groups included, from bottom to
+    /// top (a group comes before its children).
+    pub fn paths(&self) -> Vec<String> {
+        let mut result = vec![];
+        collect_paths(&self.layers, "", &mut result);
+        result
+    }
+
+    /// Returns the tile layers with their paths, from bottom to top.
+    pub fn tile_layers(&self) -> Vec<(String, &Map<T>)> {
+        let mut result = vec![];
+        collect_tile_layers(&self.layers, "", &mut result);
+        result
+    }
+
+    /// Returns the topmost tile layer, which is the one filters are applied
+    /// to.
+    pub fn top_tile_layer(&self) -> Option<&Map<T>> {
+        top_tile_layer(&self.layers)
+    }
+
+    /// Returns the topmost mutable tile layer.
+    pub fn top_tile_layer_mut(&mut self) -> Option<&mut Map<T>> {
+        top_tile_layer_mut(&mut self.layers)
+    }
+}
+
+/// Finds the topmost of the given layers with the given name.
+fn find_layer<'a, T>(layers: &'a [Layer<T>], name: &str) -> Option<&'a Layer<T>> {
+    layers.iter().rev().find(|layer| layer.name == name)
+}
+
+fn find_layer_mut<'a, T>(layers: &'a mut [Layer<T>], name: &str) -> Option<&'a mut Layer<T>> {
+    layers.iter_mut().rev().find(|layer| layer.name == name)
+}
+
+fn collect_paths<T>(layers: &[Layer<T>], prefix: &str, result: &mut Vec<String>) {
+    for layer in layers {
+        let path = format!("{}{}", prefix, layer.name);
+        result.push(path.clone());
+        if let Some(children) = layer.children() {
+            collect_paths(children, &format!("{}/", path), result);
+        }
+    }
+}
+
+fn collect_tile_layers<'a, T>(
+    layers: &'a [Layer<T>],
+    prefix: &str,
+    result: &mut Vec<(String, &'a Map<T>)>,
+) {
+    for layer in layers {
+        let path = format!("{}{}", prefix, layer.name);
+        match &layer.content {
+            LayerContent::Tiles(map) => result.push((path, map)),
+            LayerContent::Objects(_) => (),
+            LayerContent::Group(children) => {
+                collect_tile_layers(children, &format!("{}/", path), result)
+            }
+        }
+    }
+}
+
+fn top_tile_layer<T>(layers: &[Layer<T>]) -> Option<&Map<T>> {
+    layers.iter().rev().find_map(|layer| match &layer.content {
+        LayerContent::Tiles(map) => Some(map),
+        LayerContent::Objects(_) => None,
+        LayerContent::Group(children) => top_tile_layer(children),
+    })
+}
+
+fn top_tile_layer_mut<T>(layers: &mut [Layer<T>]) -> Option<&mut Map<T>> {
+    layers
+        .iter_mut()
+        .rev()
+        .find_map(|layer| match &mut layer.content {
+            LayerContent::Tiles(map) => Some(map),
+            LayerContent::Objects(_) => None,
+            LayerContent::Group(children) => top_tile_layer_mut(children),
+        })
+}
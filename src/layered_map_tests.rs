@@ -0,0 +1,126 @@
+use crate::{
+    layered_map::{Layer, LayerContent, LayeredMap},
+    map::Map,
+    map_object::MapObject,
+};
+
+fn create_layered_map() -> LayeredMap<u32> {
+    let tiles = |value| LayerContent::Tiles(Map::from_data([[value, value]]).unwrap());
+    LayeredMap {
+        size: (2, 1).into(),
+        layers: vec![
+            Layer::new("Ground", tiles(1)),
+            Layer::new(
+                "Dungeon",
+                LayerContent::Group(vec![
+                    Layer::new("Walls", tiles(2)),
+                    Layer::new("Spawns", LayerContent::Objects(vec![MapObject::default()])),
+                ]),
+            ),
+            Layer::new("Ground", tiles(3)),
+        ],
+    }
+}
+
+#[test]
+fn test_from_map() {
+    let map = Map::from_data([[1, 2, 3]]).unwrap();
+
+    let result = LayeredMap::from_map("Tiles", map.clone());
+
+    assert_eq!(result.size, (3, 1).into());
+    assert_eq!(result.layers.len(), 1);
+    assert_eq!(result.layers[0].name, "Tiles");
+    assert_eq!(result.layers[0].opacity, 1.0);
+    assert!(result.layers[0].visible);
+    assert_eq!(result.top_tile_layer(), Some(&map));
+}
+
+#[test]
+fn test_layer() {
+    let layered_map = create_layered_map();
+
+    let walls = layered_map.layer("Dungeon/Walls").unwrap();
+    assert_eq!(walls.tiles().unwrap().data(), [2, 2]);
+    assert!(layered_map
+        .layer("Dungeon/Spawns")
+        .unwrap()
+        .tiles()
+        .is_none());
+    assert_eq!(
+        layered_map
+            .layer("Dungeon")
+            .unwrap()
+            .children()
+            .unwrap()
+            .len(),
+        2
+    );
+    // The topmost of the layers with the same name is found
+    assert_eq!(
+        layered_map.layer("Ground").unwrap().tiles().unwrap().data(),
+        [3, 3]
+    );
+    assert!(layered_map.layer("Walls").is_none());
+    assert!(layered_map.layer("Ground/Walls").is_none());
+}
+
+#[test]
+fn test_layer_mut() {
+    let mut layered_map = create_layered_map();
+
+    let walls = layered_map.layer_mut("Dungeon/Walls").unwrap();
+    walls.visible = false;
+    walls.tiles_mut().unwrap().mut_data()[0] = 5;
+
+    let walls = layered_map.layer("Dungeon/Walls").unwrap();
+    assert!(!walls.visible);
+    assert_eq!(walls.tiles().unwrap().data(), [5, 2]);
+}
+
+#[test]
+fn test_paths() {
+    let layered_map = create_layered_map();
+
+    assert_eq!(
+        layered_map.paths(),
+        [
+            "Ground",
+            "Dungeon",
+            "Dungeon/Walls",
+            "Dungeon/Spawns",
+            "Ground"
+        ]
+    );
+    assert_eq!(
+        layered_map
+            .tile_layers()
+            .iter()
+            .map(|(path, map)| (path.as_str(), map.data()[0]))
+            .collect::<Vec<_>>(),
+        [("Ground", 1), ("Dungeon/Walls", 2), ("Ground", 3)]
+    );
+}
+
+#[test]
+fn test_top_tile_layer() {
+    let mut layered_map = create_layered_map();
+
+    assert_eq!(layered_map.top_tile_layer().unwrap().data(), [3, 3]);
+
+    // Tile layers in groups count too
+    layered_map.layers.pop();
+    assert_eq!(layered_map.top_tile_layer().unwrap().data(), [2, 2]);
+    layered_map.top_tile_layer_mut().unwrap().mut_data()[1] = 7;
+    assert_eq!(
+        layered_map
+            .layer("Dungeon/Walls")
+            .unwrap()
+            .tiles()
+            .unwrap()
+            .data(),
+        [2, 7]
+    );
+
+    assert_eq!(LayeredMap::<u32>::new((2, 2).into()).top_tile_layer(), None);
+}
@@ -1,24 +1,73 @@
+pub mod apply_stats;
+pub mod chunked_map;
+pub mod connectivity;
+pub mod distance;
 pub mod filter;
+pub mod filter_example;
+pub mod filter_exporter;
 pub mod filter_importer;
+pub mod filter_loader;
+pub mod incremental;
+pub mod layered_map;
 pub mod map;
 pub mod map_exporter;
 pub mod map_importer;
+pub mod map_object;
 pub mod map_segmenter;
+pub mod map_storage;
+#[cfg(feature = "serde")]
+pub mod pipeline;
 pub mod rect2;
+pub mod region_mask;
+pub mod regions;
+pub mod sequence;
+#[cfg(feature = "serde")]
+pub mod structured_filter_exporter;
+#[cfg(feature = "serde")]
+pub mod structured_filter_importer;
+pub mod text_filter_exporter;
+pub mod text_filter_importer;
+pub mod tile_index;
+pub mod tile_metadata;
+pub mod tiled_filter_checker;
+pub mod tiled_filter_exporter;
 pub mod tiled_filter_importer;
 pub mod tiled_map_exporter;
 pub mod tiled_map_importer;
 pub mod tiled_tile;
 pub mod tisu_error;
 pub mod vector2;
+pub mod wfc;
 
+#[cfg(test)]
+mod chunked_map_tests;
+#[cfg(test)]
+mod connectivity_tests;
+#[cfg(test)]
+mod distance_tests;
+#[cfg(test)]
+mod filter_example_tests;
 #[cfg(test)]
 mod filter_tests;
 #[cfg(test)]
+mod incremental_tests;
+#[cfg(test)]
+mod layered_map_tests;
+#[cfg(test)]
 mod map_segmenter_tests;
 #[cfg(test)]
 mod map_tests;
 #[cfg(test)]
 mod rect2_tests;
 #[cfg(test)]
+mod region_mask_tests;
+#[cfg(test)]
+mod regions_tests;
+#[cfg(test)]
+mod sequence_tests;
+#[cfg(test)]
+mod tile_index_tests;
+#[cfg(test)]
 mod vector2_tests;
+#[cfg(test)]
+mod wfc_tests;
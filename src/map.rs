@@ -2,9 +2,36 @@ use crate::rect2::Rect2u;
 use crate::tisu_error::TisuError;
 use crate::vector2::Vector2u;
 use std::fmt::Display;
+use std::ops::{Index, IndexMut};
+
+const HIGHLIGHT_START: &str = "\x1b[1;33m";
+const HIGHLIGHT_END: &str = "\x1b[0m";
+
+/// Axis along which a map is flipped
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FlipAxis {
+    /// Mirrors the map left to right (field `(x, y)` moves to
+    /// `(width - 1 - x, y)`)
+    Horizontal,
+    /// Mirrors the map top to bottom (field `(x, y)` moves to
+    /// `(x, height - 1 - y)`)
+    Vertical,
+}
 
 /// A generic map
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        into = "Vec<Vec<T>>",
+        try_from = "Vec<Vec<T>>",
+        bound(
+            serialize = "T: Clone + serde::Serialize",
+            deserialize = "T: serde::Deserialize<'de>"
+        )
+    )
+)]
 pub struct Map<T> {
     /// Size of the map
     size: Vector2u,
@@ -20,7 +47,7 @@ impl<T> Map<T> {
     {
         Self {
             size,
-            data: vec![T::default(); (size.x * size.y).try_into().unwrap()],
+            data: vec![T::default(); field_count(size)],
         }
     }
 
@@ -76,7 +103,7 @@ impl<T> Map<T> {
         if point.x >= self.size().x || point.y >= self.size().y {
             Err(TisuError::OutOfBounds)
         } else {
-            let idx = self.idx(point);
+            let idx = self.field_index(point);
             self.data.get(idx).ok_or(TisuError::OutOfBounds)
         }
     }
@@ -90,14 +117,24 @@ impl<T> Map<T> {
         if point.x >= self.size().x || point.y >= self.size().y {
             Err(TisuError::OutOfBounds)
         } else {
-            let idx = self.idx(point);
+            let idx = self.field_index(point);
             *(self.data.get_mut(idx).ok_or(TisuError::OutOfBounds)?) = value;
             Ok(())
         }
     }
 
-    fn idx(&self, point: Vector2u) -> usize {
-        (point.y * self.size.x + point.x).try_into().unwrap()
+    /// Returns the index of the field at the given position in the map data
+    /// (which is stored row by row). The position isn't checked against the
+    /// map bounds.
+    pub fn field_index(&self, point: Vector2u) -> usize {
+        point.y as usize * self.size.x as usize + point.x as usize
+    }
+
+    /// Returns the position of the field at the given index in the map data
+    /// (see `field_index`).
+    pub fn field_position(&self, index: usize) -> Vector2u {
+        let width = self.size.x as usize;
+        Vector2u::new((index % width) as u32, (index / width) as u32)
     }
 
     /// Maps the field values to type G using the given mapper.
@@ -121,18 +158,215 @@ impl<T> Map<T> {
     where
         T: Clone + Default,
     {
-        let map_rect = Rect2u::from(self);
-        if map_rect.contains_rect(&segment_rect) {
-            let mut segment = Map::new(segment_rect.size());
-            for x in 0..segment.size().x {
-                for y in 0..segment.size().y {
-                    let value = self.get(segment_rect.position() + (x, y).into())?;
-                    segment.set((x, y).into(), value.clone())?;
+        self.crop(segment_rect)
+    }
+
+    /// Returns the part of the map inside the given rectangle as a separate
+    /// map.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the given rectangle exceeds map bounds.
+    pub fn crop(&self, rect: Rect2u) -> Result<Map<T>, TisuError>
+    where
+        T: Clone,
+    {
+        let end = self.end_of(rect.position(), rect.size())?;
+        let mut data = Vec::with_capacity(field_count(rect.size()));
+        for y in rect.position().y..end.y {
+            let start = self.field_index((rect.position().x, y).into());
+            data.extend_from_slice(&self.data[start..start + rect.size().x as usize]);
+        }
+        Ok(Map {
+            size: rect.size(),
+            data,
+        })
+    }
+
+    /// Returns a copy of the map surrounded by fields with the given value:
+    /// `before` fields to the left and the top, `after` fields to the right
+    /// and the bottom.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the size of the padded map overflows.
+    pub fn pad(&self, before: Vector2u, after: Vector2u, fill: T) -> Result<Map<T>, TisuError>
+    where
+        T: Clone,
+    {
+        let size = self
+            .size
+            .checked_add(before)
+            .and_then(|size| size.checked_add(after))
+            .ok_or(TisuError::InvalidArgument)?;
+        let mut result = Map::filled(size, fill);
+        for (row, source_row) in result.rows_mut().skip(before.y as usize).zip(self.rows()) {
+            row[before.x as usize..][..source_row.len()].clone_from_slice(source_row);
+        }
+        Ok(result)
+    }
+
+    /// Returns a copy of the map with the given size. Fields outside the new
+    /// size are dropped and new fields (to the right and the bottom) get the
+    /// given value.
+    pub fn resize(&self, size: Vector2u, fill: T) -> Map<T>
+    where
+        T: Clone,
+    {
+        let mut result = Map::filled(size, fill);
+        for (row, source_row) in result.rows_mut().zip(self.rows()) {
+            let width = row.len().min(source_row.len());
+            row[..width].clone_from_slice(&source_row[..width]);
+        }
+        result
+    }
+
+    /// Returns a copy of the map rotated clockwise by the given number of
+    /// quarter turns. With one turn, field `(x, y)` moves to
+    /// `(height - 1 - y, x)` and the width and the height are swapped.
+    pub fn rotate90(&self, turns: u32) -> Map<T>
+    where
+        T: Clone,
+    {
+        let (width, height) = (self.size.x, self.size.y);
+        let source = |x: u32, y: u32| match turns % 4 {
+            0 => (x, y),
+            1 => (y, height - 1 - x),
+            2 => (width - 1 - x, height - 1 - y),
+            _ => (width - 1 - y, x),
+        };
+        let size = match turns % 4 {
+            1 | 3 => Vector2u::new(height, width),
+            _ => self.size,
+        };
+        self.remap(size, source)
+    }
+
+    /// Returns a copy of the map mirrored along the given axis.
+    pub fn flip(&self, axis: FlipAxis) -> Map<T>
+    where
+        T: Clone,
+    {
+        let (width, height) = (self.size.x, self.size.y);
+        self.remap(self.size, |x, y| match axis {
+            FlipAxis::Horizontal => (width - 1 - x, y),
+            FlipAxis::Vertical => (x, height - 1 - y),
+        })
+    }
+
+    /// Creates a map of the given size whose field `(x, y)` is the field of
+    /// this map at the position returned by `source`.
+    fn remap<F>(&self, size: Vector2u, source: F) -> Map<T>
+    where
+        T: Clone,
+        F: Fn(u32, u32) -> (u32, u32),
+    {
+        let mut data = Vec::with_capacity(self.data.len());
+        for y in 0..size.y {
+            for x in 0..size.x {
+                data.push(self.data[self.field_index(source(x, y).into())].clone());
+            }
+        }
+        Map { size, data }
+    }
+
+    /// Copies the given map onto this map, with its top left field at the
+    /// given position. Fields of the pasted map equal to the given transparent
+    /// value (e.g. the wildcard of a filter) are skipped. Returns the number
+    /// of fields whose value changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pasted map exceeds map bounds.
+    pub fn paste(
+        &mut self,
+        map: &Map<T>,
+        position: Vector2u,
+        transparent: Option<&T>,
+    ) -> Result<usize, TisuError>
+    where
+        T: Clone + PartialEq,
+    {
+        self.end_of(position, map.size)?;
+        Ok(self.paste_at(map, position, transparent))
+    }
+
+    /// Returns the end (exclusive) of the area with the given position and
+    /// size.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the area exceeds map bounds.
+    fn end_of(&self, position: Vector2u, size: Vector2u) -> Result<Vector2u, TisuError> {
+        position
+            .checked_add(size)
+            .filter(|end| end.x <= self.size.x && end.y <= self.size.y)
+            .ok_or(TisuError::InvalidArgument)
+    }
+
+    fn paste_at(&mut self, map: &Map<T>, position: Vector2u, transparent: Option<&T>) -> usize
+    where
+        T: Clone + PartialEq,
+    {
+        let mut changes = 0;
+        for (y, row) in map.rows().enumerate() {
+            for (x, field) in row.iter().enumerate() {
+                if transparent == Some(field) {
+                    continue;
+                }
+                let idx = self.field_index(position + (x as u32, y as u32).into());
+                if &self.data[idx] != field {
+                    self.data[idx] = field.clone();
+                    changes += 1;
                 }
             }
-            Ok(segment)
-        } else {
-            Err(TisuError::InvalidArgument)
+        }
+        changes
+    }
+
+    /// Returns a map made of copies of this map, repeated the given number of
+    /// times horizontally and vertically.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the size of the tiled map overflows.
+    pub fn tile(&self, count: Vector2u) -> Result<Map<T>, TisuError>
+    where
+        T: Clone,
+    {
+        let size = self
+            .size
+            .checked_mul(count)
+            .ok_or(TisuError::InvalidArgument)?;
+        Ok(self.remap(size, |x, y| (x % self.size.x, y % self.size.y)))
+    }
+
+    /// Returns an iterator over the rows of the map, from top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        self.data.chunks(self.size.x.max(1) as usize)
+    }
+
+    /// Returns an iterator over the mutable rows of the map, from top to
+    /// bottom.
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [T]> {
+        self.data.chunks_mut(self.size.x.max(1) as usize)
+    }
+
+    /// Returns an iterator over the columns of the map, from left to right,
+    /// each iterating over its fields from top to bottom.
+    pub fn columns(&self) -> impl Iterator<Item = impl Iterator<Item = &T>> {
+        let width = self.size.x as usize;
+        (0..width).map(move |x| self.data.iter().skip(x).step_by(width))
+    }
+
+    /// Creates a map of the given size with all fields set to the given value.
+    pub fn filled(size: Vector2u, value: T) -> Self
+    where
+        T: Clone,
+    {
+        Self {
+            size,
+            data: vec![value; field_count(size)],
         }
     }
 
@@ -141,14 +375,125 @@ impl<T> Map<T> {
     where
         T: Display,
     {
-        for y in 0..self.size.x {
-            for x in 0..self.size.y {
-                let idx = self.idx((x, y).into());
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                let idx = self.field_index((x, y).into());
                 print!("{}", self.data.get(idx).unwrap());
             }
             println!();
         }
     }
+
+    /// Returns a text representation of the map where the fields that differ
+    /// from the given reference map are highlighted using ANSI colours.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the size of the reference map is different from the
+    /// size of the map.
+    pub fn to_highlighted_string(&self, reference: &Map<T>) -> Result<String, TisuError>
+    where
+        T: Display + PartialEq,
+    {
+        if self.size != reference.size {
+            return Err(TisuError::InvalidMapSize);
+        }
+
+        let field_width = self
+            .data
+            .iter()
+            .map(|field| field.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let mut result = String::new();
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                let idx = self.field_index((x, y).into());
+                if x > 0 {
+                    result.push(' ');
+                }
+                let field = format!("{:>field_width$}", self.data[idx]);
+                if self.data[idx] != reference.data[idx] {
+                    result.push_str(&format!("{HIGHLIGHT_START}{field}{HIGHLIGHT_END}"));
+                } else {
+                    result.push_str(&field);
+                }
+            }
+            result.push('\n');
+        }
+        Ok(result)
+    }
+}
+
+/// Returns the number of fields of a map of the given size. It's computed
+/// with `usize`, as it can exceed `u32` for huge maps.
+fn field_count(size: Vector2u) -> usize {
+    size.x as usize * size.y as usize
+}
+
+impl<T> From<Map<T>> for Vec<Vec<T>> {
+    fn from(map: Map<T>) -> Self {
+        let width = map.size.x as usize;
+        let mut rows = vec![];
+        let mut data = map.data.into_iter();
+        for _ in 0..map.size.y {
+            rows.push(data.by_ref().take(width).collect());
+        }
+        rows
+    }
+}
+
+impl<T> TryFrom<Vec<Vec<T>>> for Map<T> {
+    type Error = TisuError;
+
+    /// Creates a map from the given rows of map data.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the rows have different lengths.
+    fn try_from(rows: Vec<Vec<T>>) -> Result<Self, Self::Error> {
+        let map_width = rows.first().map(|row| row.len()).unwrap_or(0);
+        if rows.iter().any(|row| row.len() != map_width) {
+            return Err(TisuError::InvalidArgument);
+        }
+        let map_height = rows.len();
+        Ok(Self {
+            size: Vector2u::new(
+                map_width.try_into().map_err(|_| TisuError::Unexpected)?,
+                map_height.try_into().map_err(|_| TisuError::Unexpected)?,
+            ),
+            data: rows.into_iter().flatten().collect(),
+        })
+    }
+}
+
+impl<T> Index<Vector2u> for Map<T> {
+    type Output = T;
+
+    /// Returns the field at the given position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the given position is out of map bounds.
+    fn index(&self, point: Vector2u) -> &Self::Output {
+        self.get(point).expect("position is out of map bounds")
+    }
+}
+
+impl<T> IndexMut<Vector2u> for Map<T> {
+    /// Returns the mutable field at the given position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the given position is out of map bounds.
+    fn index_mut(&mut self, point: Vector2u) -> &mut Self::Output {
+        assert!(
+            point.x < self.size.x && point.y < self.size.y,
+            "position is out of map bounds"
+        );
+        let idx = self.field_index(point);
+        &mut self.data[idx]
+    }
 }
 
 impl<T> From<&Map<T>> for Rect2u {
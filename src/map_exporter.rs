@@ -1,6 +1,6 @@
 use std::path::Path;
 
-use crate::map::Map;
+use crate::layered_map::LayeredMap;
 use crate::tisu_error::TisuError;
 use crate::vector2::Vector2u;
 
@@ -9,7 +9,7 @@ pub trait MapExporter {
 
     fn save(
         file: impl AsRef<Path>,
-        map: &Map<Self::TileType>,
+        map: &LayeredMap<Self::TileType>,
         tile_size: Vector2u,
         tileset_path: impl AsRef<Path>,
     ) -> Result<(), TisuError>;
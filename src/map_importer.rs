@@ -1,11 +1,20 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
+use crate::layered_map::LayeredMap;
 use crate::map::Map;
+use crate::tile_metadata::TilesetMetadata;
 use crate::tisu_error::TisuError;
 
 pub struct LoadResult<T> {
-    pub map_layers: Vec<Map<T>>,
+    /// Layers of the map
+    pub map: LayeredMap<T>,
     pub tileset_path: PathBuf,
+    /// Masks of the named regions of the map, which filter collections can be
+    /// restricted to
+    pub regions: HashMap<String, Map<bool>>,
+    /// Class and custom properties of the tiles of the tileset
+    pub tile_metadata: TilesetMetadata,
 }
 
 pub trait MapImporter {
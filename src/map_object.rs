@@ -0,0 +1,89 @@
+/// Shape of a map object. Sizes and points are in fields, like the position
+/// of the object.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub enum ObjectShape {
+    /// A point at the position of the object
+    #[default]
+    Point,
+    /// A rectangle with its top left corner at the position of the object
+    Rect { width: f32, height: f32 },
+    /// An ellipse inside the rectangle with its top left corner at the
+    /// position of the object
+    Ellipse { width: f32, height: f32 },
+    /// A closed polygon, with points relative to the position of the object
+    Polygon { points: Vec<(f32, f32)> },
+    /// An open polyline, with points relative to the position of the object
+    Polyline { points: Vec<(f32, f32)> },
+}
+
+/// An object placed on a map, like a spawn point, a chest or a light source.
+/// Filters can emit objects along with their substitutes (see
+/// `Filter::set_objects`).
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct MapObject {
+    /// Name of the object
+    pub name: String,
+    /// Class of the object
+    pub class: String,
+    /// Position of the object in fields (e.g. `(1.5, 2.5)` is the center of
+    /// field `(1, 2)`). For the objects of a filter, the position is relative
+    /// to the position of the substitute.
+    pub position: (f32, f32),
+    /// Shape of the object
+    pub shape: ObjectShape,
+    /// Clockwise rotation of the object around its position, in degrees
+    pub rotation: f32,
+    /// Custom properties of the object
+    pub properties: tiled::Properties,
+}
+
+impl MapObject {
+    /// Returns a copy of the object moved by the given offset (in fields).
+    pub fn translated(&self, offset: (f32, f32)) -> Self {
+        Self {
+            position: (self.position.0 + offset.0, self.position.1 + offset.1),
+            ..self.clone()
+        }
+    }
+}
+
+/// Converts a Tiled object, whose position and shape are in pixels. Text
+/// objects are converted to rectangles of their size.
+impl From<&tiled::Object<'_>> for MapObject {
+    fn from(value: &tiled::Object<'_>) -> Self {
+        let tile_width = value.map().tile_width as f32;
+        let tile_height = value.map().tile_height as f32;
+        let to_fields = |points: &[(f32, f32)]| {
+            points
+                .iter()
+                .map(|(x, y)| (x / tile_width, y / tile_height))
+                .collect()
+        };
+        let shape = match &value.shape {
+            tiled::ObjectShape::Rect { width, height }
+            | tiled::ObjectShape::Text { width, height, .. } => ObjectShape::Rect {
+                width: width / tile_width,
+                height: height / tile_height,
+            },
+            tiled::ObjectShape::Ellipse { width, height } => ObjectShape::Ellipse {
+                width: width / tile_width,
+                height: height / tile_height,
+            },
+            tiled::ObjectShape::Polygon { points } => ObjectShape::Polygon {
+                points: to_fields(points),
+            },
+            tiled::ObjectShape::Polyline { points } => ObjectShape::Polyline {
+                points: to_fields(points),
+            },
+            tiled::ObjectShape::Point(..) => ObjectShape::Point,
+        };
+        Self {
+            name: value.name.clone(),
+            class: value.user_type.clone(),
+            position: (value.x / tile_width, value.y / tile_height),
+            shape,
+            rotation: value.rotation,
+            properties: value.properties.clone(),
+        }
+    }
+}
@@ -1,13 +1,14 @@
-use crate::map::Map;
+use crate::map_storage::MapStorage;
 use crate::rect2::Rect2u;
 use crate::tisu_error::TisuError;
 use crate::vector2::{Vector2i, Vector2u};
 
 /// Returns a vector of rectangles in the given map separated by transparent
 /// fields.
-pub fn extract_segments<T>(map: &Map<T>, transparent_value: &T) -> Vec<Rect2u>
+pub fn extract_segments<T, M>(map: &M, transparent_value: &T) -> Vec<Rect2u>
 where
     T: PartialEq,
+    M: MapStorage<T> + ?Sized,
 {
     let mut result = vec![];
 
@@ -25,9 +26,10 @@ where
 }
 
 /// Checks if the field of the given map at the given coordinates is transparent.
-pub fn is_field_transparent<T>(map: &Map<T>, transparent_value: &T, field: Vector2u) -> bool
+pub fn is_field_transparent<T, M>(map: &M, transparent_value: &T, field: Vector2u) -> bool
 where
     T: PartialEq,
+    M: MapStorage<T> + ?Sized,
 {
     let field2i = match Vector2i::try_from(field) {
         Err(_) => return true, // Invalid (too large) value must be transparent
@@ -36,18 +38,17 @@ where
     is_field_transparent_impl(map, transparent_value, field2i)
 }
 
-fn is_field_transparent_impl<T>(map: &Map<T>, transparent_value: &T, field: Vector2i) -> bool
+fn is_field_transparent_impl<T, M>(map: &M, transparent_value: &T, field: Vector2i) -> bool
 where
     T: PartialEq,
+    M: MapStorage<T> + ?Sized,
 {
     if field.x < 0 || field.y < 0 {
         return true;
     }
 
-    let map_rect = Rect2u::from(map);
-    if !map_rect.contains_point((field.x as u32, field.y as u32).into()) {
-        true
-    } else if let Ok(field_value) = map.get((field.x as u32, field.y as u32).into()) {
+    // Fields out of map bounds are transparent
+    if let Ok(field_value) = map.get((field.x as u32, field.y as u32).into()) {
         field_value == transparent_value
     } else {
         true
@@ -56,9 +57,10 @@ where
 
 /// Checks if the field of the given map at the given coordinates is the start
 /// of a rectangle.
-pub fn is_rect_start<T>(map: &Map<T>, transparent_value: &T, field: Vector2u) -> bool
+pub fn is_rect_start<T, M>(map: &M, transparent_value: &T, field: Vector2u) -> bool
 where
     T: PartialEq,
+    M: MapStorage<T> + ?Sized,
 {
     let field2i = match Vector2i::try_from(field) {
         Err(_) => return false, // Invalid (negative) value can't be rect start
@@ -71,13 +73,14 @@ where
 
 /// Returns the starting coordinates of the first rectangle, starting from
 /// top-left.
-pub fn find_rect_start<T>(
-    map: &Map<T>,
+pub fn find_rect_start<T, M>(
+    map: &M,
     transparent_value: &T,
     from: Vector2u,
 ) -> Result<Vector2u, TisuError>
 where
     T: PartialEq,
+    M: MapStorage<T> + ?Sized,
 {
     let mut it = from;
     while it.y < map.size().y {
@@ -95,9 +98,10 @@ where
 }
 
 /// Returns the size of the rectangle that starts at the given coordinates.
-pub fn find_rect_size<T>(map: &Map<T>, transparent_value: &T, field: Vector2u) -> Vector2u
+pub fn find_rect_size<T, M>(map: &M, transparent_value: &T, field: Vector2u) -> Vector2u
 where
     T: PartialEq,
+    M: MapStorage<T> + ?Sized,
 {
     if is_field_transparent(map, transparent_value, field) {
         Vector2u::default()
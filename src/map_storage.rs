@@ -0,0 +1,39 @@
+use crate::map::Map;
+use crate::tisu_error::TisuError;
+use crate::vector2::Vector2u;
+
+/// Storage of the fields of a map, shared by the dense `Map` and the sparse
+/// `ChunkedMap`. Filters (see `Filter::apply`) and the map segmenter work on
+/// any storage.
+pub trait MapStorage<T> {
+    /// Returns the size of the map.
+    fn size(&self) -> Vector2u;
+
+    /// Returns the value of the field at the given position.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the given position is out of map bounds.
+    fn get(&self, point: Vector2u) -> Result<&T, TisuError>;
+
+    /// Sets the field at the given position to the given value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the given position is out of map bounds.
+    fn set(&mut self, point: Vector2u, value: T) -> Result<(), TisuError>;
+}
+
+impl<T> MapStorage<T> for Map<T> {
+    fn size(&self) -> Vector2u {
+        Map::size(self)
+    }
+
+    fn get(&self, point: Vector2u) -> Result<&T, TisuError> {
+        Map::get(self, point)
+    }
+
+    fn set(&mut self, point: Vector2u, value: T) -> Result<(), TisuError> {
+        Map::set(self, point, value)
+    }
+}
@@ -1,4 +1,8 @@
-use crate::{map::Map, tisu_error::TisuError, vector2::Vector2u};
+use crate::{
+    map::{FlipAxis, Map},
+    tisu_error::TisuError,
+    vector2::Vector2u,
+};
 
 #[test]
 fn test_constructor() {
@@ -76,6 +80,17 @@ fn test_set_failure() {
     assert_eq!(result.err().unwrap(), TisuError::OutOfBounds);
 }
 
+#[test]
+fn test_field_index() {
+    let map = Map::from_data([[1, 2, 3], [4, 5, 6]]).unwrap();
+
+    let idx = map.field_index((2, 1).into());
+
+    assert_eq!(idx, 5);
+    assert_eq!(map.data()[idx], 6);
+    assert_eq!(map.field_position(idx), (2, 1).into());
+}
+
 #[test]
 fn test_map() {
     let mut map = Map::<i32>::new((2, 2).into());
@@ -120,3 +135,240 @@ fn test_extract_segment_failure() {
 
     assert_eq!(result.err().unwrap(), TisuError::InvalidArgument);
 }
+
+#[test]
+fn test_to_highlighted_string_success() {
+    let map = Map::<i32>::from_data([[1, 10], [3, 4]]).unwrap();
+    let reference = Map::<i32>::from_data([[1, 10], [3, 5]]).unwrap();
+
+    let result = map.to_highlighted_string(&reference);
+
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), " 1 10\n 3 \x1b[1;33m 4\x1b[0m\n");
+}
+
+#[test]
+fn test_to_highlighted_string_failure() {
+    let map = Map::<i32>::new((2, 2).into());
+    let reference = Map::<i32>::new((3, 2).into());
+
+    let result = map.to_highlighted_string(&reference);
+
+    assert_eq!(result.err().unwrap(), TisuError::InvalidMapSize);
+}
+
+#[test]
+fn test_try_from_rows_success() {
+    let result = Map::<i32>::try_from(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+
+    assert!(result.is_ok());
+    let map = result.unwrap();
+    assert_eq!(map.size(), (3, 2).into());
+    assert_eq!(map.data(), [1, 2, 3, 4, 5, 6]);
+}
+
+#[test]
+fn test_try_from_rows_failure() {
+    let result = Map::<i32>::try_from(vec![vec![1, 2, 3], vec![4, 5]]);
+    assert_eq!(result.err().unwrap(), TisuError::InvalidArgument);
+}
+
+#[test]
+fn test_try_from_empty_rows() {
+    let empty = Map::<i32>::new((0, 0).into());
+
+    assert_eq!(Map::<i32>::try_from(vec![]), Ok(empty.clone()));
+    assert_eq!(
+        Map::<i32>::try_from(Vec::<Vec<i32>>::from(empty.clone())),
+        Ok(empty)
+    );
+    assert_eq!(
+        Map::<i32>::try_from(vec![vec![], vec![]]).map(|map| map.size()),
+        Ok((0, 2).into())
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_empty_map() {
+    let map = Map::<i32>::new((0, 0).into());
+
+    let json = serde_json::to_string(&map).unwrap();
+
+    assert_eq!(json, "[]");
+    assert_eq!(serde_json::from_str::<Map<i32>>(&json).unwrap(), map);
+}
+
+#[test]
+fn test_into_rows() {
+    let map = Map::<i32>::from_data([[1, 2, 3], [4, 5, 6]]).unwrap();
+
+    let rows = Vec::<Vec<i32>>::from(map);
+
+    assert_eq!(rows, [[1, 2, 3], [4, 5, 6]]);
+}
+
+fn create_map() -> Map<i32> {
+    Map::from_data([[1, 2, 3], [4, 5, 6]]).unwrap()
+}
+
+#[test]
+fn test_filled() {
+    let map = Map::filled((2, 3).into(), 7);
+
+    assert_eq!(map.size(), (2, 3).into());
+    assert!(map.data().iter().all(|field| *field == 7));
+}
+
+#[test]
+fn test_crop_success() {
+    let result = create_map().crop((1, 0, 2, 2).try_into().unwrap());
+
+    assert_eq!(result, Ok(Map::from_data([[2, 3], [5, 6]]).unwrap()));
+}
+
+#[test]
+fn test_crop_failure() {
+    let result = create_map().crop((2, 1, 2, 1).try_into().unwrap());
+
+    assert_eq!(result.err().unwrap(), TisuError::InvalidArgument);
+}
+
+#[test]
+fn test_pad() {
+    let result = create_map().pad((1, 0).into(), (0, 1).into(), 0);
+
+    assert_eq!(
+        result,
+        Map::from_data([[0, 1, 2, 3], [0, 4, 5, 6], [0, 0, 0, 0]])
+    );
+    assert_eq!(
+        create_map().pad((u32::MAX, 0).into(), (0, 0).into(), 0),
+        Err(TisuError::InvalidArgument)
+    );
+}
+
+#[test]
+fn test_resize() {
+    let map = create_map();
+
+    assert_eq!(
+        map.resize((2, 3).into(), 0),
+        Map::from_data([[1, 2], [4, 5], [0, 0]]).unwrap()
+    );
+    assert_eq!(
+        map.resize((4, 1).into(), 9),
+        Map::from_data([[1, 2, 3, 9]]).unwrap()
+    );
+}
+
+#[test]
+fn test_rotate90() {
+    let map = create_map();
+
+    assert_eq!(
+        map.rotate90(1),
+        Map::from_data([[4, 1], [5, 2], [6, 3]]).unwrap()
+    );
+    assert_eq!(
+        map.rotate90(2),
+        Map::from_data([[6, 5, 4], [3, 2, 1]]).unwrap()
+    );
+    assert_eq!(
+        map.rotate90(3),
+        Map::from_data([[3, 6], [2, 5], [1, 4]]).unwrap()
+    );
+    assert_eq!(map.rotate90(4), map);
+    assert_eq!(map.rotate90(1).rotate90(3), map);
+}
+
+#[test]
+fn test_flip() {
+    let map = create_map();
+
+    assert_eq!(
+        map.flip(FlipAxis::Horizontal),
+        Map::from_data([[3, 2, 1], [6, 5, 4]]).unwrap()
+    );
+    assert_eq!(
+        map.flip(FlipAxis::Vertical),
+        Map::from_data([[4, 5, 6], [1, 2, 3]]).unwrap()
+    );
+}
+
+#[test]
+fn test_paste_success() {
+    let mut map = create_map();
+    let stamp = Map::from_data([[7, 0], [0, 6]]).unwrap();
+
+    let result = map.paste(&stamp, (1, 0).into(), Some(&0));
+
+    // The 0s are transparent and the 6 is unchanged
+    assert_eq!(result, Ok(1));
+    assert_eq!(map, Map::from_data([[1, 7, 3], [4, 5, 6]]).unwrap());
+
+    let result = map.paste(&stamp, (0, 0).into(), None);
+
+    assert_eq!(result, Ok(4));
+    assert_eq!(map, Map::from_data([[7, 0, 3], [0, 6, 6]]).unwrap());
+}
+
+#[test]
+fn test_paste_failure() {
+    let mut map = create_map();
+    let stamp = Map::from_data([[7, 8]]).unwrap();
+
+    let result = map.paste(&stamp, (2, 0).into(), None);
+
+    assert_eq!(result.err().unwrap(), TisuError::InvalidArgument);
+    assert_eq!(map, create_map());
+}
+
+#[test]
+fn test_tile() {
+    let map = Map::from_data([[1, 2]]).unwrap();
+
+    assert_eq!(
+        map.tile((2, 2).into()),
+        Map::from_data([[1, 2, 1, 2], [1, 2, 1, 2]])
+    );
+    assert_eq!(
+        map.tile((u32::MAX, 1).into()),
+        Err(TisuError::InvalidArgument)
+    );
+}
+
+#[test]
+fn test_rows_and_columns() {
+    let mut map = create_map();
+
+    assert_eq!(map.rows().collect::<Vec<_>>(), [[1, 2, 3], [4, 5, 6]]);
+    assert_eq!(
+        map.columns()
+            .map(|column| column.copied().collect::<Vec<_>>())
+            .collect::<Vec<_>>(),
+        [[1, 4], [2, 5], [3, 6]]
+    );
+
+    for row in map.rows_mut() {
+        row.reverse();
+    }
+    assert_eq!(map, Map::from_data([[3, 2, 1], [6, 5, 4]]).unwrap());
+}
+
+#[test]
+fn test_index() {
+    let mut map = create_map();
+
+    assert_eq!(map[Vector2u::new(2, 1)], 6);
+    map[Vector2u::new(0, 1)] = 9;
+    assert_eq!(map.get((0, 1).into()), Ok(&9));
+}
+
+#[test]
+#[should_panic]
+fn test_index_out_of_bounds() {
+    let map = create_map();
+
+    _ = map[Vector2u::new(3, 0)];
+}
@@ -0,0 +1,261 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    filter::{
+        ApplyMode, FilterCollection, FilterProperties, MatchFlips, PatternMatching, SubstituteFlips,
+    },
+    filter_loader,
+    tiled_tile::TiledTile,
+    tisu_error::TisuError,
+};
+
+/// Filter properties that override the properties loaded from a filter file.
+/// Properties that aren't set are left unchanged.
+#[derive(Clone, PartialEq, Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PropertyOverrides {
+    pub probability: Option<f32>,
+    pub pattern_matching: Option<PatternMatching>,
+    pub iterations: Option<u32>,
+    pub ignore: Option<bool>,
+    pub region: Option<String>,
+    pub match_flips: Option<MatchFlips>,
+    pub substitute_flips: Option<SubstituteFlips>,
+    pub mode: Option<ApplyMode>,
+    pub max_steps: Option<u32>,
+    pub when: Option<String>,
+    pub until: Option<String>,
+    pub max_runs: Option<u32>,
+    pub sentinel: Option<bool>,
+}
+
+impl PropertyOverrides {
+    /// Applies the overrides to the given filter properties.
+    pub fn apply(&self, properties: &mut FilterProperties) {
+        if let Some(probability) = self.probability {
+            properties.probability = probability;
+        }
+        if let Some(pattern_matching) = &self.pattern_matching {
+            properties.pattern_matching = pattern_matching.clone();
+        }
+        if let Some(iterations) = self.iterations {
+            properties.iterations = iterations;
+        }
+        if let Some(ignore) = self.ignore {
+            properties.ignore = ignore;
+        }
+        if let Some(region) = &self.region {
+            properties.region = Some(region.clone());
+        }
+        if let Some(match_flips) = &self.match_flips {
+            properties.match_flips = match_flips.clone();
+        }
+        if let Some(substitute_flips) = &self.substitute_flips {
+            properties.substitute_flips = substitute_flips.clone();
+        }
+        if let Some(mode) = &self.mode {
+            properties.mode = mode.clone();
+        }
+        if let Some(max_steps) = self.max_steps {
+            properties.max_steps = Some(max_steps);
+        }
+        if let Some(when) = &self.when {
+            properties.when = Some(when.clone());
+        }
+        if let Some(until) = &self.until {
+            properties.until = Some(until.clone());
+        }
+        if let Some(max_runs) = self.max_runs {
+            properties.max_runs = Some(max_runs);
+        }
+        if let Some(sentinel) = self.sentinel {
+            properties.sentinel = sentinel;
+        }
+    }
+}
+
+/// A pipeline step, which loads the filter collections of a filter file.
+#[derive(Clone, PartialEq, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineStep {
+    /// Path of the filter file (relative to the manifest)
+    pub filters: PathBuf,
+    /// Names of the filter collections (or group layers) to use. All filter
+    /// collections are used if empty.
+    #[serde(default)]
+    pub layers: Vec<String>,
+    /// Tags enabling the step. Steps without tags are always enabled.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Overrides for the properties of the filter collections and their
+    /// filters
+    #[serde(default)]
+    pub properties: PropertyOverrides,
+}
+
+impl PipelineStep {
+    /// Checks if the step is enabled by the given tags.
+    pub fn is_enabled(&self, tags: &[String]) -> bool {
+        self.tags.is_empty() || self.tags.iter().any(|tag| tags.contains(tag))
+    }
+
+    /// Checks if the filter collection with the given name is selected by the
+    /// step. Names of filter collections within group layers are prefixed by
+    /// the group names (e.g. `Group/Layer`), so selecting a group selects all
+    /// of its filter collections.
+    fn selects(&self, name: &str) -> bool {
+        self.layers.is_empty()
+            || self.layers.iter().any(|layer| {
+                name == layer
+                    || name
+                        .strip_prefix(layer.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+    }
+
+    /// Loads the filter collections of the step.
+    fn load_filters(
+        &self,
+        directory: &Path,
+    ) -> Result<Vec<FilterCollection<TiledTile>>, TisuError> {
+        let mut filter_collections = filter_loader::load_filters(directory.join(&self.filters))?;
+        filter_collections.retain(|filter_collection| self.selects(&filter_collection.name));
+        for filter_collection in &mut filter_collections {
+            self.properties.apply(&mut filter_collection.properties);
+            for filter in &mut filter_collection.filters {
+                let mut properties = filter.properties().clone();
+                self.properties.apply(&mut properties);
+                filter.set_properties(properties);
+            }
+        }
+        Ok(filter_collections)
+    }
+}
+
+/// A pipeline manifest, listing the filter files to apply in order:
+///
+/// ```toml
+/// [[steps]]
+/// filters = "terrain.tmx"
+///
+/// [[steps]]
+/// filters = "decoration.txt"
+/// layers = ["Plants", "Rocks"]
+/// tags = ["decorate"]
+/// properties = { probability = 0.25 }
+/// ```
+#[derive(Clone, PartialEq, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pipeline {
+    pub steps: Vec<PipelineStep>,
+    /// Directory that filter file paths are relative to
+    #[serde(skip)]
+    pub directory: PathBuf,
+}
+
+impl Pipeline {
+    /// Loads a pipeline manifest from the given TOML file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or parsed.
+    pub fn load(file: impl AsRef<Path>) -> Result<Self, TisuError> {
+        let text = std::fs::read_to_string(&file).map_err(|_| TisuError::InvalidArgument)?;
+        let mut pipeline: Pipeline =
+            toml::from_str(&text).map_err(|_| TisuError::InvalidArgument)?;
+        pipeline.directory = file
+            .as_ref()
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        Ok(pipeline)
+    }
+
+    /// Loads the filter collections of all the steps enabled by the given
+    /// tags, in order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the filter file of a step can't be loaded.
+    pub fn load_filters(
+        &self,
+        tags: &[String],
+    ) -> Result<Vec<FilterCollection<TiledTile>>, TisuError> {
+        let mut result = vec![];
+        for step in self.steps.iter().filter(|step| step.is_enabled(tags)) {
+            result.extend(step.load_filters(&self.directory)?);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_pipeline() -> Pipeline {
+        Pipeline::load(format!(
+            "{}/data/test_include/pipeline.toml",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_selects() {
+        let step = PipelineStep {
+            filters: PathBuf::new(),
+            layers: vec![String::from("Group")],
+            tags: vec![],
+            properties: PropertyOverrides::default(),
+        };
+
+        assert!(step.selects("Group"));
+        assert!(step.selects("Group/Layer"));
+        assert!(!step.selects("Group 2/Layer"));
+        assert!(!step.selects("Layer"));
+    }
+
+    #[test]
+    fn test_load() {
+        let pipeline = load_pipeline();
+
+        assert_eq!(pipeline.steps.len(), 2);
+        assert_eq!(pipeline.steps[1].tags, ["extra"]);
+        assert_eq!(pipeline.steps[1].properties.probability, Some(0.5));
+    }
+
+    #[test]
+    fn test_load_filters() {
+        let pipeline = load_pipeline();
+
+        let result = pipeline.load_filters(&[]);
+
+        assert!(result.is_ok());
+        let filter_collections = result.unwrap();
+        assert_eq!(filter_collections.len(), 1);
+        assert_eq!(filter_collections[0].name, "Layer 1");
+        assert_eq!(filter_collections[0].properties.iterations, 2);
+        assert_eq!(filter_collections[0].filters[0].properties().iterations, 2);
+    }
+
+    #[test]
+    fn test_load_filters_tags() {
+        let pipeline = load_pipeline();
+
+        let result = pipeline.load_filters(&[String::from("extra")]);
+
+        assert!(result.is_ok());
+        let filter_collections = result.unwrap();
+        let names: Vec<_> = filter_collections
+            .iter()
+            .map(|filter_collection| filter_collection.name.as_str())
+            .collect();
+        assert_eq!(names, ["Layer 1", "Base", "Layer 1", "Main"]);
+        assert_eq!(filter_collections[3].properties.probability, 0.5);
+        assert_eq!(
+            filter_collections[3].filters[0].properties().probability,
+            0.5
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use crate::map::Map;
+use crate::tisu_error::TisuError;
+use crate::vector2::Vector2u;
+
+/// Creates a mask of the given size, where the fields whose centers lie inside
+/// the given polygon are set. Polygon points are given in fields (e.g. `(1.5,
+/// 2.0)` is the middle of the top edge of field `(1, 2)`).
+pub fn from_polygon(size: Vector2u, points: &[(f32, f32)]) -> Map<bool> {
+    let mut mask = Map::new(size);
+    for x in 0..size.x {
+        for y in 0..size.y {
+            if polygon_contains(points, (x as f32 + 0.5, y as f32 + 0.5)) {
+                _ = mask.set((x, y).into(), true);
+            }
+        }
+    }
+    mask
+}
+
+/// Creates a mask of the size of the given map, where the fields that differ
+/// from the given empty value are set.
+pub fn from_fields<T: PartialEq>(map: &Map<T>, empty: &T) -> Map<bool> {
+    map.map(|field| field != empty)
+}
+
+/// Adds the fields set in the given mask to the target mask.
+///
+/// # Errors
+///
+/// Returns an error if the masks have different sizes.
+pub fn union(target: &mut Map<bool>, mask: &Map<bool>) -> Result<(), TisuError> {
+    if target.size() != mask.size() {
+        return Err(TisuError::InvalidMapSize);
+    }
+    for (target_field, field) in target.mut_data().iter_mut().zip(mask.data()) {
+        *target_field |= *field;
+    }
+    Ok(())
+}
+
+/// Prefix of a region name that selects the fields outside of the named region
+/// (e.g. `!Spawn`)
+pub const NEGATION_PREFIX: char = '!';
+
+/// Returns the mask of the region with the given name from the given regions.
+/// Names starting with `!` select the fields outside of the named region.
+///
+/// # Errors
+///
+/// Returns an error if there is no region with the given name.
+pub fn resolve(regions: &HashMap<String, Map<bool>>, name: &str) -> Result<Map<bool>, TisuError> {
+    match name.strip_prefix(NEGATION_PREFIX) {
+        Some(name) => Ok(regions
+            .get(name)
+            .ok_or(TisuError::NotFound)?
+            .map(|field| !field)),
+        None => regions.get(name).cloned().ok_or(TisuError::NotFound),
+    }
+}
+
+/// Checks if the given point lies inside the given polygon (using the even-odd
+/// rule).
+fn polygon_contains(points: &[(f32, f32)], point: (f32, f32)) -> bool {
+    let mut inside = false;
+    for (idx, a) in points.iter().enumerate() {
+        let b = points[(idx + 1) % points.len()];
+        if (a.1 > point.1) != (b.1 > point.1)
+            && point.0 < a.0 + (point.1 - a.1) * (b.0 - a.0) / (b.1 - a.1)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use crate::{map::Map, region_mask, tisu_error::TisuError};
+
+#[test]
+fn test_from_polygon() {
+    // Triangle covering the centers of the fields on and below the diagonal
+    let result = region_mask::from_polygon((3, 3).into(), &[(0.0, 0.0), (3.0, 3.0), (0.0, 3.0)]);
+
+    assert_eq!(
+        result,
+        Map::from_data([
+            [false, false, false],
+            [true, false, false],
+            [true, true, false],
+        ])
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_from_polygon_rect() {
+    let result = region_mask::from_polygon(
+        (4, 3).into(),
+        &[(1.0, 0.5), (3.0, 0.5), (3.0, 2.0), (1.0, 2.0)],
+    );
+
+    assert_eq!(
+        result,
+        Map::from_data([
+            [false, true, true, false],
+            [false, true, true, false],
+            [false, false, false, false],
+        ])
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_from_fields() {
+    let map = Map::<u32>::from_data([[0, 1], [2, 0]]).unwrap();
+
+    let result = region_mask::from_fields(&map, &0);
+
+    assert_eq!(
+        result,
+        Map::from_data([[false, true], [true, false]]).unwrap()
+    );
+}
+
+#[test]
+fn test_union() {
+    let mut target = Map::from_data([[true, false, false]]).unwrap();
+    let mask = Map::from_data([[false, false, true]]).unwrap();
+
+    let result = region_mask::union(&mut target, &mask);
+
+    assert!(result.is_ok());
+    assert_eq!(target, Map::from_data([[true, false, true]]).unwrap());
+}
+
+#[test]
+fn test_union_failure() {
+    let mut target = Map::<bool>::new((2, 2).into());
+
+    let result = region_mask::union(&mut target, &Map::new((3, 2).into()));
+
+    assert_eq!(result.err().unwrap(), TisuError::InvalidMapSize);
+}
+
+#[test]
+fn test_resolve() {
+    let spawn = Map::from_data([[true, false], [false, false]]).unwrap();
+    let regions = HashMap::from([(String::from("Spawn"), spawn.clone())]);
+
+    assert_eq!(region_mask::resolve(&regions, "Spawn"), Ok(spawn));
+    assert_eq!(
+        region_mask::resolve(&regions, "!Spawn"),
+        Map::from_data([[false, true], [true, true]])
+    );
+    assert_eq!(
+        region_mask::resolve(&regions, "!Exit"),
+        Err(TisuError::NotFound)
+    );
+}
@@ -0,0 +1,383 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use rand::Rng;
+
+use crate::apply_stats::FilterStats;
+use crate::map::Map;
+use crate::rect2::Rect2u;
+use crate::tisu_error::TisuError;
+use crate::vector2::{Vector2, Vector2u};
+
+/// Neighborhood that defines which fields are connected
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "i32", try_from = "i32")
+)]
+pub enum Neighborhood {
+    /// Fields are connected to the fields next to them horizontally and
+    /// vertically
+    #[default]
+    Four,
+    /// Fields are also connected to the fields next to them diagonally
+    Eight,
+}
+
+impl TryFrom<i32> for Neighborhood {
+    type Error = TisuError;
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            4 => Ok(Neighborhood::Four),
+            8 => Ok(Neighborhood::Eight),
+            _ => Err(TisuError::InvalidArgument),
+        }
+    }
+}
+
+impl From<Neighborhood> for i32 {
+    fn from(value: Neighborhood) -> Self {
+        match value {
+            Neighborhood::Four => 4,
+            Neighborhood::Eight => 8,
+        }
+    }
+}
+
+impl Neighborhood {
+    /// Returns the neighbors of the given point that lie inside a map of the
+    /// given size.
+    pub(crate) fn neighbors(
+        self,
+        point: Vector2u,
+        size: Vector2u,
+    ) -> impl Iterator<Item = Vector2u> {
+        const OFFSETS: [(i64, i64); 8] = [
+            (-1, 0),
+            (1, 0),
+            (0, -1),
+            (0, 1),
+            (-1, -1),
+            (1, -1),
+            (-1, 1),
+            (1, 1),
+        ];
+        let count = match self {
+            Neighborhood::Four => 4,
+            Neighborhood::Eight => 8,
+        };
+        OFFSETS[..count].iter().filter_map(move |(dx, dy)| {
+            let x = u32::try_from(point.x as i64 + dx).ok()?;
+            let y = u32::try_from(point.y as i64 + dy).ok()?;
+            (x < size.x && y < size.y).then(|| Vector2::new(x, y))
+        })
+    }
+}
+
+/// A connected region of a map
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Region {
+    /// Number of fields of the region
+    pub size: usize,
+    /// Smallest rectangle containing all the fields of the region
+    pub bounding_box: Rect2u,
+}
+
+/// Connected regions of a map, with the label of each field. Labels are
+/// indices into the regions, which are ordered by their first field (row by
+/// row).
+#[derive(Clone, PartialEq, Debug)]
+pub struct Labels {
+    /// Label of each field, `None` for fields that don't belong to a region
+    pub labels: Map<Option<usize>>,
+    /// Regions, by label
+    pub regions: Vec<Region>,
+}
+
+impl Labels {
+    /// Returns a mask where the fields of the region with the given label are
+    /// set.
+    pub fn mask(&self, label: usize) -> Map<bool> {
+        self.labels.map(|field| *field == Some(label))
+    }
+
+    /// Returns the label of the largest region (the first one if several
+    /// regions have the same size).
+    pub fn largest(&self) -> Option<usize> {
+        (0..self.regions.len()).reduce(|largest, label| {
+            if self.regions[label].size > self.regions[largest].size {
+                label
+            } else {
+                largest
+            }
+        })
+    }
+}
+
+/// Labels the connected regions of the fields that satisfy the given
+/// predicate.
+pub fn label<T, P>(map: &Map<T>, neighborhood: Neighborhood, predicate: P) -> Labels
+where
+    P: Fn(&T) -> bool,
+{
+    label_with(map, neighborhood, predicate, |_, _| true)
+}
+
+/// Labels the connected regions of fields with equal tiles. Every field
+/// belongs to a region.
+pub fn label_by_tile<T>(map: &Map<T>, neighborhood: Neighborhood) -> Labels
+where
+    T: PartialEq,
+{
+    label_with(map, neighborhood, |_| true, |a, b| a == b)
+}
+
+/// Labels the connected regions of the fields that satisfy the given
+/// predicate, where neighboring fields are connected if `connected` is true
+/// for their tiles.
+fn label_with<T, P, C>(
+    map: &Map<T>,
+    neighborhood: Neighborhood,
+    predicate: P,
+    connected: C,
+) -> Labels
+where
+    P: Fn(&T) -> bool,
+    C: Fn(&T, &T) -> bool,
+{
+    let size = map.size();
+    let mut labels = Map::<Option<usize>>::new(size);
+    let mut regions = vec![];
+    let mut queue = VecDeque::new();
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let start = Vector2::new(x, y);
+            let idx = map.field_index(start);
+            if labels.data()[idx].is_some() || !predicate(&map.data()[idx]) {
+                continue;
+            }
+
+            let label = regions.len();
+            let (mut min, mut max) = (start, start);
+            let mut region_size = 0;
+            labels.mut_data()[idx] = Some(label);
+            queue.push_back(start);
+            while let Some(point) = queue.pop_front() {
+                region_size += 1;
+                min = Vector2::new(min.x.min(point.x), min.y.min(point.y));
+                max = Vector2::new(max.x.max(point.x), max.y.max(point.y));
+                let tile = &map.data()[map.field_index(point)];
+                for neighbor in neighborhood.neighbors(point, size) {
+                    let neighbor_idx = map.field_index(neighbor);
+                    let neighbor_tile = &map.data()[neighbor_idx];
+                    if labels.data()[neighbor_idx].is_none()
+                        && predicate(neighbor_tile)
+                        && connected(tile, neighbor_tile)
+                    {
+                        labels.mut_data()[neighbor_idx] = Some(label);
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+            regions.push(Region {
+                size: region_size,
+                bounding_box: Rect2u::new(min, max - min + Vector2::one())
+                    .expect("region size is positive"),
+            });
+        }
+    }
+    Labels { labels, regions }
+}
+
+/// Replaces the tiles of the connected region of fields equal to the field at
+/// the given start point with the given value. Returns the number of changed
+/// fields.
+///
+/// # Errors
+///
+/// Returns an error if the start point is outside the map.
+pub fn flood_fill<T>(
+    map: &mut Map<T>,
+    start: Vector2u,
+    neighborhood: Neighborhood,
+    value: T,
+) -> Result<usize, TisuError>
+where
+    T: Clone + PartialEq,
+{
+    let target = map.get(start)?.clone();
+    if target == value {
+        return Ok(0);
+    }
+
+    let size = map.size();
+    let mut changes = 0;
+    let mut queue = VecDeque::from([start]);
+    map.set(start, value.clone())?;
+    while let Some(point) = queue.pop_front() {
+        changes += 1;
+        for neighbor in neighborhood.neighbors(point, size) {
+            let idx = map.field_index(neighbor);
+            let field = &mut map.mut_data()[idx];
+            if *field == target {
+                *field = value.clone();
+                queue.push_back(neighbor);
+            }
+        }
+    }
+    Ok(changes)
+}
+
+/// Operation done by a `RegionOp` on the regions it selects
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum RegionOpKind {
+    /// Replaces every region
+    #[default]
+    Replace,
+    /// Replaces every region except the largest one
+    KeepLargest,
+}
+
+impl TryFrom<&String> for RegionOpKind {
+    type Error = ();
+    fn try_from(value: &String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "replace" => Ok(RegionOpKind::Replace),
+            "keep_largest" => Ok(RegionOpKind::KeepLargest),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<&RegionOpKind> for String {
+    fn from(value: &RegionOpKind) -> Self {
+        match value {
+            RegionOpKind::Replace => String::from("replace"),
+            RegionOpKind::KeepLargest => String::from("keep_largest"),
+        }
+    }
+}
+
+/// An operation on the connected regions of a map, applied as a step of a
+/// filter sequence in place of filters (see `FilterCollection::region_op`).
+/// Regions are made of connected fields with any of the given tiles. Each
+/// selected region is replaced by one of the replacement tiles, chosen at
+/// random for the whole region (a tile listed several times is chosen more
+/// often). For example, replacing floor regions gives each room its own
+/// random floor type, and replacing floor regions with walls up to a maximum
+/// size removes small disconnected islands.
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegionOp<T> {
+    /// Operation done on the selected regions
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub kind: RegionOpKind,
+    /// Neighborhood that defines which fields are connected
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub neighborhood: Neighborhood,
+    /// Tiles of the fields that make up the regions
+    pub tiles: Vec<T>,
+    /// Tiles that replace the selected regions
+    pub replacements: Vec<T>,
+    /// Only regions with at least this many fields are selected
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub min_size: Option<usize>,
+    /// Only regions with at most this many fields are selected
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub max_size: Option<usize>,
+}
+
+impl<T> RegionOp<T> {
+    /// Creates a region operation over the regions of the given tiles, with a
+    /// 4-neighborhood and no size limits.
+    pub fn new(kind: RegionOpKind, tiles: Vec<T>, replacements: Vec<T>) -> Self {
+        Self {
+            kind,
+            neighborhood: Neighborhood::Four,
+            tiles,
+            replacements,
+            min_size: None,
+            max_size: None,
+        }
+    }
+
+    /// Finds the regions of the source map and replaces the selected ones in
+    /// the destination map. If a mask is given, only the fields set in it
+    /// belong to regions. Returns statistics where every field counts as a
+    /// tested position and every selected region as a match.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the maps (or the mask) have different sizes or if
+    /// there are no replacement tiles.
+    pub fn apply_with_rng<R>(
+        &self,
+        source: &Map<T>,
+        destination: &mut Map<T>,
+        mask: Option<&Map<bool>>,
+        rng: &mut R,
+    ) -> Result<FilterStats, TisuError>
+    where
+        T: Clone + PartialEq,
+        R: Rng + ?Sized,
+    {
+        if source.size() != destination.size()
+            || mask.is_some_and(|mask| mask.size() != source.size())
+        {
+            return Err(TisuError::InvalidMapSize);
+        }
+        if self.replacements.is_empty() {
+            return Err(TisuError::InvalidArgument);
+        }
+
+        let start = Instant::now();
+        let mut candidates = source.map(|field| self.tiles.contains(field));
+        if let Some(mask) = mask {
+            for (candidate, in_mask) in candidates.mut_data().iter_mut().zip(mask.data()) {
+                *candidate &= *in_mask;
+            }
+        }
+        let labels = label(&candidates, self.neighborhood, |field| *field);
+        let largest = labels.largest();
+
+        let mut replacements = vec![];
+        for (idx, region) in labels.regions.iter().enumerate() {
+            let selected = match self.kind {
+                RegionOpKind::Replace => true,
+                RegionOpKind::KeepLargest => Some(idx) != largest,
+            } && self.min_size.is_none_or(|min_size| region.size >= min_size)
+                && self.max_size.is_none_or(|max_size| region.size <= max_size);
+            replacements.push(
+                selected.then(|| &self.replacements[rng.random_range(0..self.replacements.len())]),
+            );
+        }
+
+        let mut stats = FilterStats {
+            positions_tested: source.data().len(),
+            matches: replacements.iter().flatten().count(),
+            ..Default::default()
+        };
+        for (field, label) in destination.mut_data().iter_mut().zip(labels.labels.data()) {
+            if let Some(replacement) = label.and_then(|label| replacements[label]) {
+                if field != replacement {
+                    *field = replacement.clone();
+                    stats.changes += 1;
+                }
+            }
+        }
+        stats.duration = start.elapsed();
+        Ok(stats)
+    }
+}
@@ -0,0 +1,296 @@
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    filter::{FilterCollection, FilterProperties, PatternMatching},
+    map::Map,
+    regions::{self, Neighborhood, Region, RegionOp, RegionOpKind},
+    tisu_error::TisuError,
+    vector2::Vector2u,
+};
+
+fn create_map() -> Map<u32> {
+    Map::from_data([
+        [1, 1, 0, 0, 1],
+        [1, 0, 0, 1, 0],
+        [0, 0, 0, 0, 0],
+        [0, 1, 1, 1, 0],
+    ])
+    .unwrap()
+}
+
+#[test]
+fn test_neighborhood_conversion() {
+    assert_eq!(Neighborhood::try_from(4), Ok(Neighborhood::Four));
+    assert_eq!(Neighborhood::try_from(8), Ok(Neighborhood::Eight));
+    assert_eq!(Neighborhood::try_from(6), Err(TisuError::InvalidArgument));
+    assert_eq!(i32::from(Neighborhood::Eight), 8);
+}
+
+#[test]
+fn test_label() {
+    let result = regions::label(&create_map(), Neighborhood::Four, |field| *field == 1);
+
+    assert_eq!(
+        result.labels,
+        Map::from_data([
+            [Some(0), Some(0), None, None, Some(1)],
+            [Some(0), None, None, Some(2), None],
+            [None, None, None, None, None],
+            [None, Some(3), Some(3), Some(3), None],
+        ])
+        .unwrap()
+    );
+    assert_eq!(
+        result.regions,
+        [
+            Region {
+                size: 3,
+                bounding_box: (0, 0, 2, 2).try_into().unwrap(),
+            },
+            Region {
+                size: 1,
+                bounding_box: (4, 0, 1, 1).try_into().unwrap(),
+            },
+            Region {
+                size: 1,
+                bounding_box: (3, 1, 1, 1).try_into().unwrap(),
+            },
+            Region {
+                size: 3,
+                bounding_box: (1, 3, 3, 1).try_into().unwrap(),
+            },
+        ]
+    );
+    assert_eq!(result.largest(), Some(0));
+}
+
+#[test]
+fn test_label_eight_neighborhood() {
+    let result = regions::label(&create_map(), Neighborhood::Eight, |field| *field == 1);
+
+    // The diagonal fields in the top right corner are connected
+    assert_eq!(result.regions.len(), 3);
+    assert_eq!(result.regions[1].size, 2);
+    assert_eq!(
+        result.regions[1].bounding_box,
+        (3, 0, 2, 2).try_into().unwrap()
+    );
+    assert_eq!(
+        result.mask(1),
+        Map::from_data([
+            [false, false, false, false, true],
+            [false, false, false, true, false],
+            [false, false, false, false, false],
+            [false, false, false, false, false],
+        ])
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_label_by_tile() {
+    let map = Map::from_data([[1, 1, 2], [3, 1, 2]]).unwrap();
+
+    let result = regions::label_by_tile(&map, Neighborhood::Four);
+
+    assert_eq!(
+        result.labels,
+        Map::from_data([[Some(0), Some(0), Some(1)], [Some(2), Some(0), Some(1)]]).unwrap()
+    );
+    assert_eq!(
+        result
+            .regions
+            .iter()
+            .map(|region| region.size)
+            .collect::<Vec<_>>(),
+        [3, 2, 1]
+    );
+}
+
+#[test]
+fn test_flood_fill() {
+    let mut map = create_map();
+
+    let result = regions::flood_fill(&mut map, (2, 2).into(), Neighborhood::Four, 5);
+
+    assert_eq!(result, Ok(12));
+    assert_eq!(
+        map,
+        Map::from_data([
+            [1, 1, 5, 5, 1],
+            [1, 5, 5, 1, 5],
+            [5, 5, 5, 5, 5],
+            [5, 1, 1, 1, 5],
+        ])
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_flood_fill_same_value() {
+    let mut map = create_map();
+
+    let result = regions::flood_fill(&mut map, (0, 0).into(), Neighborhood::Eight, 1);
+
+    assert_eq!(result, Ok(0));
+    assert_eq!(map, create_map());
+}
+
+#[test]
+fn test_flood_fill_failure() {
+    let mut map = create_map();
+
+    let result = regions::flood_fill(&mut map, Vector2u::new(5, 0), Neighborhood::Four, 5);
+
+    assert_eq!(result, Err(TisuError::OutOfBounds));
+}
+
+#[test]
+fn test_region_op_replace() {
+    let map = create_map();
+    let mut output = map.clone();
+    let region_op = RegionOp {
+        max_size: Some(2),
+        ..RegionOp::new(RegionOpKind::Replace, vec![1], vec![0])
+    };
+
+    let result = region_op.apply_with_rng(&map, &mut output, None, &mut StdRng::seed_from_u64(0));
+
+    // The single fields are removed
+    let stats = result.unwrap();
+    assert_eq!(stats.positions_tested, 20);
+    assert_eq!(stats.matches, 2);
+    assert_eq!(stats.changes, 2);
+    assert_eq!(
+        output,
+        Map::from_data([
+            [1, 1, 0, 0, 0],
+            [1, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0],
+            [0, 1, 1, 1, 0],
+        ])
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_region_op_replace_random() {
+    let map = create_map();
+    let region_op = RegionOp::new(RegionOpKind::Replace, vec![1], vec![6, 7, 8]);
+
+    for seed in 0..10 {
+        let mut output = map.clone();
+        region_op
+            .apply_with_rng(&map, &mut output, None, &mut StdRng::seed_from_u64(seed))
+            .unwrap();
+
+        // Each region is replaced by a single tile
+        let labels = regions::label(&map, Neighborhood::Four, |field| *field == 1);
+        for label in 0..labels.regions.len() {
+            let mut tiles: Vec<_> = output
+                .data()
+                .iter()
+                .zip(labels.mask(label).data())
+                .filter(|(_, in_region)| **in_region)
+                .map(|(tile, _)| *tile)
+                .collect();
+            tiles.dedup();
+            assert_eq!(tiles.len(), 1);
+            assert!([6, 7, 8].contains(&tiles[0]));
+        }
+    }
+}
+
+#[test]
+fn test_region_op_keep_largest() {
+    let map = create_map();
+    let mut output = map.clone();
+    let region_op = RegionOp::new(RegionOpKind::KeepLargest, vec![1], vec![0]);
+
+    region_op
+        .apply_with_rng(&map, &mut output, None, &mut StdRng::seed_from_u64(0))
+        .unwrap();
+
+    assert_eq!(
+        output,
+        Map::from_data([
+            [1, 1, 0, 0, 0],
+            [1, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0],
+        ])
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_region_op_with_mask() {
+    let map = create_map();
+    let mut output = map.clone();
+    let mut mask = Map::new(map.size());
+    mask.set((0, 3).into(), true).unwrap();
+    mask.set((1, 3).into(), true).unwrap();
+    let region_op = RegionOp::new(RegionOpKind::Replace, vec![1], vec![4]);
+
+    region_op
+        .apply_with_rng(
+            &map,
+            &mut output,
+            Some(&mask),
+            &mut StdRng::seed_from_u64(0),
+        )
+        .unwrap();
+
+    assert_eq!(
+        output,
+        Map::from_data([
+            [1, 1, 0, 0, 1],
+            [1, 0, 0, 1, 0],
+            [0, 0, 0, 0, 0],
+            [0, 4, 1, 1, 0],
+        ])
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_region_op_failure() {
+    let map = create_map();
+    let mut output = Map::new(Vector2u::new(2, 2));
+    let region_op = RegionOp::new(RegionOpKind::Replace, vec![1], vec![0]);
+
+    let result = region_op.apply_with_rng(&map, &mut output, None, &mut StdRng::seed_from_u64(0));
+    assert_eq!(result, Err(TisuError::InvalidMapSize));
+
+    let mut output = map.clone();
+    let region_op = RegionOp::new(RegionOpKind::Replace, vec![1], vec![]);
+
+    let result = region_op.apply_with_rng(&map, &mut output, None, &mut StdRng::seed_from_u64(0));
+    assert_eq!(result, Err(TisuError::InvalidArgument));
+}
+
+#[test]
+fn test_apply_filter_collection_region_op() {
+    let map = Map::from_data([[1, 0, 1, 1]]).unwrap();
+    let mut filter_collection = FilterCollection::<u32>::new_with_properties(
+        &[],
+        FilterProperties {
+            pattern_matching: PatternMatching::Destination,
+            iterations: 2,
+            ..Default::default()
+        },
+    );
+    filter_collection.region_op = Some(RegionOp {
+        max_size: Some(2),
+        ..RegionOp::new(RegionOpKind::Replace, vec![1], vec![2])
+    });
+    let mut output = map.clone();
+
+    let result = filter_collection.apply(&map, &mut output).unwrap();
+
+    // The second iteration finds no regions left
+    assert_eq!(output, Map::from_data([[2, 0, 2, 2]]).unwrap());
+    assert_eq!(result.filters.len(), 1);
+    assert_eq!(result.filters[0].matches, 2);
+    assert_eq!(result.changes(), 3);
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use rand::Rng;
+
+use crate::filter::{ApplyOptions, FilterCollection, MaybeSync};
+use crate::map::Map;
+use crate::region_mask;
+use crate::tisu_error::TisuError;
+
+/// Value of the `until` property that repeats a filter collection until a run
+/// changes no fields
+pub const UNTIL_UNCHANGED: &str = "unchanged";
+
+/// Maximum number of runs of a filter collection repeated with `until` when
+/// `max_runs` isn't set
+pub const DEFAULT_MAX_RUNS: u32 = 1000;
+
+/// Checks if the filter collection with the given name belongs to the filter
+/// collection or group with the given name (e.g. `Rooms/Walls` belongs to
+/// `Rooms`).
+fn belongs_to(name: &str, scope: &str) -> bool {
+    name == scope
+        || name
+            .strip_prefix(scope)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Checks if the pattern of any filter of the given (sentinel) filter
+/// collection matches the given map.
+fn sentinel_matches<T>(sentinel: &FilterCollection<T>, map: &Map<T>) -> bool
+where
+    T: PartialEq + MaybeSync,
+{
+    sentinel
+        .filters
+        .iter()
+        .filter(|filter| !filter.properties().ignore)
+        .any(|filter| !filter.find_matches(map).is_empty())
+}
+
+/// Applies the given filter collections one after another, following their
+/// control flow properties:
+/// * `when` - The filter collection is only applied if the named filter
+///   collection (or any filter collection of the named group) changed at
+///   least one field earlier in the sequence.
+/// * `until` - The filter collection is applied repeatedly until a run
+///   changes no fields (`unchanged`) or until the pattern of the named
+///   sentinel filter collection matches the destination map, at most
+///   `max_runs` times (`DEFAULT_MAX_RUNS` if not set, in which case reaching
+///   it is an error). Each filter collection is repeated on its own, so the
+///   filter collections of a group with `until` are repeated one after
+///   another rather than the group as a whole.
+/// * `sentinel` - The filter collection isn't applied. If its pattern matches
+///   the destination map, the remaining filter collections are skipped.
+///
+/// Each run of a filter collection is done by the given function, which
+/// returns the number of fields it changed (see
+/// `FilterCollectionStats::changes`).
+/// Returns the number of fields changed by the whole sequence.
+///
+/// # Errors
+///
+/// Returns an error if a filter collection can't be applied, if an `until`
+/// property names a filter collection that doesn't exist or if a filter
+/// collection without `max_runs` doesn't stop within `DEFAULT_MAX_RUNS` runs.
+pub fn apply_sequence<T, F>(
+    filter_collections: &[FilterCollection<T>],
+    destination: &mut Map<T>,
+    mut apply: F,
+) -> Result<usize, TisuError>
+where
+    T: PartialEq + MaybeSync,
+    F: FnMut(usize, &FilterCollection<T>, &mut Map<T>) -> Result<usize, TisuError>,
+{
+    let mut changes_by_name: HashMap<&str, usize> = HashMap::new();
+    let mut total_changes = 0;
+    for (idx, filter_collection) in filter_collections.iter().enumerate() {
+        let properties = &filter_collection.properties;
+        if properties.sentinel {
+            if sentinel_matches(filter_collection, destination) {
+                break;
+            }
+            continue;
+        }
+        if let Some(when) = &properties.when {
+            let changed = changes_by_name
+                .iter()
+                .any(|(name, changes)| *changes > 0 && belongs_to(name, when));
+            if !changed {
+                continue;
+            }
+        }
+
+        let sentinel = match properties.until.as_deref() {
+            None | Some(UNTIL_UNCHANGED) => None,
+            Some(name) => Some(
+                filter_collections
+                    .iter()
+                    .find(|other| other.name == name)
+                    .ok_or(TisuError::NotFound)?,
+            ),
+        };
+        let max_runs = properties.max_runs.unwrap_or(DEFAULT_MAX_RUNS);
+        let mut runs = 0;
+        loop {
+            let changes = apply(idx, filter_collection, destination)?;
+            runs += 1;
+            total_changes += changes;
+            *changes_by_name.entry(&filter_collection.name).or_default() += changes;
+
+            let done = match (&properties.until, sentinel) {
+                (None, _) => true,
+                (Some(_), Some(sentinel)) => sentinel_matches(sentinel, destination),
+                (Some(_), None) => changes == 0,
+            };
+            if done {
+                break;
+            }
+            if runs >= max_runs {
+                // Only an explicit limit is an expected way to stop
+                if properties.max_runs.is_none() {
+                    return Err(TisuError::InvalidArgument);
+                }
+                break;
+            }
+        }
+    }
+
+    Ok(total_changes)
+}
+
+/// Applies the given filter collections like `apply_sequence`, where each run
+/// is done by `FilterCollection::apply_with_rng`. Filter collections
+/// restricted to a region can't be applied (see
+/// `apply_sequence_with_regions`).
+///
+/// # Errors
+///
+/// Returns an error if a filter collection can't be applied or if an `until`
+/// property names a filter collection that doesn't exist.
+pub fn apply_sequence_with_rng<T, R>(
+    filter_collections: &[FilterCollection<T>],
+    source: &Map<T>,
+    destination: &mut Map<T>,
+    rng: &mut R,
+) -> Result<usize, TisuError>
+where
+    T: Clone + PartialEq + MaybeSync,
+    R: Rng + ?Sized,
+{
+    apply_sequence(
+        filter_collections,
+        destination,
+        |_, filter_collection, destination| {
+            filter_collection
+                .apply_with_rng(source, destination, rng)
+                .map(|stats| stats.changes())
+        },
+    )
+}
+
+/// Applies the given filter collections like `apply_sequence_with_rng`, where
+/// filter collections restricted to a region are applied with the mask of
+/// the region from the given regions (see `region_mask::resolve`).
+///
+/// # Errors
+///
+/// Returns an error if a filter collection can't be applied, if a region
+/// doesn't exist or if an `until` property names a filter collection that
+/// doesn't exist.
+pub fn apply_sequence_with_regions<T, R>(
+    filter_collections: &[FilterCollection<T>],
+    source: &Map<T>,
+    destination: &mut Map<T>,
+    regions: &HashMap<String, Map<bool>>,
+    rng: &mut R,
+) -> Result<usize, TisuError>
+where
+    T: Clone + PartialEq + MaybeSync,
+    R: Rng + ?Sized,
+{
+    apply_sequence(
+        filter_collections,
+        destination,
+        |_, filter_collection, destination| {
+            let mask = filter_collection
+                .properties
+                .region
+                .as_ref()
+                .map(|region| region_mask::resolve(regions, region))
+                .transpose()?;
+            let options = &mut ApplyOptions {
+                mask: mask.as_ref(),
+                ..ApplyOptions::new(&mut *rng)
+            };
+            let stats = filter_collection.apply_with(source, destination, options)?;
+            Ok(stats.changes())
+        },
+    )
+}
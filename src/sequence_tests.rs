@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    filter::{Filter, FilterCollection, FilterProperties},
+    map::Map,
+    sequence::{
+        apply_sequence, apply_sequence_with_regions, apply_sequence_with_rng, UNTIL_UNCHANGED,
+    },
+    tisu_error::TisuError,
+};
+
+fn create_filter_collection<const N: usize>(
+    name: &str,
+    pattern: [u32; N],
+    substitute: [u32; N],
+    properties: FilterProperties,
+) -> FilterCollection<u32> {
+    let filter = Filter::new(
+        Map::from_data([pattern]).unwrap(),
+        Map::from_data([substitute]).unwrap(),
+        9,
+    )
+    .unwrap();
+    FilterCollection {
+        name: String::from(name),
+        ..FilterCollection::new_with_properties(&[filter], properties)
+    }
+}
+
+/// Applies the filter collections, matching each run against the result of
+/// the previous run, and returns the output and the applied collection indices.
+fn apply_steps(
+    filter_collections: &[FilterCollection<u32>],
+    input: &Map<u32>,
+) -> Result<(Map<u32>, Vec<usize>), TisuError> {
+    let mut output = input.clone();
+    let mut applied = vec![];
+    apply_sequence(
+        filter_collections,
+        &mut output,
+        |idx, filter_collection, destination| {
+            applied.push(idx);
+            let source = destination.clone();
+            filter_collection
+                .apply(&source, destination)
+                .map(|stats| stats.changes())
+        },
+    )?;
+    Ok((output, applied))
+}
+
+#[test]
+fn test_apply_sequence_when() {
+    let when_rooms = FilterProperties {
+        when: Some(String::from("Rooms")),
+        ..Default::default()
+    };
+    let filter_collections = [
+        create_filter_collection("Rooms/Floor", [1], [2], FilterProperties::default()),
+        create_filter_collection("Doors", [0], [3], when_rooms),
+    ];
+
+    let (output, applied) =
+        apply_steps(&filter_collections, &Map::from_data([[1, 0]]).unwrap()).unwrap();
+    assert_eq!(output, Map::from_data([[2, 3]]).unwrap());
+    assert_eq!(applied, [0, 1]);
+
+    let (output, applied) =
+        apply_steps(&filter_collections, &Map::from_data([[4, 0]]).unwrap()).unwrap();
+    assert_eq!(output, Map::from_data([[4, 0]]).unwrap());
+    assert_eq!(applied, [0]);
+}
+
+#[test]
+fn test_apply_sequence_when_prefix() {
+    // "Room" is not a group of "Rooms/Floor"
+    let when_room = FilterProperties {
+        when: Some(String::from("Room")),
+        ..Default::default()
+    };
+    let filter_collections = [
+        create_filter_collection("Rooms/Floor", [1], [2], FilterProperties::default()),
+        create_filter_collection("Doors", [0], [3], when_room),
+    ];
+
+    let (output, _) = apply_steps(&filter_collections, &Map::from_data([[1, 0]]).unwrap()).unwrap();
+
+    assert_eq!(output, Map::from_data([[2, 0]]).unwrap());
+}
+
+#[test]
+fn test_apply_sequence_until_unchanged() {
+    // Grows 1s to the right, one field per run
+    let grow = |max_runs| {
+        create_filter_collection(
+            "Grow",
+            [1, 0],
+            [1, 1],
+            FilterProperties {
+                until: Some(String::from(UNTIL_UNCHANGED)),
+                max_runs,
+                ..Default::default()
+            },
+        )
+    };
+    let input = Map::from_data([[1, 0, 0, 0]]).unwrap();
+
+    let (output, applied) = apply_steps(&[grow(None)], &input).unwrap();
+    assert_eq!(output, Map::from_data([[1, 1, 1, 1]]).unwrap());
+    // The last run changes nothing
+    assert_eq!(applied, [0, 0, 0, 0]);
+
+    let (output, applied) = apply_steps(&[grow(Some(2))], &input).unwrap();
+    assert_eq!(output, Map::from_data([[1, 1, 1, 0]]).unwrap());
+    assert_eq!(applied, [0, 0]);
+}
+
+#[test]
+fn test_apply_sequence_until_default_max_runs() {
+    // The sentinel never matches
+    let filter_collections = |max_runs| {
+        [
+            create_filter_collection(
+                "Grow",
+                [1, 0],
+                [1, 1],
+                FilterProperties {
+                    until: Some(String::from("Never")),
+                    max_runs,
+                    ..Default::default()
+                },
+            ),
+            create_filter_collection(
+                "Never",
+                [5],
+                [5],
+                FilterProperties {
+                    sentinel: true,
+                    ..Default::default()
+                },
+            ),
+        ]
+    };
+    let input = Map::from_data([[1, 0, 0]]).unwrap();
+
+    let result = apply_steps(&filter_collections(None), &input);
+    assert_eq!(result.err(), Some(TisuError::InvalidArgument));
+
+    let (output, applied) = apply_steps(&filter_collections(Some(3)), &input).unwrap();
+    assert_eq!(output, Map::from_data([[1, 1, 1]]).unwrap());
+    assert_eq!(applied, [0, 0, 0]);
+}
+
+#[test]
+fn test_apply_sequence_until_group() {
+    // Both filter collections inherit `until` from their group
+    let properties = FilterProperties {
+        until: Some(String::from(UNTIL_UNCHANGED)),
+        ..Default::default()
+    };
+    let filter_collections = [
+        create_filter_collection("Rooms/Grow", [1, 0], [1, 1], properties.clone()),
+        create_filter_collection("Rooms/Mark", [1, 1], [2, 1], properties),
+    ];
+
+    let (output, applied) =
+        apply_steps(&filter_collections, &Map::from_data([[1, 0, 0]]).unwrap()).unwrap();
+
+    // Each filter collection is repeated on its own, not the group as a whole
+    assert_eq!(output, Map::from_data([[2, 2, 1]]).unwrap());
+    assert_eq!(applied, [0, 0, 0, 1, 1]);
+}
+
+#[test]
+fn test_apply_sequence_until_sentinel() {
+    let filter_collections = [
+        create_filter_collection(
+            "Grow",
+            [1, 0],
+            [1, 1],
+            FilterProperties {
+                until: Some(String::from("Long")),
+                ..Default::default()
+            },
+        ),
+        create_filter_collection(
+            "Long",
+            [1, 1, 1],
+            [1, 1, 1],
+            FilterProperties {
+                sentinel: true,
+                ..Default::default()
+            },
+        ),
+    ];
+
+    let (output, applied) = apply_steps(
+        &filter_collections,
+        &Map::from_data([[1, 0, 0, 0, 0]]).unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!(output, Map::from_data([[1, 1, 1, 0, 0]]).unwrap());
+    assert_eq!(applied, [0, 0]);
+}
+
+#[test]
+fn test_apply_sequence_sentinel() {
+    let filter_collections = [
+        create_filter_collection("Mark", [0], [1], FilterProperties::default()),
+        create_filter_collection(
+            "Stop",
+            [1, 1],
+            [1, 1],
+            FilterProperties {
+                sentinel: true,
+                ..Default::default()
+            },
+        ),
+        create_filter_collection("Fill", [2], [3], FilterProperties::default()),
+    ];
+
+    let (output, applied) =
+        apply_steps(&filter_collections, &Map::from_data([[0, 0, 2]]).unwrap()).unwrap();
+    assert_eq!(output, Map::from_data([[1, 1, 2]]).unwrap());
+    assert_eq!(applied, [0]);
+
+    let (output, applied) =
+        apply_steps(&filter_collections, &Map::from_data([[0, 2, 2]]).unwrap()).unwrap();
+    assert_eq!(output, Map::from_data([[1, 3, 3]]).unwrap());
+    assert_eq!(applied, [0, 2]);
+}
+
+#[test]
+fn test_apply_sequence_failure() {
+    let filter_collections = [create_filter_collection(
+        "Grow",
+        [1, 0],
+        [1, 1],
+        FilterProperties {
+            until: Some(String::from("Missing")),
+            ..Default::default()
+        },
+    )];
+
+    let result = apply_steps(&filter_collections, &Map::from_data([[1, 0]]).unwrap());
+
+    assert_eq!(result.err().unwrap(), TisuError::NotFound);
+}
+
+#[test]
+fn test_apply_sequence_with_rng() {
+    let filter_collections = [
+        create_filter_collection("Mark", [0, 0], [1, 9], FilterProperties::default()),
+        create_filter_collection(
+            "Fill",
+            [0],
+            [2],
+            FilterProperties {
+                when: Some(String::from("Mark")),
+                ..Default::default()
+            },
+        ),
+    ];
+    let input = Map::from_data([[0, 0, 5, 0]]).unwrap();
+    let mut output = input.clone();
+
+    let result = apply_sequence_with_rng(
+        &filter_collections,
+        &input,
+        &mut output,
+        &mut StdRng::seed_from_u64(0),
+    );
+
+    // Both filter collections match the input, so "Fill" replaces the 1
+    assert_eq!(result, Ok(4));
+    assert_eq!(output, Map::from_data([[2, 2, 5, 2]]).unwrap());
+}
+
+#[test]
+fn test_apply_sequence_with_regions() {
+    let filter_collections = [
+        create_filter_collection(
+            "Inside",
+            [0],
+            [1],
+            FilterProperties {
+                region: Some(String::from("Spawn")),
+                ..Default::default()
+            },
+        ),
+        create_filter_collection(
+            "Outside",
+            [0],
+            [2],
+            FilterProperties {
+                region: Some(String::from("!Spawn")),
+                ..Default::default()
+            },
+        ),
+    ];
+    let regions = HashMap::from([(
+        String::from("Spawn"),
+        Map::from_data([[false, true, true, false]]).unwrap(),
+    )]);
+    let input = Map::from_data([[0, 0, 0, 0]]).unwrap();
+    let mut output = input.clone();
+
+    let result = apply_sequence_with_regions(
+        &filter_collections,
+        &input,
+        &mut output,
+        &regions,
+        &mut StdRng::seed_from_u64(0),
+    );
+
+    assert_eq!(result, Ok(4));
+    assert_eq!(output, Map::from_data([[2, 1, 1, 2]]).unwrap());
+
+    // Regions need masks
+    let mut output = input.clone();
+    assert_eq!(
+        apply_sequence_with_rng(
+            &filter_collections,
+            &input,
+            &mut output,
+            &mut StdRng::seed_from_u64(0)
+        ),
+        Err(TisuError::InvalidArgument)
+    );
+    assert_eq!(
+        apply_sequence_with_regions(
+            &filter_collections,
+            &input,
+            &mut output,
+            &HashMap::new(),
+            &mut StdRng::seed_from_u64(0)
+        ),
+        Err(TisuError::NotFound)
+    );
+}
@@ -0,0 +1,114 @@
+use crate::{
+    filter::FilterCollection,
+    filter_exporter::FilterExporter,
+    structured_filter_importer::{FilterFile, StructuredFormat},
+    tiled_tile::TiledTile,
+    tisu_error::TisuError,
+    vector2::Vector2u,
+};
+
+/// Formats the given filter collections in the given structured format (see
+/// `StructuredFilterImporter`).
+///
+/// # Errors
+///
+/// Returns an error if the filter collections can't be serialized, or if a
+/// filter has objects, alternative fields or distance constraints, which the
+/// structured formats don't represent.
+pub fn format(
+    filter_collections: &[FilterCollection<TiledTile>],
+    format: StructuredFormat,
+) -> Result<String, TisuError> {
+    if filter_collections.iter().any(|filter_collection| {
+        filter_collection.has_objects()
+            || filter_collection.has_alternatives()
+            || filter_collection.has_distance_constraints()
+    }) {
+        return Err(TisuError::InvalidArgument);
+    }
+    let filter_file = FilterFile {
+        include: vec![],
+        filter_collections: filter_collections.to_vec(),
+    };
+    match format {
+        StructuredFormat::Json => {
+            serde_json::to_string_pretty(&filter_file).map_err(|_| TisuError::Unexpected)
+        }
+        StructuredFormat::Toml => {
+            toml::to_string_pretty(&filter_file).map_err(|_| TisuError::Unexpected)
+        }
+        StructuredFormat::Yaml => {
+            serde_yaml::to_string(&filter_file).map_err(|_| TisuError::Unexpected)
+        }
+    }
+}
+
+pub struct StructuredFilterExporter;
+
+impl FilterExporter for StructuredFilterExporter {
+    type TileType = TiledTile;
+
+    fn save(
+        file: impl AsRef<std::path::Path>,
+        filter_collections: &[FilterCollection<Self::TileType>],
+        _tile_size: Vector2u,
+        _tileset_path: impl AsRef<std::path::Path>,
+    ) -> Result<(), TisuError> {
+        let text = format(
+            filter_collections,
+            StructuredFormat::try_from(file.as_ref())?,
+        )?;
+        std::fs::write(file, text).map_err(|_| TisuError::InvalidArgument)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        filter_importer::FilterImporter, map_object::MapObject, structured_filter_importer,
+        tiled_filter_importer::TiledFilterImporter,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let filter_collections = TiledFilterImporter::load(format!(
+            "{}/data/test_apply_filter_collection_pattern_matching/filter_collection.tmx",
+            env!("CARGO_MANIFEST_DIR"),
+        ))
+        .unwrap();
+
+        for structured_format in [
+            StructuredFormat::Json,
+            StructuredFormat::Toml,
+            StructuredFormat::Yaml,
+        ] {
+            let text = format(&filter_collections, structured_format).unwrap();
+
+            assert_eq!(
+                structured_filter_importer::parse(&text, structured_format).unwrap(),
+                filter_collections
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_failure() {
+        let mut filter_collections = TiledFilterImporter::load(format!(
+            "{}/data/test_apply_filter_collection_pattern_matching/filter_collection.tmx",
+            env!("CARGO_MANIFEST_DIR"),
+        ))
+        .unwrap();
+        filter_collections[0].filters[0].set_objects(vec![MapObject {
+            name: String::from("Chest"),
+            ..Default::default()
+        }]);
+
+        // Objects would be lost
+        assert_eq!(
+            format(&filter_collections, StructuredFormat::Json).err(),
+            Some(TisuError::InvalidArgument)
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    filter::FilterCollection,
+    filter_importer::FilterImporter,
+    filter_loader::{self, FilterFormat},
+    tiled_tile::TiledTile,
+    tisu_error::TisuError,
+};
+
+/// Format of a structured filter file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StructuredFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl TryFrom<&Path> for StructuredFormat {
+    type Error = TisuError;
+
+    /// Determines the format from the file extension.
+    fn try_from(value: &Path) -> Result<Self, Self::Error> {
+        match value.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Ok(StructuredFormat::Json),
+            Some("toml") => Ok(StructuredFormat::Toml),
+            Some("yaml") | Some("yml") => Ok(StructuredFormat::Yaml),
+            _ => Err(TisuError::InvalidArgument),
+        }
+    }
+}
+
+/// Contents of a structured filter file
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(crate) struct FilterFile {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<PathBuf>,
+    pub filter_collections: Vec<FilterCollection<TiledTile>>,
+}
+
+/// Parses filter collections from the given text in the given format.
+/// Included filter files are ignored.
+///
+/// # Errors
+///
+/// Returns an error if the text can't be parsed or if it contains invalid
+/// filters.
+pub fn parse(
+    text: &str,
+    format: StructuredFormat,
+) -> Result<Vec<FilterCollection<TiledTile>>, TisuError> {
+    Ok(parse_filter_file(text, format)?.filter_collections)
+}
+
+fn parse_filter_file(text: &str, format: StructuredFormat) -> Result<FilterFile, TisuError> {
+    let mut filter_file: FilterFile = match format {
+        StructuredFormat::Json => {
+            serde_json::from_str(text).map_err(|_| TisuError::InvalidArgument)?
+        }
+        StructuredFormat::Toml => toml::from_str(text).map_err(|_| TisuError::InvalidArgument)?,
+        StructuredFormat::Yaml => {
+            serde_yaml::from_str(text).map_err(|_| TisuError::InvalidArgument)?
+        }
+    };
+    for filter_collection in &mut filter_file.filter_collections {
+        filter_collection.set_field_orientation();
+    }
+    Ok(filter_file)
+}
+
+/// Imports filters from JSON, TOML or YAML files (determined by the file
+/// extension). The file holds a `filter_collections` list, where each filter
+/// collection has a `name`, `properties` and a list of `filters`. Each filter
+/// has a `pattern` and a `substitute` (lists of tile rows), and optionally a
+/// `wildcard` and `properties`. Filters without `properties` inherit the
+/// properties of their filter collection. Other filter files can be included
+/// by listing their paths in an `include` list.
+///
+/// ```json
+/// {
+///   "filter_collections": [
+///     {
+///       "name": "Walls",
+///       "properties": { "probability": 0.5 },
+///       "filters": [
+///         {
+///           "pattern": [[{ "index": 1 }, { "index": 2 }]],
+///           "substitute": [[{ "index": 2 }, { "index": 2 }]]
+///         }
+///       ]
+///     }
+///   ]
+/// }
+/// ```
+pub struct StructuredFilterImporter;
+
+impl StructuredFilterImporter {
+    /// Loads the filter collections of the given file, along with the paths of
+    /// the filter files it includes.
+    pub(crate) fn load_file(
+        file: impl AsRef<Path>,
+    ) -> Result<(Vec<FilterCollection<TiledTile>>, Vec<PathBuf>), TisuError> {
+        let format = StructuredFormat::try_from(file.as_ref())?;
+        let text = std::fs::read_to_string(file).map_err(|_| TisuError::InvalidArgument)?;
+        let filter_file = parse_filter_file(&text, format)?;
+        Ok((filter_file.filter_collections, filter_file.include))
+    }
+}
+
+impl FilterImporter for StructuredFilterImporter {
+    type TileType = TiledTile;
+
+    fn load(
+        file: impl AsRef<std::path::Path>,
+    ) -> Result<Vec<FilterCollection<Self::TileType>>, TisuError> {
+        filter_loader::load_with_includes(file, FilterFormat::Structured)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::filter::{FilterProperties, PatternMatching};
+    use crate::regions::{Neighborhood, RegionOp, RegionOpKind};
+
+    use super::*;
+
+    fn tile(index: u32) -> TiledTile {
+        TiledTile {
+            index: Some(index),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            StructuredFormat::try_from(Path::new("filters.json")),
+            Ok(StructuredFormat::Json)
+        );
+        assert_eq!(
+            StructuredFormat::try_from(Path::new("filters.toml")),
+            Ok(StructuredFormat::Toml)
+        );
+        assert_eq!(
+            StructuredFormat::try_from(Path::new("filters.yml")),
+            Ok(StructuredFormat::Yaml)
+        );
+        assert_eq!(
+            StructuredFormat::try_from(Path::new("filters.tmx")),
+            Err(TisuError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let text = r#"{
+            "filter_collections": [
+                {
+                    "name": "Walls",
+                    "properties": { "probability": 0.5 },
+                    "filters": [
+                        {
+                            "pattern": [[{ "index": 1 }, { "index": 2 }]],
+                            "substitute": [[{ "index": 2 }, {}]]
+                        },
+                        {
+                            "pattern": [[{ "index": 3 }]],
+                            "substitute": [[{ "index": 4, "flip_h": true }]],
+                            "wildcard": { "index": 5 },
+                            "properties": { "pattern_matching": "destination" }
+                        }
+                    ]
+                }
+            ]
+        }"#;
+
+        let result = parse(text, StructuredFormat::Json);
+
+        assert!(result.is_ok());
+        let filter_collections = result.unwrap();
+        assert_eq!(filter_collections.len(), 1);
+        let walls = &filter_collections[0];
+        assert_eq!(walls.name, "Walls");
+        assert_eq!(walls.properties.probability, 0.5);
+        assert_eq!(walls.filters.len(), 2);
+        assert_eq!(walls.filters[0].properties(), &walls.properties);
+        assert_eq!(walls.filters[0].pattern().size(), (2, 1).into());
+        assert_eq!(
+            walls.filters[0].substitute().data(),
+            [tile(2), TiledTile::default()]
+        );
+        assert_eq!(walls.filters[1].wildcard(), &tile(5));
+        assert_eq!(
+            walls.filters[1].properties(),
+            &FilterProperties {
+                pattern_matching: PatternMatching::Destination,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_toml() {
+        let text = r#"
+            [[filter_collections]]
+            properties = { iterations = 2 }
+
+            [[filter_collections.filters]]
+            pattern = [[{ index = 1 }], [{ index = 1 }]]
+            substitute = [[{ index = 2 }], [{ index = 2 }]]
+        "#;
+
+        let result = parse(text, StructuredFormat::Toml);
+
+        assert!(result.is_ok());
+        let filter_collections = result.unwrap();
+        assert_eq!(filter_collections.len(), 1);
+        assert_eq!(filter_collections[0].properties.iterations, 2);
+        assert_eq!(
+            filter_collections[0].filters[0].pattern().size(),
+            (1, 2).into()
+        );
+    }
+
+    #[test]
+    fn test_parse_region_op() {
+        let text = r#"
+            [[filter_collections]]
+            name = "Islands"
+
+            [filter_collections.region_op]
+            kind = "keep_largest"
+            neighborhood = 8
+            tiles = [{ index = 1 }]
+            replacements = [{ index = 2 }]
+        "#;
+
+        let result = parse(text, StructuredFormat::Toml);
+
+        assert!(result.is_ok());
+        let filter_collections = result.unwrap();
+        assert!(filter_collections[0].filters.is_empty());
+        assert_eq!(
+            filter_collections[0].region_op,
+            Some(RegionOp {
+                neighborhood: Neighborhood::Eight,
+                ..RegionOp::new(RegionOpKind::KeepLargest, vec![tile(1)], vec![tile(2)])
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_yaml() {
+        let text = "
+            filter_collections:
+              - name: Floor
+                filters:
+                  - pattern: [[{ index: 1 }]]
+                    substitute: [[{ index: 2 }]]
+        ";
+
+        let result = parse(text, StructuredFormat::Yaml);
+
+        assert!(result.is_ok());
+        let filter_collections = result.unwrap();
+        assert_eq!(filter_collections[0].name, "Floor");
+        assert_eq!(
+            filter_collections[0].filters[0].substitute().data(),
+            [tile(2)]
+        );
+    }
+
+    #[test]
+    fn test_parse_failure() {
+        // Pattern and substitute sizes differ
+        let text = r#"{
+            "filter_collections": [
+                {
+                    "filters": [
+                        {
+                            "pattern": [[{ "index": 1 }, { "index": 2 }]],
+                            "substitute": [[{ "index": 2 }]]
+                        }
+                    ]
+                }
+            ]
+        }"#;
+        assert_eq!(
+            parse(text, StructuredFormat::Json).err().unwrap(),
+            TisuError::InvalidArgument
+        );
+
+        // Rows of different lengths
+        let text = r#"{
+            "filter_collections": [
+                {
+                    "filters": [
+                        {
+                            "pattern": [[{ "index": 1 }, { "index": 2 }], [{ "index": 1 }]],
+                            "substitute": [[{ "index": 2 }, { "index": 2 }], [{ "index": 1 }]]
+                        }
+                    ]
+                }
+            ]
+        }"#;
+        assert_eq!(
+            parse(text, StructuredFormat::Json).err().unwrap(),
+            TisuError::InvalidArgument
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use std::fmt::Write;
+
+use crate::{
+    filter::{FilterCollection, FilterProperties},
+    filter_exporter::FilterExporter,
+    map::Map,
+    tiled_tile::TiledTile,
+    tisu_error::TisuError,
+    vector2::Vector2u,
+};
+
+/// Characters assigned to tiles in the legend (the empty tile is always `.`)
+const LEGEND_CHARS: &str =
+    "#?+*o~%&@$0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+fn format_tile(tile: &TiledTile) -> String {
+    match tile.index {
+        Some(index) => {
+            let mut result = index.to_string();
+            if tile.flip_h {
+                result.push('h');
+            }
+            if tile.flip_v {
+                result.push('v');
+            }
+            if tile.flip_d {
+                result.push('d');
+            }
+            result
+        }
+        None => String::from("empty"),
+    }
+}
+
+/// Assigns a legend character to each distinct tile of the given filter
+/// collections.
+///
+/// # Errors
+///
+/// Returns an error if there are more distinct tiles than legend characters.
+fn create_legend(
+    filter_collections: &[FilterCollection<TiledTile>],
+) -> Result<Vec<(char, TiledTile)>, TisuError> {
+    let mut tiles: Vec<TiledTile> = vec![];
+    let mut add_tile = |tile: &TiledTile| {
+        if !tiles.contains(tile) {
+            tiles.push(tile.clone());
+        }
+    };
+    for filter in filter_collections.iter().flat_map(|fc| &fc.filters) {
+        add_tile(filter.wildcard());
+        filter.pattern().data().iter().for_each(&mut add_tile);
+        filter.substitute().data().iter().for_each(&mut add_tile);
+    }
+
+    let mut chars = LEGEND_CHARS.chars();
+    tiles
+        .into_iter()
+        .map(|tile| {
+            let c = if tile == TiledTile::default() {
+                '.'
+            } else {
+                chars.next().ok_or(TisuError::InvalidArgument)?
+            };
+            Ok((c, tile))
+        })
+        .collect()
+}
+
+fn legend_char(legend: &[(char, TiledTile)], tile: &TiledTile) -> char {
+    legend
+        .iter()
+        .find(|(_, t)| t == tile)
+        .map(|(c, _)| *c)
+        .unwrap_or('.')
+}
+
+fn format_row(legend: &[(char, TiledTile)], map: &Map<TiledTile>, y: u32) -> String {
+    (0..map.size().x)
+        .filter_map(|x| map.get((x, y).into()).ok())
+        .map(|tile| legend_char(legend, tile))
+        .collect()
+}
+
+fn format_properties(result: &mut String, properties: &FilterProperties) {
+    let default = FilterProperties::default();
+    if properties.probability != default.probability {
+        _ = writeln!(result, "probability = {}", properties.probability);
+    }
+    if properties.pattern_matching != default.pattern_matching {
+        _ = writeln!(
+            result,
+            "pattern_matching = {}",
+            String::from(&properties.pattern_matching)
+        );
+    }
+    if properties.iterations != default.iterations {
+        _ = writeln!(result, "iterations = {}", properties.iterations);
+    }
+    if properties.ignore != default.ignore {
+        _ = writeln!(result, "ignore = {}", properties.ignore);
+    }
+    if let Some(region) = &properties.region {
+        _ = writeln!(result, "region = {}", region);
+    }
+    if properties.match_flips != default.match_flips {
+        _ = writeln!(
+            result,
+            "match_flips = {}",
+            String::from(&properties.match_flips)
+        );
+    }
+    if properties.substitute_flips != default.substitute_flips {
+        _ = writeln!(
+            result,
+            "substitute_flips = {}",
+            String::from(&properties.substitute_flips)
+        );
+    }
+    if properties.mode != default.mode {
+        _ = writeln!(result, "mode = {}", String::from(&properties.mode));
+    }
+    if let Some(max_steps) = properties.max_steps {
+        _ = writeln!(result, "max_steps = {}", max_steps);
+    }
+    if let Some(when) = &properties.when {
+        _ = writeln!(result, "when = {}", when);
+    }
+    if let Some(until) = &properties.until {
+        _ = writeln!(result, "until = {}", until);
+    }
+    if let Some(max_runs) = properties.max_runs {
+        _ = writeln!(result, "max_runs = {}", max_runs);
+    }
+    if properties.sentinel != default.sentinel {
+        _ = writeln!(result, "sentinel = {}", properties.sentinel);
+    }
+    if let Some(min_distance_to) = &properties.min_distance_to {
+        _ = writeln!(result, "min_distance_to = {}", min_distance_to);
+    }
+    if let Some(min_distance) = properties.min_distance {
+        _ = writeln!(result, "min_distance = {}", min_distance);
+    }
+    if let Some(max_distance_to) = &properties.max_distance_to {
+        _ = writeln!(result, "max_distance_to = {}", max_distance_to);
+    }
+    if let Some(max_distance) = properties.max_distance {
+        _ = writeln!(result, "max_distance = {}", max_distance);
+    }
+    if properties.distance_metric != default.distance_metric {
+        _ = writeln!(
+            result,
+            "distance_metric = {}",
+            String::from(&properties.distance_metric)
+        );
+    }
+}
+
+/// Formats the given filter collections using the text filter format (see
+/// `TextFilterImporter`).
+///
+/// # Errors
+///
+/// Returns an error if the filters of a filter collection have different
+/// wildcards, if there are too many distinct tiles to fit in the legend or if
+/// a filter collection has a region operation, a connectivity step, or
+/// filters with objects, alternative fields or distance constraints, which
+/// can't be represented.
+pub fn format(filter_collections: &[FilterCollection<TiledTile>]) -> Result<String, TisuError> {
+    if filter_collections.iter().any(|filter_collection| {
+        filter_collection.region_op.is_some()
+            || filter_collection.connectivity.is_some()
+            || filter_collection.has_objects()
+            || filter_collection.has_alternatives()
+            || filter_collection.has_distance_constraints()
+    }) {
+        return Err(TisuError::InvalidArgument);
+    }
+    let legend = create_legend(filter_collections)?;
+    let mut result = String::from("[legend]\n");
+    for (c, tile) in &legend {
+        _ = writeln!(result, "{} = {}", c, format_tile(tile));
+    }
+
+    for filter_collection in filter_collections {
+        result.push('\n');
+        if filter_collection.name.is_empty() {
+            result.push_str("[collection]\n");
+        } else {
+            _ = writeln!(result, "[collection {}]", filter_collection.name);
+        }
+        format_properties(&mut result, &filter_collection.properties);
+
+        let wildcard = filter_collection
+            .filters
+            .first()
+            .map(|filter| filter.wildcard().clone())
+            .unwrap_or_default();
+        if wildcard != TiledTile::default() {
+            _ = writeln!(result, "wildcard = {}", legend_char(&legend, &wildcard));
+        }
+
+        for filter in &filter_collection.filters {
+            if filter.wildcard() != &wildcard {
+                return Err(TisuError::InvalidArgument);
+            }
+            result.push('\n');
+            for y in 0..filter.pattern().size().y {
+                _ = writeln!(
+                    result,
+                    "{}  {}",
+                    format_row(&legend, filter.pattern(), y),
+                    format_row(&legend, filter.substitute(), y)
+                );
+            }
+        }
+    }
+    Ok(result)
+}
+
+pub struct TextFilterExporter;
+
+impl FilterExporter for TextFilterExporter {
+    type TileType = TiledTile;
+
+    fn save(
+        file: impl AsRef<std::path::Path>,
+        filter_collections: &[FilterCollection<Self::TileType>],
+        _tile_size: Vector2u,
+        _tileset_path: impl AsRef<std::path::Path>,
+    ) -> Result<(), TisuError> {
+        std::fs::write(file, format(filter_collections)?).map_err(|_| TisuError::InvalidArgument)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        filter_importer::FilterImporter,
+        text_filter_importer::{self, parse_tile},
+        tiled_filter_importer::TiledFilterImporter,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_format_tile() {
+        for text in ["empty", "0", "12", "3h", "4hvd", "5vd"] {
+            assert_eq!(format_tile(&parse_tile(text).unwrap()), text);
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        let text = "\
+[legend]
+? = 4
+# = 3
+. = empty
++ = 5hv
+
+[collection Walls]
+probability = 0.5
+pattern_matching = destination
+wildcard = ?
+
+#.  ##
+?.  ?+
+
+[collection]
+##  ..
+";
+        let filter_collections = text_filter_importer::parse(text).unwrap();
+
+        let result = format(&filter_collections);
+
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        assert_eq!(
+            text_filter_importer::parse(&result).unwrap(),
+            filter_collections
+        );
+    }
+
+    #[test]
+    fn test_format_failure() {
+        let mut filter_collections =
+            text_filter_importer::parse("[legend]\n# = 1\n. = 2\n\n[collection]\n#  .\n").unwrap();
+        filter_collections[0].filters[0]
+            .set_alternatives((0, 0).into(), vec![parse_tile("3").unwrap()])
+            .unwrap();
+
+        // Alternatives would be lost
+        assert_eq!(
+            format(&filter_collections).err(),
+            Some(TisuError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_round_trip_tiled() {
+        let filter_collections = TiledFilterImporter::load(format!(
+            "{}/data/test_apply_filter_collection_pattern_matching/filter_collection.tmx",
+            env!("CARGO_MANIFEST_DIR"),
+        ))
+        .unwrap();
+
+        let text = format(&filter_collections).unwrap();
+
+        assert_eq!(
+            text_filter_importer::parse(&text).unwrap(),
+            filter_collections
+        );
+    }
+}
@@ -0,0 +1,527 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    distance::DistanceMetric,
+    filter::{
+        ApplyMode, Filter, FilterCollection, FilterProperties, MatchFlips, PatternMatching,
+        SubstituteFlips,
+    },
+    filter_importer::FilterImporter,
+    filter_loader::{self, FilterFormat},
+    map::Map,
+    tile_metadata::{self, TilePredicate, TilesetMetadata},
+    tiled_tile::TiledTile,
+    tisu_error::TisuError,
+    vector2::Vector2u,
+};
+
+/// Prefix of legend values that stand for the tiles of a class
+const CLASS_PREFIX: &str = "class:";
+
+/// Parses a tile from its text representation: either `empty` or a tile index
+/// followed by any of the `h`, `v` and `d` flip flags (e.g. `12hv`).
+pub(crate) fn parse_tile(text: &str) -> Result<TiledTile, TisuError> {
+    if text == "empty" {
+        return Ok(TiledTile::default());
+    }
+
+    let flags_start = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (index, flags) = text.split_at(flags_start);
+    let mut tile = TiledTile {
+        index: Some(index.parse().map_err(|_| TisuError::InvalidArgument)?),
+        ..Default::default()
+    };
+    for flag in flags.chars() {
+        match flag {
+            'h' => tile.flip_h = true,
+            'v' => tile.flip_v = true,
+            'd' => tile.flip_d = true,
+            _ => return Err(TisuError::InvalidArgument),
+        }
+    }
+    Ok(tile)
+}
+
+/// Meaning of a legend character.
+#[derive(Clone, PartialEq, Debug)]
+enum LegendEntry {
+    /// A single tile
+    Tile(TiledTile),
+    /// Any tile of the given class of the tileset (only in patterns)
+    Class(String),
+}
+
+/// Parses a legend value: either a tile (see `parse_tile`) or `class:`
+/// followed by a tile class.
+fn parse_legend_entry(text: &str) -> Result<LegendEntry, TisuError> {
+    match text.strip_prefix(CLASS_PREFIX) {
+        Some("") => Err(TisuError::InvalidArgument),
+        Some(class) => Ok(LegendEntry::Class(String::from(class))),
+        None => parse_tile(text).map(LegendEntry::Tile),
+    }
+}
+
+/// Fields of a filter map that stand for tile classes, with their classes.
+type ClassFields = Vec<(Vector2u, String)>;
+
+/// Rows of a pattern/substitute pair, as written in the text.
+type FilterRows = Vec<(Vec<char>, Vec<char>)>;
+
+/// A filter collection section, as written in the text.
+#[derive(Default)]
+struct CollectionSection {
+    name: String,
+    properties: FilterProperties,
+    wildcard: Option<char>,
+    filters: Vec<FilterRows>,
+    current_filter: FilterRows,
+}
+
+impl CollectionSection {
+    fn end_filter(&mut self) {
+        if !self.current_filter.is_empty() {
+            self.filters.push(std::mem::take(&mut self.current_filter));
+        }
+    }
+
+    fn set_property(&mut self, key: &str, value: &str) -> Result<(), TisuError> {
+        match key {
+            "probability" => {
+                self.properties.probability =
+                    value.parse().map_err(|_| TisuError::InvalidArgument)?
+            }
+            "pattern_matching" => {
+                self.properties.pattern_matching = PatternMatching::try_from(&String::from(value))
+                    .map_err(|_| TisuError::InvalidArgument)?
+            }
+            "iterations" => {
+                self.properties.iterations =
+                    value.parse().map_err(|_| TisuError::InvalidArgument)?
+            }
+            "ignore" => {
+                self.properties.ignore = value.parse().map_err(|_| TisuError::InvalidArgument)?
+            }
+            "region" => self.properties.region = Some(String::from(value)),
+            "match_flips" => {
+                self.properties.match_flips = MatchFlips::try_from(&String::from(value))
+                    .map_err(|_| TisuError::InvalidArgument)?
+            }
+            "substitute_flips" => {
+                self.properties.substitute_flips = SubstituteFlips::try_from(&String::from(value))
+                    .map_err(|_| TisuError::InvalidArgument)?
+            }
+            "mode" => {
+                self.properties.mode = ApplyMode::try_from(&String::from(value))
+                    .map_err(|_| TisuError::InvalidArgument)?
+            }
+            "max_steps" => {
+                self.properties.max_steps =
+                    Some(value.parse().map_err(|_| TisuError::InvalidArgument)?)
+            }
+            "when" => self.properties.when = Some(String::from(value)),
+            "until" => self.properties.until = Some(String::from(value)),
+            "max_runs" => {
+                self.properties.max_runs =
+                    Some(value.parse().map_err(|_| TisuError::InvalidArgument)?)
+            }
+            "sentinel" => {
+                self.properties.sentinel = value.parse().map_err(|_| TisuError::InvalidArgument)?
+            }
+            "min_distance_to" => self.properties.min_distance_to = Some(String::from(value)),
+            "min_distance" => {
+                self.properties.min_distance =
+                    Some(value.parse().map_err(|_| TisuError::InvalidArgument)?)
+            }
+            "max_distance_to" => self.properties.max_distance_to = Some(String::from(value)),
+            "max_distance" => {
+                self.properties.max_distance =
+                    Some(value.parse().map_err(|_| TisuError::InvalidArgument)?)
+            }
+            "distance_metric" => {
+                self.properties.distance_metric = DistanceMetric::try_from(&String::from(value))
+                    .map_err(|_| TisuError::InvalidArgument)?
+            }
+            "wildcard" => {
+                let mut chars = value.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => self.wildcard = Some(c),
+                    _ => return Err(TisuError::InvalidArgument),
+                }
+            }
+            _ => return Err(TisuError::InvalidArgument),
+        }
+        Ok(())
+    }
+
+    fn build(
+        mut self,
+        legend: &HashMap<char, LegendEntry>,
+        tile_metadata: Option<&TilesetMetadata>,
+    ) -> Result<FilterCollection<TiledTile>, TisuError> {
+        self.end_filter();
+        let wildcard = match self.wildcard {
+            Some(c) => match legend.get(&c).ok_or(TisuError::NotFound)? {
+                LegendEntry::Tile(tile) => tile.clone(),
+                LegendEntry::Class(_) => return Err(TisuError::InvalidArgument),
+            },
+            None => TiledTile::default(),
+        };
+
+        let mut filter_collection =
+            FilterCollection::new_with_properties(&[], self.properties.clone());
+        filter_collection.name = self.name;
+        for rows in &self.filters {
+            let (pattern, classes) = build_map(rows.iter().map(|row| &row.0), legend)?;
+            let (substitute, substitute_classes) =
+                build_map(rows.iter().map(|row| &row.1), legend)?;
+            if !substitute_classes.is_empty() {
+                return Err(TisuError::InvalidArgument);
+            }
+            let mut filter = Filter::new_with_properties(
+                pattern,
+                substitute,
+                wildcard.clone(),
+                self.properties.clone(),
+            )?;
+            filter.set_field_orientation();
+            for (point, class) in classes {
+                let tile_metadata = tile_metadata.ok_or(TisuError::InvalidArgument)?;
+                let predicate = TilePredicate {
+                    class: Some(class),
+                    ..Default::default()
+                };
+                let tiles = predicate.matching_tiles(tile_metadata, &self.properties.match_flips);
+                filter.set_alternatives(point, tiles)?;
+            }
+            filter_collection.push(filter);
+        }
+        Ok(filter_collection)
+    }
+}
+
+/// Builds a map from the given rows of legend characters. Fields of class
+/// entries are left empty and returned along with their class.
+fn build_map<'a>(
+    rows: impl Iterator<Item = &'a Vec<char>> + Clone,
+    legend: &HashMap<char, LegendEntry>,
+) -> Result<(Map<TiledTile>, ClassFields), TisuError> {
+    let height = rows.clone().count();
+    let width = rows.clone().map(|row| row.len()).max().unwrap_or(0);
+    let mut map = Map::new(
+        (
+            u32::try_from(width).map_err(|_| TisuError::InvalidArgument)?,
+            u32::try_from(height).map_err(|_| TisuError::InvalidArgument)?,
+        )
+            .into(),
+    );
+    let mut classes = vec![];
+    for (y, row) in rows.enumerate() {
+        if row.len() != width {
+            return Err(TisuError::InvalidMapSize);
+        }
+        for (x, c) in row.iter().enumerate() {
+            let point = Vector2u::new(x as u32, y as u32);
+            match legend.get(c).ok_or(TisuError::NotFound)? {
+                LegendEntry::Tile(tile) => map.set(point, tile.clone())?,
+                LegendEntry::Class(class) => classes.push((point, class.clone())),
+            }
+        }
+    }
+    Ok((map, classes))
+}
+
+/// Parses filter collections from the text filter format. Included filter
+/// files are ignored, and the tileset path is relative to the current
+/// directory.
+///
+/// # Errors
+///
+/// Returns an error if the text can't be parsed or if the tileset can't be
+/// loaded.
+pub fn parse(text: &str) -> Result<Vec<FilterCollection<TiledTile>>, TisuError> {
+    Ok(parse_with_includes(text, Path::new(""))?.0)
+}
+
+/// Parses filter collections and the paths of included filter files from the
+/// text filter format. The tileset path is relative to the given directory.
+pub(crate) fn parse_with_includes(
+    text: &str,
+    directory: &Path,
+) -> Result<(Vec<FilterCollection<TiledTile>>, Vec<PathBuf>), TisuError> {
+    let mut includes = vec![];
+    let mut tile_metadata = None;
+    let mut legend = HashMap::new();
+    let mut in_legend = false;
+    let mut sections: Vec<CollectionSection> = vec![];
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with(';') {
+            continue;
+        }
+        if let Some(section) = sections.last_mut() {
+            if line.is_empty() || line.starts_with('[') || line.contains(" = ") {
+                section.end_filter();
+            }
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let (kind, name) = header.split_once(' ').unwrap_or((header, ""));
+            match kind {
+                "legend" => in_legend = true,
+                "include" if !name.trim().is_empty() => includes.push(PathBuf::from(name.trim())),
+                "tileset" if !name.trim().is_empty() => {
+                    tile_metadata = Some(tile_metadata::load(directory.join(name.trim()))?)
+                }
+                "collection" => {
+                    in_legend = false;
+                    sections.push(CollectionSection {
+                        name: String::from(name.trim()),
+                        ..Default::default()
+                    });
+                }
+                _ => return Err(TisuError::InvalidArgument),
+            }
+            continue;
+        }
+
+        let tokens: Vec<_> = line.split_whitespace().collect();
+        match (tokens.as_slice(), in_legend, sections.last_mut()) {
+            ([key, "=", value], true, _) => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => legend.insert(c, parse_legend_entry(value)?),
+                    _ => return Err(TisuError::InvalidArgument),
+                };
+            }
+            ([key, "=", value], false, Some(section)) => section.set_property(key, value)?,
+            ([pattern, substitute], false, Some(section)) => section
+                .current_filter
+                .push((pattern.chars().collect(), substitute.chars().collect())),
+            _ => return Err(TisuError::InvalidArgument),
+        }
+    }
+
+    let filter_collections = sections
+        .into_iter()
+        .map(|section| section.build(&legend, tile_metadata.as_ref()))
+        .collect::<Result<_, _>>()?;
+    Ok((filter_collections, includes))
+}
+
+/// Imports filters from a plain text format, where patterns and substitutes
+/// are drawn as character grids:
+///
+/// ```text
+/// ; Comments start with a semicolon
+/// [legend]
+/// . = empty
+/// # = 3
+/// ? = 4
+/// + = 5hv
+///
+/// [collection Walls]
+/// probability = 0.5
+/// wildcard = ?
+///
+/// #.  ##
+/// ?.  ?+
+/// ```
+///
+/// The legend maps characters to tile indices (optionally followed by the
+/// `h`, `v` and `d` flip flags) or, in patterns, to any tile of a class of the
+/// tileset given by a `[tileset <path>]` line (e.g. `W = class:wall`). Each `collection` section holds the filter
+/// properties and the filters of a filter collection. A filter is a block of
+/// lines, each holding a pattern row and a substitute row separated by
+/// whitespace. Filters are separated by empty lines. Other filter files can
+/// be included with `[include <path>]` lines.
+pub struct TextFilterImporter;
+
+impl TextFilterImporter {
+    /// Loads the filter collections of the given file, along with the paths of
+    /// the filter files it includes.
+    pub(crate) fn load_file(
+        file: impl AsRef<Path>,
+    ) -> Result<(Vec<FilterCollection<TiledTile>>, Vec<PathBuf>), TisuError> {
+        let text =
+            std::fs::read_to_string(file.as_ref()).map_err(|_| TisuError::InvalidArgument)?;
+        parse_with_includes(&text, file.as_ref().parent().unwrap_or(Path::new("")))
+    }
+}
+
+impl FilterImporter for TextFilterImporter {
+    type TileType = TiledTile;
+
+    fn load(
+        file: impl AsRef<std::path::Path>,
+    ) -> Result<Vec<FilterCollection<Self::TileType>>, TisuError> {
+        filter_loader::load_with_includes(file, FilterFormat::Text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tile() {
+        assert_eq!(parse_tile("empty"), Ok(TiledTile::default()));
+        assert_eq!(
+            parse_tile("12"),
+            Ok(TiledTile {
+                index: Some(12),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_tile("3hd"),
+            Ok(TiledTile {
+                index: Some(3),
+                flip_h: true,
+                flip_v: false,
+                flip_d: true,
+            })
+        );
+        assert_eq!(parse_tile("3x"), Err(TisuError::InvalidArgument));
+        assert_eq!(parse_tile("h"), Err(TisuError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = "
+            ; Test filters
+            [legend]
+            . = empty
+            # = 3
+            ? = 4
+            + = 5hv
+
+            [collection Walls]
+            probability = 0.5
+            pattern_matching = destination
+            region = Dungeon
+            match_flips = ignore
+            wildcard = ?
+
+            #.  ##
+            ?.  ?+
+
+            ##  ..
+
+            [collection]
+            mode = markov
+            max_steps = 10
+            when = Walls
+            #  .
+        ";
+
+        let result = parse(text);
+
+        assert!(result.is_ok());
+        let filter_collections = result.unwrap();
+        assert_eq!(filter_collections.len(), 2);
+
+        let walls = &filter_collections[0];
+        assert_eq!(walls.name, "Walls");
+        assert_eq!(
+            walls.properties,
+            FilterProperties {
+                probability: 0.5,
+                pattern_matching: PatternMatching::Destination,
+                region: Some(String::from("Dungeon")),
+                match_flips: MatchFlips::Ignore,
+                ..Default::default()
+            }
+        );
+        assert_eq!(walls.filters.len(), 2);
+        assert_eq!(walls.filters[0].pattern().size(), (2, 2).into());
+        assert_eq!(walls.filters[0].wildcard(), &parse_tile("4").unwrap());
+        assert_eq!(
+            walls.filters[0].substitute().get((1, 1).into()),
+            Ok(&parse_tile("5hv").unwrap())
+        );
+        assert_eq!(walls.filters[1].pattern().size(), (2, 1).into());
+
+        assert_eq!(filter_collections[1].name, "");
+        assert_eq!(filter_collections[1].properties.mode, ApplyMode::Markov);
+        assert_eq!(filter_collections[1].properties.max_steps, Some(10));
+        assert_eq!(
+            filter_collections[1].properties.when,
+            Some(String::from("Walls"))
+        );
+        assert_eq!(filter_collections[1].filters.len(), 1);
+        assert_eq!(
+            filter_collections[1].filters[0].wildcard(),
+            &TiledTile::default()
+        );
+    }
+
+    #[test]
+    fn test_parse_classes() {
+        let text = format!(
+            "
+            [tileset {}/data/test_metadata/tileset.tsx]
+            [legend]
+            . = empty
+            W = class:wall
+
+            [collection]
+            W.  ..
+            ",
+            env!("CARGO_MANIFEST_DIR")
+        );
+
+        let result = parse(&text);
+
+        assert!(result.is_ok());
+        let filter = &result.unwrap()[0].filters[0];
+        let wall = |index| TiledTile {
+            index: Some(index),
+            ..Default::default()
+        };
+        let map = Map::from_data([[wall(2), TiledTile::default()]]).unwrap();
+        assert!(filter.pattern_matches(&map, (0, 0).into()));
+        let map = Map::from_data([[wall(1), TiledTile::default()]]).unwrap();
+        assert!(!filter.pattern_matches(&map, (0, 0).into()));
+        assert_eq!(filter.alternatives()[0].1.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_failure() {
+        // Undefined legend character
+        assert_eq!(
+            parse("[collection]\n#  .").err().unwrap(),
+            TisuError::NotFound
+        );
+        // Pattern and substitute sizes differ
+        assert_eq!(
+            parse("[legend]\n# = 1\n[collection]\n#  ##").err().unwrap(),
+            TisuError::InvalidMapSize
+        );
+        // Unknown property
+        assert_eq!(
+            parse("[collection]\nprobabilty = 1").err().unwrap(),
+            TisuError::InvalidArgument
+        );
+        // Filter outside of a collection
+        assert_eq!(
+            parse("[legend]\n# = 1\n#  #").err().unwrap(),
+            TisuError::InvalidArgument
+        );
+        // Class without a tileset
+        assert_eq!(
+            parse("[legend]\n# = class:wall\n. = 1\n[collection]\n#  .")
+                .err()
+                .unwrap(),
+            TisuError::InvalidArgument
+        );
+    }
+}
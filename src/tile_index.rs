@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::map::Map;
+use crate::vector2::Vector2u;
+
+/// Looks up the positions of a field value in the positions of an index
+type Lookup<T> = for<'a, 'b> fn(&'a HashMap<T, Vec<Vector2u>>, &'b T) -> Option<&'a Vec<Vector2u>>;
+
+/// An index of the positions of each field value in a map, used to find
+/// pattern match candidates without scanning the whole map.
+#[derive(Clone, Debug)]
+pub struct TileIndex<T> {
+    /// Size of the indexed map
+    size: Vector2u,
+    /// Positions of each field value, column by column
+    positions: HashMap<T, Vec<Vector2u>>,
+    /// Lookup of the positions, so that the index can be used where field
+    /// values aren't known to be hashable (see `filter::ApplyOptions`)
+    lookup: Lookup<T>,
+}
+
+impl<T> TileIndex<T> {
+    /// Creates an index of the given map.
+    pub fn new(map: &Map<T>) -> Self
+    where
+        T: Clone + Eq + Hash,
+    {
+        let mut positions: HashMap<T, Vec<Vector2u>> = HashMap::new();
+        for x in 0..map.size().x {
+            for y in 0..map.size().y {
+                let point = Vector2u::new(x, y);
+                if let Ok(field) = map.get(point) {
+                    positions.entry(field.clone()).or_default().push(point);
+                }
+            }
+        }
+        Self {
+            size: map.size(),
+            positions,
+            lookup: HashMap::get,
+        }
+    }
+
+    /// Returns the size of the indexed map.
+    pub fn size(&self) -> Vector2u {
+        self.size
+    }
+
+    /// Returns the positions of the given field value, column by column (the
+    /// order in which filters visit positions).
+    pub fn positions(&self, field: &T) -> &[Vector2u] {
+        (self.lookup)(&self.positions, field).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the number of fields with the given value.
+    pub fn count(&self, field: &T) -> usize {
+        self.positions(field).len()
+    }
+}
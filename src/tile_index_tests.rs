@@ -0,0 +1,31 @@
+use crate::{map::Map, tile_index::TileIndex, vector2::Vector2u};
+
+#[test]
+fn test_positions() {
+    let map = Map::<u32>::from_data([[1, 2, 1], [2, 1, 3]]).unwrap();
+
+    let index = TileIndex::new(&map);
+
+    assert_eq!(index.size(), (3, 2).into());
+    assert_eq!(
+        index.positions(&1),
+        [(0, 0).into(), (1, 1).into(), (2, 0).into()] as [Vector2u; 3]
+    );
+    assert_eq!(
+        index.positions(&2),
+        [(0, 1).into(), (1, 0).into()] as [Vector2u; 2]
+    );
+    assert_eq!(index.positions(&3), [(2, 1).into()] as [Vector2u; 1]);
+    assert!(index.positions(&4).is_empty());
+}
+
+#[test]
+fn test_count() {
+    let map = Map::<u32>::from_data([[1, 2, 1], [2, 1, 3]]).unwrap();
+
+    let index = TileIndex::new(&map);
+
+    assert_eq!(index.count(&1), 3);
+    assert_eq!(index.count(&3), 1);
+    assert_eq!(index.count(&4), 0);
+}
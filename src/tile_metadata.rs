@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use tiled::Loader;
+
+use crate::{filter::MatchFlips, tiled_tile::TiledTile, tisu_error::TisuError};
+
+/// Prefix of the properties of predicate tiles (see `TilePredicate`)
+const PREDICATE_PREFIX: &str = "match_";
+
+/// Class and custom properties of a tileset tile.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TileMetadata {
+    pub class: String,
+    pub properties: tiled::Properties,
+}
+
+/// Metadata of the tiles of a tileset, by tile ID. Tiles without a class and
+/// properties are omitted.
+pub type TilesetMetadata = HashMap<u32, TileMetadata>;
+
+/// Returns the metadata of the tiles of the given tileset.
+pub fn from_tileset(tileset: &tiled::Tileset) -> TilesetMetadata {
+    tileset
+        .tiles()
+        .filter(|(_, tile)| tile.user_type.is_some() || !tile.properties.is_empty())
+        .map(|(id, tile)| {
+            let metadata = TileMetadata {
+                class: tile.user_type.clone().unwrap_or_default(),
+                properties: tile.properties.clone(),
+            };
+            (id, metadata)
+        })
+        .collect()
+}
+
+/// Loads the metadata of the tiles of the given tileset file (`.tsx`).
+///
+/// # Errors
+///
+/// Returns an error if the tileset can't be loaded.
+pub fn load(file: impl AsRef<Path>) -> Result<TilesetMetadata, TisuError> {
+    let mut loader = Loader::new();
+    let tileset = loader
+        .load_tsx_tileset(file)
+        .map_err(|_| TisuError::InvalidArgument)?;
+    Ok(from_tileset(&tileset))
+}
+
+/// A condition on the class and the custom properties of tiles. A tile
+/// satisfies the predicate if it has the given class (if any) and all the
+/// given properties with equal values.
+///
+/// In filter files, predicates are given by predicate tiles: tileset tiles
+/// with a `match_class` property and/or properties prefixed with `match_`
+/// (e.g. `match_solid = true` is satisfied by tiles whose `solid` property is
+/// `true`).
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TilePredicate {
+    pub class: Option<String>,
+    pub properties: tiled::Properties,
+}
+
+impl TilePredicate {
+    /// Returns the predicate of a predicate tile with the given metadata, or
+    /// `None` if the tile isn't a predicate tile.
+    pub fn from_predicate_tile(metadata: &TileMetadata) -> Option<Self> {
+        let mut predicate = Self::default();
+        for (name, value) in &metadata.properties {
+            match name.strip_prefix(PREDICATE_PREFIX) {
+                Some("class") => match value {
+                    tiled::PropertyValue::StringValue(class) => {
+                        predicate.class = Some(class.clone())
+                    }
+                    _ => return None,
+                },
+                Some(property) => {
+                    predicate
+                        .properties
+                        .insert(String::from(property), value.clone());
+                }
+                None => (),
+            }
+        }
+        if predicate.class.is_none() && predicate.properties.is_empty() {
+            None
+        } else {
+            Some(predicate)
+        }
+    }
+
+    /// Checks if a tile with the given metadata satisfies the predicate.
+    pub fn matches(&self, metadata: &TileMetadata) -> bool {
+        self.class
+            .as_ref()
+            .is_none_or(|class| class == &metadata.class)
+            && self
+                .properties
+                .iter()
+                .all(|(name, value)| metadata.properties.get(name) == Some(value))
+    }
+
+    /// Returns the tiles of the given tileset that satisfy the predicate,
+    /// ordered by tile ID. With `match_flips` set to `ignore`, the tiles are
+    /// returned in all orientations, and otherwise only unflipped.
+    pub fn matching_tiles(
+        &self,
+        tileset: &TilesetMetadata,
+        match_flips: &MatchFlips,
+    ) -> Vec<TiledTile> {
+        let mut ids: Vec<_> = tileset
+            .iter()
+            .filter(|(_, metadata)| self.matches(metadata))
+            .map(|(id, _)| *id)
+            .collect();
+        ids.sort();
+        let orientations = match match_flips {
+            MatchFlips::Exact => 1,
+            MatchFlips::Ignore => 8,
+        };
+        ids.into_iter()
+            .flat_map(|id| {
+                (0..orientations).map(move |flags| TiledTile {
+                    index: Some(id),
+                    flip_h: flags & 1 != 0,
+                    flip_v: flags & 2 != 0,
+                    flip_d: flags & 4 != 0,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tiled::PropertyValue;
+
+    use super::*;
+
+    fn load_tileset() -> TilesetMetadata {
+        load(format!(
+            "{}/data/test_metadata/tileset.tsx",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_load() {
+        let tileset = load_tileset();
+
+        assert_eq!(tileset.len(), 5);
+        assert_eq!(
+            tileset[&0],
+            TileMetadata {
+                class: String::from("wall"),
+                properties: [(String::from("solid"), PropertyValue::BoolValue(true))].into(),
+            }
+        );
+        assert_eq!(tileset[&3].class, "");
+        assert!(!tileset.contains_key(&5));
+    }
+
+    #[test]
+    fn test_from_predicate_tile() {
+        let tileset = load_tileset();
+
+        assert_eq!(TilePredicate::from_predicate_tile(&tileset[&0]), None);
+        assert_eq!(
+            TilePredicate::from_predicate_tile(&tileset[&3]),
+            Some(TilePredicate {
+                class: Some(String::from("wall")),
+                properties: tiled::Properties::new(),
+            })
+        );
+        assert_eq!(
+            TilePredicate::from_predicate_tile(&tileset[&4]),
+            Some(TilePredicate {
+                class: None,
+                properties: [(String::from("solid"), PropertyValue::BoolValue(false))].into(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_matches() {
+        let tileset = load_tileset();
+        let predicate = TilePredicate {
+            class: Some(String::from("wall")),
+            properties: [(
+                String::from("biome"),
+                PropertyValue::StringValue(String::from("cave")),
+            )]
+            .into(),
+        };
+
+        assert!(!predicate.matches(&tileset[&0]));
+        assert!(!predicate.matches(&tileset[&1]));
+        assert!(predicate.matches(&tileset[&2]));
+    }
+
+    #[test]
+    fn test_matching_tiles() {
+        let tileset = load_tileset();
+        let predicate = TilePredicate {
+            class: None,
+            properties: [(String::from("solid"), PropertyValue::BoolValue(true))].into(),
+        };
+
+        let tiles = predicate.matching_tiles(&tileset, &MatchFlips::Ignore);
+
+        assert_eq!(tiles.len(), 16);
+        assert!(tiles[..8].iter().all(|tile| tile.index == Some(0)));
+        assert!(tiles[8..].iter().all(|tile| tile.index == Some(2)));
+        assert!(tiles.contains(&TiledTile {
+            index: Some(2),
+            flip_h: true,
+            flip_d: true,
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn test_matching_tiles_exact() {
+        let tileset = load_tileset();
+        let predicate = TilePredicate {
+            class: None,
+            properties: [(String::from("solid"), PropertyValue::BoolValue(true))].into(),
+        };
+
+        let tiles = predicate.matching_tiles(&tileset, &MatchFlips::Exact);
+
+        assert_eq!(
+            tiles,
+            [
+                TiledTile {
+                    index: Some(0),
+                    ..Default::default()
+                },
+                TiledTile {
+                    index: Some(2),
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+}
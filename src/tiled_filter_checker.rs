@@ -0,0 +1,520 @@
+use std::fmt::Display;
+
+use tiled::PropertyValue;
+
+use crate::{
+    filter::{ApplyMode, FilterProperties},
+    map::Map,
+    map_segmenter,
+    rect2::Rect2u,
+    tiled_filter_importer::{
+        load_filter_layers, load_tmx_map, parse_layer_property, substitute_objects, FilterLayer,
+    },
+    tiled_tile::TiledTile,
+    tisu_error::TisuError,
+    vector2::{Vector2, Vector2u},
+};
+
+/// Severity of a filter file diagnostic
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    /// The filter file is valid, but probably doesn't do what was intended
+    Warning,
+    /// A part of the filter file can't be interpreted
+    Error,
+}
+
+/// A problem found in a filter file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Name of the layer containing the problem (if any)
+    pub layer: Option<String>,
+    /// Position of the problem within the layer (if any)
+    pub position: Option<Vector2u>,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning")?,
+            Severity::Error => write!(f, "error")?,
+        }
+        if let Some(layer) = &self.layer {
+            write!(f, ": layer '{}'", layer)?;
+        }
+        if let Some(position) = self.position {
+            write!(f, " at ({}, {})", position.x, position.y)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Checks the given Tiled filter file for problems that would otherwise be
+/// silently ignored during filter import.
+///
+/// # Errors
+///
+/// Returns an error if the filter file can't be loaded.
+pub fn check(file: impl AsRef<std::path::Path>) -> Result<Vec<Diagnostic>, TisuError> {
+    let tmx_map = load_tmx_map(file)?;
+    let filter_layers = load_filter_layers(&tmx_map)?;
+
+    let mut result = vec![];
+    // Map properties starting with 'include' list included filter files
+    for name in tmx_map
+        .properties
+        .keys()
+        .filter(|name| !name.starts_with("include"))
+    {
+        result.push(Diagnostic {
+            severity: Severity::Warning,
+            layer: None,
+            position: None,
+            message: format!(
+                "map property '{}' is ignored, only layer properties apply to filters",
+                name
+            ),
+        });
+    }
+    for filter_layer in &filter_layers {
+        check_properties(filter_layer, &mut result);
+        match &filter_layer.replacements {
+            Some(replacements) => check_region_op(filter_layer, replacements, &mut result),
+            None if filter_layer.properties.contains_key("connectivity") => {
+                check_connectivity(filter_layer, &mut result)
+            }
+            None => check_segments(filter_layer, &mut result)?,
+        }
+    }
+    Ok(result)
+}
+
+fn check_properties(filter_layer: &FilterLayer, result: &mut Vec<Diagnostic>) {
+    let mut names: Vec<_> = filter_layer.properties.keys().collect();
+    names.sort();
+    for name in names {
+        if let Some((severity, message)) = check_property(name, &filter_layer.properties[name]) {
+            result.push(Diagnostic {
+                severity,
+                layer: Some(filter_layer.name.clone()),
+                position: None,
+                message,
+            });
+        }
+    }
+    // A distance constraint needs both the tile class and the distance
+    for (class, distance) in [
+        ("min_distance_to", "min_distance"),
+        ("max_distance_to", "max_distance"),
+    ] {
+        let (has_class, has_distance) = (
+            filter_layer.properties.contains_key(class),
+            filter_layer.properties.contains_key(distance),
+        );
+        if has_class != has_distance {
+            let (present, missing) = if has_class {
+                (class, distance)
+            } else {
+                (distance, class)
+            };
+            result.push(Diagnostic {
+                severity: Severity::Warning,
+                layer: Some(filter_layer.name.clone()),
+                position: None,
+                message: format!("'{}' is ignored without '{}'", present, missing),
+            });
+        }
+    }
+    // Rules that can always match again only stop at the default step limit
+    let markov = FilterProperties::from(filter_layer).mode == ApplyMode::Markov;
+    if markov && !filter_layer.properties.contains_key("max_steps") {
+        result.push(Diagnostic {
+            severity: Severity::Warning,
+            layer: Some(filter_layer.name.clone()),
+            position: None,
+            message: String::from(
+                "markov mode without 'max_steps' stops after as many steps as the map has fields",
+            ),
+        });
+    }
+}
+
+fn check_property(name: &str, value: &PropertyValue) -> Option<(Severity, String)> {
+    match parse_layer_property(name, value) {
+        Ok(true) => None,
+        Ok(false) => Some((Severity::Warning, format!("unknown property '{}'", name))),
+        Err(message) => Some((Severity::Error, message)),
+    }
+}
+
+fn check_region_op(
+    filter_layer: &FilterLayer,
+    replacements: &Map<TiledTile>,
+    result: &mut Vec<Diagnostic>,
+) {
+    let empty = TiledTile::default();
+    for (layer, map) in [("tiles", &filter_layer.map), ("replacements", replacements)] {
+        if map.data().iter().all(|tile| tile == &empty) {
+            result.push(Diagnostic {
+                severity: Severity::Error,
+                layer: Some(filter_layer.name.clone()),
+                position: None,
+                message: format!("region operation '{}' layer contains no tiles", layer),
+            });
+        }
+    }
+}
+
+fn check_connectivity(filter_layer: &FilterLayer, result: &mut Vec<Diagnostic>) {
+    let diagnostic = |message| Diagnostic {
+        severity: Severity::Error,
+        layer: Some(filter_layer.name.clone()),
+        position: None,
+        message,
+    };
+
+    if !filter_layer.properties.contains_key("walkable") {
+        result.push(diagnostic(String::from(
+            "connectivity layer has no 'walkable' tile class",
+        )));
+    }
+    let carve = matches!(
+        filter_layer.properties.get("connectivity"),
+        Some(PropertyValue::StringValue(fix)) if fix == "carve"
+    );
+    if carve
+        && filter_layer
+            .map
+            .data()
+            .iter()
+            .all(|tile| tile == &TiledTile::default())
+    {
+        result.push(diagnostic(String::from(
+            "connectivity layer contains no carve tile",
+        )));
+    }
+}
+
+fn check_segments(
+    filter_layer: &FilterLayer,
+    result: &mut Vec<Diagnostic>,
+) -> Result<(), TisuError> {
+    let map = &filter_layer.map;
+    let diagnostic = |severity, position, message| Diagnostic {
+        severity,
+        layer: Some(filter_layer.name.clone()),
+        position,
+        message,
+    };
+
+    let segments = map_segmenter::extract_segments(map, &TiledTile::default());
+    if segments.is_empty() {
+        result.push(diagnostic(
+            Severity::Warning,
+            None,
+            String::from("layer contains no filters"),
+        ));
+        return Ok(());
+    }
+
+    let mut idx = 0;
+    if segments.len() % 2 != 0 {
+        if segments[0].size() != Vector2::one() {
+            result.push(diagnostic(
+                Severity::Error,
+                Some(segments[0].position()),
+                format!(
+                    "odd number of segments, but the first one is {}x{} and can't be a \
+                     wildcard; either the wildcard must be 1x1 or a pattern is missing its \
+                     substitute",
+                    segments[0].size().x,
+                    segments[0].size().y
+                ),
+            ));
+        }
+        idx = 1;
+    }
+
+    let mut filters: Vec<(Rect2u, Map<TiledTile>, Map<TiledTile>)> = vec![];
+    let mut substitute_rects = vec![];
+    while idx < segments.len() - 1 {
+        let pattern_rect = segments[idx];
+        let substitute_rect = segments[idx + 1];
+        idx += 2;
+        if pattern_rect.size() != substitute_rect.size() {
+            result.push(diagnostic(
+                Severity::Error,
+                Some(pattern_rect.position()),
+                format!(
+                    "pattern is {}x{}, but its substitute at ({}, {}) is {}x{}",
+                    pattern_rect.size().x,
+                    pattern_rect.size().y,
+                    substitute_rect.position().x,
+                    substitute_rect.position().y,
+                    substitute_rect.size().x,
+                    substitute_rect.size().y
+                ),
+            ));
+            continue;
+        }
+
+        substitute_rects.push(substitute_rect);
+        let pattern = map.extract_segment(pattern_rect)?;
+        let substitute = map.extract_segment(substitute_rect)?;
+        if pattern == substitute {
+            result.push(diagnostic(
+                Severity::Warning,
+                Some(pattern_rect.position()),
+                String::from("pattern and substitute are equal, the filter has no effect"),
+            ));
+        }
+        for (other_rect, other_pattern, other_substitute) in &filters {
+            if &pattern != other_pattern {
+                continue;
+            }
+            let message = if &substitute == other_substitute {
+                format!(
+                    "filter duplicates the filter at ({}, {})",
+                    other_rect.position().x,
+                    other_rect.position().y
+                )
+            } else {
+                format!(
+                    "filter has the same pattern as the filter at ({}, {}) and shadows it",
+                    other_rect.position().x,
+                    other_rect.position().y
+                )
+            };
+            result.push(diagnostic(
+                Severity::Warning,
+                Some(pattern_rect.position()),
+                message,
+            ));
+        }
+        filters.push((pattern_rect, pattern, substitute));
+    }
+
+    for object in &filter_layer.objects {
+        if substitute_rects
+            .iter()
+            .all(|rect| substitute_objects(std::slice::from_ref(object), *rect).is_empty())
+        {
+            result.push(diagnostic(
+                Severity::Warning,
+                Some(Vector2::new(
+                    object.position.0.max(0.0) as u32,
+                    object.position.1.max(0.0) as u32,
+                )),
+                format!(
+                    "object '{}' is not on a substitute and is never emitted",
+                    object.name
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer_diagnostics<'a>(diagnostics: &'a [Diagnostic], layer: &str) -> Vec<&'a Diagnostic> {
+        diagnostics
+            .iter()
+            .filter(|d| d.layer.as_deref() == Some(layer))
+            .collect()
+    }
+
+    #[test]
+    fn test_check_property() {
+        assert_eq!(
+            check_property("probability", &PropertyValue::FloatValue(0.5)),
+            None
+        );
+        assert!(check_property("probability", &PropertyValue::FloatValue(1.5)).is_some());
+        assert!(check_property("probability", &PropertyValue::IntValue(1)).is_some());
+        assert_eq!(
+            check_property(
+                "pattern_matching",
+                &PropertyValue::StringValue(String::from("destination"))
+            ),
+            None
+        );
+        assert!(check_property(
+            "pattern_matching",
+            &PropertyValue::StringValue(String::from("dest"))
+        )
+        .is_some());
+        assert!(check_property("iterations", &PropertyValue::IntValue(-1)).is_some());
+        assert!(
+            check_property("mode", &PropertyValue::StringValue(String::from("random"))).is_some()
+        );
+        assert!(check_property("max_steps", &PropertyValue::IntValue(-1)).is_some());
+        assert!(check_property("sentinel", &PropertyValue::IntValue(1)).is_some());
+        assert!(check_property(
+            "region_op",
+            &PropertyValue::StringValue(String::from("fill"))
+        )
+        .is_some());
+        assert!(check_property("neighborhood", &PropertyValue::IntValue(6)).is_some());
+        assert_eq!(
+            check_property("neighborhood", &PropertyValue::IntValue(8)),
+            None
+        );
+        assert!(check_property("max_size", &PropertyValue::IntValue(-1)).is_some());
+        assert!(check_property(
+            "connectivity",
+            &PropertyValue::StringValue(String::from("fix"))
+        )
+        .is_some());
+        assert!(check_property("walkable", &PropertyValue::BoolValue(true)).is_some());
+        assert!(check_property("min_distance", &PropertyValue::FloatValue(-1.0)).is_some());
+        assert_eq!(
+            check_property("max_distance", &PropertyValue::IntValue(3)),
+            None
+        );
+        assert!(check_property(
+            "distance_metric",
+            &PropertyValue::StringValue(String::from("euclidean"))
+        )
+        .is_some());
+        assert_eq!(
+            check_property("ignore", &PropertyValue::BoolValue(true)),
+            None
+        );
+        assert!(check_property("ignored", &PropertyValue::BoolValue(true)).is_some());
+    }
+
+    #[test]
+    fn test_check() {
+        let result = check(
+            format!(
+                "{}/data/test_check/filter_collection.tmx",
+                env!("CARGO_MANIFEST_DIR"),
+            )
+            .as_str(),
+        );
+
+        assert!(result.is_ok());
+        let diagnostics = result.unwrap();
+        assert_eq!(diagnostics.len(), 6);
+        assert_eq!(diagnostics[0].layer, None);
+
+        let bad_wildcard = layer_diagnostics(&diagnostics, "Bad Wildcard");
+        assert_eq!(bad_wildcard.len(), 1);
+        assert_eq!(bad_wildcard[0].severity, Severity::Error);
+        assert_eq!(bad_wildcard[0].position, Some((1, 1).into()));
+
+        let size_mismatch = layer_diagnostics(&diagnostics, "Size Mismatch");
+        assert_eq!(size_mismatch.len(), 1);
+        assert_eq!(size_mismatch[0].severity, Severity::Error);
+        assert_eq!(size_mismatch[0].position, Some((1, 1).into()));
+
+        let duplicates = layer_diagnostics(&diagnostics, "Duplicates");
+        assert_eq!(duplicates.len(), 3);
+        assert_eq!(duplicates[0].message, "'iterations' must be an int");
+        assert_eq!(duplicates[1].severity, Severity::Warning);
+        assert_eq!(duplicates[1].message, "unknown property 'probabilty'");
+        assert_eq!(duplicates[2].severity, Severity::Warning);
+        assert_eq!(duplicates[2].position, Some((1, 3).into()));
+    }
+
+    #[test]
+    fn test_check_objects() {
+        let result = check(
+            format!(
+                "{}/data/test_objects/filter_collection.tmx",
+                env!("CARGO_MANIFEST_DIR"),
+            )
+            .as_str(),
+        );
+
+        assert!(result.is_ok());
+        let diagnostics = result.unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].position, Some((0, 2).into()));
+        assert_eq!(
+            diagnostics[0].message,
+            "object 'Stray' is not on a substitute and is never emitted"
+        );
+    }
+
+    #[test]
+    fn test_check_region_op() {
+        let result = check(
+            format!(
+                "{}/data/test_region_op/filter_collection.tmx",
+                env!("CARGO_MANIFEST_DIR"),
+            )
+            .as_str(),
+        );
+
+        assert_eq!(result, Ok(vec![]));
+    }
+
+    #[test]
+    fn test_check_connectivity() {
+        let result = check(
+            format!(
+                "{}/data/test_connectivity/filter_collection.tmx",
+                env!("CARGO_MANIFEST_DIR"),
+            )
+            .as_str(),
+        );
+
+        assert_eq!(result, Ok(vec![]));
+    }
+
+    #[test]
+    fn test_check_distance() {
+        let result = check(
+            format!(
+                "{}/data/test_distance/filter_collection.tmx",
+                env!("CARGO_MANIFEST_DIR"),
+            )
+            .as_str(),
+        );
+
+        assert_eq!(result, Ok(vec![]));
+    }
+
+    #[test]
+    fn test_check_markov() {
+        let mut filter_layer = FilterLayer {
+            name: String::from("Walk"),
+            properties: [(
+                String::from("mode"),
+                PropertyValue::StringValue(String::from("markov")),
+            )]
+            .into(),
+            visible: true,
+            map: Map::new((3, 3).into()),
+            objects: vec![],
+            replacements: None,
+        };
+        let mut result = vec![];
+
+        check_properties(&filter_layer, &mut result);
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].severity, Severity::Warning);
+
+        filter_layer
+            .properties
+            .insert(String::from("max_steps"), PropertyValue::IntValue(100));
+        result.clear();
+        check_properties(&filter_layer, &mut result);
+        assert_eq!(result, vec![]);
+    }
+
+    #[test]
+    fn test_check_failure() {
+        let result = check("non_existent.tmx");
+
+        assert_eq!(result.err().unwrap(), TisuError::InvalidArgument);
+    }
+}
@@ -0,0 +1,380 @@
+use crate::{
+    filter::FilterCollection,
+    filter_exporter::FilterExporter,
+    map::Map,
+    map_object::MapObject,
+    tiled_map_exporter::{end_map, start_map, write_object_layer, write_tile_layer},
+    tiled_tile::TiledTile,
+    tisu_error::TisuError,
+    vector2::{Vector2, Vector2u},
+};
+
+/// Returns the wildcard of the given filter collection (the wildcard of its
+/// first filter).
+fn collection_wildcard(filter_collection: &FilterCollection<TiledTile>) -> TiledTile {
+    filter_collection
+        .filters
+        .first()
+        .map(|filter| filter.wildcard().clone())
+        .unwrap_or_default()
+}
+
+/// Returns the positions of the pattern and the substitute of each filter of
+/// the given filter collection in its layout (see `layout_filter_collection`).
+fn segment_positions(filter_collection: &FilterCollection<TiledTile>) -> Vec<(Vector2u, Vector2u)> {
+    let mut y = 1;
+    if collection_wildcard(filter_collection) != TiledTile::default() {
+        y += 2;
+    }
+
+    let mut result = vec![];
+    for filter in &filter_collection.filters {
+        let substitute_x = filter.pattern().size().x + 2;
+        result.push(((1, y).into(), (substitute_x, y).into()));
+        y += filter.pattern().size().y + 1;
+    }
+    result
+}
+
+/// Lays out the filters of the given filter collection on a single map, the
+/// way they're expected by the `TiledFilterImporter`: an optional 1x1
+/// wildcard segment first, followed by pattern/substitute pairs, each pair in
+/// its own row and all segments separated by transparent fields.
+///
+/// # Errors
+///
+/// Returns an error if the filters have different wildcards or if a pattern
+/// or substitute contains transparent fields, which can't be represented.
+fn layout_filter_collection(
+    filter_collection: &FilterCollection<TiledTile>,
+    size: Vector2u,
+) -> Result<Map<TiledTile>, TisuError> {
+    let mut map = Map::<TiledTile>::new(size);
+
+    let wildcard = collection_wildcard(filter_collection);
+    if wildcard != TiledTile::default() {
+        map.set((1, 1).into(), wildcard.clone())?;
+    }
+
+    for (filter, (pattern_position, substitute_position)) in filter_collection
+        .filters
+        .iter()
+        .zip(segment_positions(filter_collection))
+    {
+        if filter.wildcard() != &wildcard {
+            return Err(TisuError::InvalidArgument);
+        }
+        blit(&mut map, filter.pattern(), pattern_position)?;
+        blit(&mut map, filter.substitute(), substitute_position)?;
+    }
+
+    Ok(map)
+}
+
+/// Returns the objects emitted by the filters of the given filter collection,
+/// positioned on their substitutes in its layout.
+fn layout_objects(filter_collection: &FilterCollection<TiledTile>) -> Vec<MapObject> {
+    filter_collection
+        .filters
+        .iter()
+        .zip(segment_positions(filter_collection))
+        .flat_map(|(filter, (_, substitute_position))| {
+            let offset = (substitute_position.x as f32, substitute_position.y as f32);
+            filter
+                .objects()
+                .iter()
+                .map(move |object| object.translated(offset))
+        })
+        .collect()
+}
+
+fn blit(
+    map: &mut Map<TiledTile>,
+    segment: &Map<TiledTile>,
+    position: Vector2u,
+) -> Result<(), TisuError> {
+    for x in 0..segment.size().x {
+        for y in 0..segment.size().y {
+            let field = segment.get((x, y).into())?;
+            if field == &TiledTile::default() {
+                return Err(TisuError::InvalidArgument);
+            }
+            map.set(position + (x, y).into(), field.clone())?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the map size needed to lay out the given filter collection.
+fn layout_size(filter_collection: &FilterCollection<TiledTile>) -> Vector2u {
+    let mut size = Vector2::new(3, 1);
+    for filter in &filter_collection.filters {
+        let pattern_size = filter.pattern().size();
+        size.x = size.x.max(2 * pattern_size.x + 3);
+        size.y += pattern_size.y + 1;
+    }
+    // Leave room for the wildcard
+    size.y += 2;
+    size
+}
+
+pub struct TiledFilterExporter;
+
+impl FilterExporter for TiledFilterExporter {
+    type TileType = TiledTile;
+
+    fn save(
+        file: impl AsRef<std::path::Path>,
+        filter_collections: &[FilterCollection<Self::TileType>],
+        tile_size: Vector2u,
+        tileset_path: impl AsRef<std::path::Path>,
+    ) -> Result<(), TisuError> {
+        // Region operations and connectivity steps are only written by the
+        // structured exporters, alternative fields and distance constraints
+        // (resolved from tile classes and properties) by none
+        if filter_collections.iter().any(|filter_collection| {
+            filter_collection.region_op.is_some()
+                || filter_collection.connectivity.is_some()
+                || filter_collection.has_alternatives()
+                || filter_collection.has_distance_constraints()
+        }) {
+            return Err(TisuError::InvalidArgument);
+        }
+        let mut size = Vector2u::new(1, 1);
+        for filter_collection in filter_collections {
+            let layout_size = layout_size(filter_collection);
+            size.x = size.x.max(layout_size.x);
+            size.y = size.y.max(layout_size.y);
+        }
+
+        let objects: Vec<_> = filter_collections.iter().map(layout_objects).collect();
+        let object_layer_count = objects.iter().filter(|objects| !objects.is_empty()).count();
+        let layer_count = (filter_collections.len() + object_layer_count) as u32;
+        let object_count = objects.iter().map(Vec::len).sum::<usize>() as u32;
+        let mut writer = start_map(
+            file,
+            size,
+            tile_size,
+            tileset_path,
+            layer_count,
+            object_count,
+        )?;
+        let mut layer_id = 1;
+        let mut object_id = 1;
+        // The importer processes layers in reverse order
+        for (idx, filter_collection) in filter_collections.iter().enumerate().rev() {
+            let map = layout_filter_collection(filter_collection, size)?;
+            let name = if filter_collection.name.is_empty() {
+                format!("Filter Collection {}", idx + 1)
+            } else {
+                filter_collection.name.clone()
+            };
+            write_tile_layer(
+                &mut writer,
+                layer_id,
+                &name,
+                &map,
+                &(&filter_collection.properties).into(),
+            )?;
+            layer_id += 1;
+            // Objects are assigned to the filter collection with the same name
+            if !objects[idx].is_empty() {
+                write_object_layer(
+                    &mut writer,
+                    layer_id,
+                    &name,
+                    &objects[idx],
+                    tile_size,
+                    object_id,
+                )?;
+                layer_id += 1;
+                object_id += objects[idx].len() as u32;
+            }
+        }
+        end_map(&mut writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        filter::{Filter, FilterProperties},
+        filter_importer::FilterImporter,
+        map_object::ObjectShape,
+        map_segmenter,
+        tiled_filter_importer::TiledFilterImporter,
+    };
+
+    use super::*;
+
+    fn create_tiled_map<const N: usize, const M: usize>(data: [[u32; N]; M]) -> Map<TiledTile> {
+        Map::<TiledTile>::from_data(data.map(|x| {
+            x.map(|x| TiledTile {
+                index: Some(x),
+                ..Default::default()
+            })
+        }))
+        .unwrap()
+    }
+
+    fn create_filter_collection(wildcard: u32) -> FilterCollection<TiledTile> {
+        let wildcard = TiledTile {
+            index: Some(wildcard),
+            ..Default::default()
+        };
+        let filter1 = Filter::new(
+            create_tiled_map([[0, 1]]),
+            create_tiled_map([[1, 1]]),
+            wildcard.clone(),
+        )
+        .unwrap();
+        let filter2 = Filter::new(
+            create_tiled_map([[2, 2], [2, 2]]),
+            create_tiled_map([[4, 3], [4, 4]]),
+            wildcard,
+        )
+        .unwrap();
+        FilterCollection::new(&[filter1, filter2])
+    }
+
+    #[test]
+    fn test_layout_filter_collection() {
+        let filter_collection = create_filter_collection(4);
+
+        let map = layout_filter_collection(&filter_collection, layout_size(&filter_collection));
+
+        assert!(map.is_ok());
+        let map = map.unwrap();
+        let segments = map_segmenter::extract_segments(&map, &TiledTile::default());
+        assert_eq!(
+            segments,
+            [
+                (1, 1, 1, 1).try_into().unwrap(),
+                (1, 3, 2, 1).try_into().unwrap(),
+                (4, 3, 2, 1).try_into().unwrap(),
+                (1, 5, 2, 2).try_into().unwrap(),
+                (4, 5, 2, 2).try_into().unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_layout_filter_collection_failure() {
+        let mut filter_collection = create_filter_collection(4);
+        filter_collection.push(
+            Filter::new(
+                create_tiled_map([[1]]),
+                create_tiled_map([[2]]),
+                TiledTile::default(),
+            )
+            .unwrap(),
+        );
+
+        let map = layout_filter_collection(&filter_collection, layout_size(&filter_collection));
+
+        assert_eq!(map.err().unwrap(), TisuError::InvalidArgument);
+    }
+
+    #[test]
+    fn test_save() {
+        let mut filter_collection1 = create_filter_collection(4);
+        filter_collection1.name = String::from("First");
+        filter_collection1.properties = FilterProperties {
+            probability: 0.5,
+            iterations: 2,
+            ..Default::default()
+        };
+        let mut filter_collection2 = create_filter_collection(5);
+        filter_collection2.name = String::from("Second");
+        for filter_collection in [&mut filter_collection1, &mut filter_collection2] {
+            filter_collection.filters = filter_collection
+                .filters
+                .iter()
+                .map(|filter| {
+                    Filter::new_with_properties(
+                        filter.pattern().clone(),
+                        filter.substitute().clone(),
+                        filter.wildcard().clone(),
+                        filter_collection.properties.clone(),
+                    )
+                    .unwrap()
+                })
+                .collect();
+        }
+        let filter_collections = [filter_collection1, filter_collection2];
+        let file = std::env::temp_dir().join(format!(
+            "tisu_test_tiled_filter_exporter_save_{}.tmx",
+            std::process::id()
+        ));
+
+        let result = TiledFilterExporter::save(
+            &file,
+            &filter_collections,
+            (16, 16).into(),
+            format!("{}/data/tileset.tsx", env!("CARGO_MANIFEST_DIR")),
+        );
+
+        assert!(result.is_ok());
+        let loaded = TiledFilterImporter::load(&file);
+        _ = std::fs::remove_file(&file);
+        assert!(loaded.is_ok());
+        assert_eq!(loaded.unwrap(), filter_collections);
+    }
+
+    #[test]
+    fn test_save_objects() {
+        let mut filter_collection = create_filter_collection(4);
+        filter_collection.name = String::from("Chests");
+        filter_collection.filters[1].set_objects(vec![MapObject {
+            name: String::from("Chest"),
+            class: String::from("chest"),
+            position: (1.5, 0.5),
+            shape: ObjectShape::Rect {
+                width: 0.5,
+                height: 0.5,
+            },
+            rotation: 0.0,
+            properties: [(String::from("gold"), tiled::PropertyValue::IntValue(10))].into(),
+        }]);
+        let filter_collections = [filter_collection];
+        let file = std::env::temp_dir().join(format!(
+            "tisu_test_tiled_filter_exporter_save_objects_{}.tmx",
+            std::process::id()
+        ));
+
+        let result = TiledFilterExporter::save(
+            &file,
+            &filter_collections,
+            (16, 16).into(),
+            format!("{}/data/tileset.tsx", env!("CARGO_MANIFEST_DIR")),
+        );
+
+        assert!(result.is_ok());
+        let loaded = TiledFilterImporter::load(&file);
+        _ = std::fs::remove_file(&file);
+        assert!(loaded.is_ok());
+        assert_eq!(loaded.unwrap(), filter_collections);
+    }
+
+    #[test]
+    fn test_save_failure() {
+        let mut filter_collection = create_filter_collection(4);
+        filter_collection.filters[0]
+            .set_alternatives((0, 0).into(), vec![TiledTile::default()])
+            .unwrap();
+        let file = std::env::temp_dir().join(format!(
+            "tisu_test_tiled_filter_exporter_save_failure_{}.tmx",
+            std::process::id()
+        ));
+
+        let result = TiledFilterExporter::save(
+            &file,
+            &[filter_collection],
+            (16, 16).into(),
+            format!("{}/data/tileset.tsx", env!("CARGO_MANIFEST_DIR")),
+        );
+
+        assert_eq!(result.err(), Some(TisuError::InvalidArgument));
+        assert!(!file.exists());
+    }
+}
@@ -1,53 +1,444 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
 use tiled::Loader;
 
 use crate::{
-    filter::{Filter, FilterCollection, FilterProperties},
+    connectivity::Connectivity,
+    distance::DistanceConstraint,
+    filter::{
+        bool_property, count_property, enum_property, string_property, Filter, FilterCollection,
+        FilterProperties,
+    },
+    filter_example::FilterExample,
     filter_importer::FilterImporter,
-    map_importer::MapImporter,
+    filter_loader::{self, FilterFormat},
+    layered_map::{Layer, LayerContent},
+    map::Map,
+    map_object::MapObject,
     map_segmenter,
+    rect2::Rect2u,
+    regions::{Neighborhood, RegionOp, RegionOpKind},
+    tile_metadata::{self, TilePredicate, TilesetMetadata},
     tiled_map_importer::TiledMapImporter,
     tiled_tile::TiledTile,
     tisu_error::TisuError,
     vector2::Vector2,
 };
 
-fn load_layer_properties(
-    file: impl AsRef<std::path::Path>,
-) -> Result<Vec<FilterProperties>, TisuError> {
+/// Information about a tile layer of a filter file.
+pub(crate) struct FilterLayer {
+    /// Layer name, prefixed with the names of parent group layers
+    pub name: String,
+    /// Layer properties, including the ones inherited from parent group layers
+    pub properties: tiled::Properties,
+    /// Layer visibility
+    pub visible: bool,
+    /// Layer tiles
+    pub map: Map<TiledTile>,
+    /// Objects of the object layer with the same name as the layer, emitted by
+    /// the filters whose substitutes contain them
+    pub objects: Vec<MapObject>,
+    /// Replacement tiles of a region operation group layer, whose region tiles
+    /// are in `map`
+    pub replacements: Option<Map<TiledTile>>,
+}
+
+impl From<&FilterLayer> for FilterProperties {
+    fn from(value: &FilterLayer) -> Self {
+        let mut filter_properties = FilterProperties::from(&value.properties);
+        if !value.visible {
+            filter_properties.ignore = true;
+        }
+        filter_properties
+    }
+}
+
+/// Loads the given filter file as a Tiled map.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be loaded.
+pub(crate) fn load_tmx_map(file: impl AsRef<std::path::Path>) -> Result<tiled::Map, TisuError> {
     let mut loader = Loader::new();
-    let tmx_map = loader
+    loader
         .load_tmx_map(file)
-        .map_err(|_| TisuError::InvalidArgument)?;
+        .map_err(|_| TisuError::InvalidArgument)
+}
+
+/// Loads the tile layers of the given filter map, in the order in which
+/// they're interpreted as filter collections.
+pub(crate) fn load_filter_layers(tmx_map: &tiled::Map) -> Result<Vec<FilterLayer>, TisuError> {
+    let layered_map = TiledMapImporter::load_layered_map(tmx_map, &mut HashMap::new())?;
 
     let mut result = vec![];
-    // Collect the layers into a Vec to traverse it in reverse order
-    for layer in tmx_map.layers().collect::<Vec<_>>().iter().rev() {
-        process_layer(layer, &tiled::Properties::new(), &mut result);
+    let mut objects = HashMap::new();
+    for layer in layered_map.layers.iter().rev() {
+        process_layer(
+            layer,
+            "",
+            &tiled::Properties::new(),
+            &mut result,
+            &mut objects,
+        )?;
+    }
+    for filter_layer in &mut result {
+        filter_layer.objects = objects.remove(&filter_layer.name).unwrap_or_default();
     }
     Ok(result)
 }
 
 fn process_layer(
-    layer: &tiled::Layer,
+    layer: &Layer<TiledTile>,
+    parent_name: &str,
     parent_properties: &tiled::Properties,
-    result: &mut Vec<FilterProperties>,
-) {
+    result: &mut Vec<FilterLayer>,
+    objects: &mut HashMap<String, Vec<MapObject>>,
+) -> Result<(), TisuError> {
+    let name = format!("{}{}", parent_name, layer.name);
     let properties = override_properties(parent_properties, &layer.properties);
-    match layer.layer_type() {
-        tiled::LayerType::Tiles(_) => {
-            let mut filter_properties = FilterProperties::from(&properties);
-            if !layer.visible {
-                filter_properties.ignore = true;
+    match &layer.content {
+        LayerContent::Tiles(map) => {
+            result.push(FilterLayer {
+                name,
+                properties,
+                visible: layer.visible,
+                map: map.clone(),
+                objects: vec![],
+                replacements: None,
+            });
+        }
+        LayerContent::Objects(layer_objects) => {
+            objects
+                .entry(name)
+                .or_default()
+                .extend(layer_objects.iter().cloned());
+        }
+        LayerContent::Group(children) if is_region_op_group(layer) => {
+            result.push(FilterLayer {
+                name,
+                properties,
+                visible: layer.visible,
+                map: child_tiles(children, "tiles")?,
+                objects: vec![],
+                replacements: Some(child_tiles(children, "replacements")?),
+            });
+        }
+        LayerContent::Group(children) if !is_example_group(layer) => {
+            let name = format!("{}/", name);
+            for child_layer in children.iter().rev() {
+                process_layer(child_layer, &name, &properties, result, objects)?;
             }
-            result.push(filter_properties);
         }
-        tiled::LayerType::Group(group) => {
-            for child_layer in group.layers().collect::<Vec<_>>().iter().rev() {
-                process_layer(child_layer, &properties, result);
+        LayerContent::Group(_) => (),
+    }
+    Ok(())
+}
+
+/// Returns a copy of the tiles of the topmost of the given tile layers with
+/// the given name.
+///
+/// # Errors
+///
+/// Returns an error if there's no tile layer with the given name.
+fn child_tiles(children: &[Layer<TiledTile>], name: &str) -> Result<Map<TiledTile>, TisuError> {
+    children
+        .iter()
+        .rev()
+        .filter(|layer| layer.name == name)
+        .find_map(Layer::tiles)
+        .cloned()
+        .ok_or(TisuError::NotFound)
+}
+
+/// Returns the objects inside the given substitute segment, positioned
+/// relative to the segment.
+pub(crate) fn substitute_objects(objects: &[MapObject], substitute_rect: Rect2u) -> Vec<MapObject> {
+    let position = substitute_rect.position();
+    let size = substitute_rect.size();
+    objects
+        .iter()
+        .filter(|object| {
+            let (x, y) = object.position;
+            x >= position.x as f32
+                && y >= position.y as f32
+                && x < (position.x + size.x) as f32
+                && y < (position.y + size.y) as f32
+        })
+        .map(|object| object.translated((-(position.x as f32), -(position.y as f32))))
+        .collect()
+}
+
+fn is_example_group(layer: &Layer<TiledTile>) -> bool {
+    matches!(
+        layer.properties.get("example"),
+        Some(tiled::PropertyValue::BoolValue(true))
+    )
+}
+
+fn is_region_op_group(layer: &Layer<TiledTile>) -> bool {
+    layer.properties.contains_key("region_op")
+}
+
+/// Creates the region operation of the given region operation group layer.
+/// Its `tiles` layer contains the tiles that make up the regions and its
+/// `replacements` layer the tiles that replace them.
+fn region_op_from_layer(
+    filter_layer: &FilterLayer,
+    replacements: &Map<TiledTile>,
+) -> RegionOp<TiledTile> {
+    let empty = TiledTile::default();
+    let mut tiles = vec![];
+    for tile in filter_layer.map.data() {
+        if tile != &empty && !tiles.contains(tile) {
+            tiles.push(tile.clone());
+        }
+    }
+    let replacements = replacements
+        .data()
+        .iter()
+        .filter(|tile| *tile != &empty)
+        .cloned()
+        .collect();
+
+    let mut region_op = RegionOp::new(RegionOpKind::default(), tiles, replacements);
+    for (name, value) in &filter_layer.properties {
+        // Invalid properties are reported by the filter file checker
+        _ = parse_region_op_property(&mut region_op, name, value);
+    }
+    region_op
+}
+
+/// Creates the connectivity step of the given tile layer with a
+/// `connectivity` property (`carve` or `check`). The `walkable`, `breakable`,
+/// `start` and `goal` properties name tile classes of the tileset, and the
+/// first tile of the layer is the carve tile.
+///
+/// # Errors
+///
+/// Returns an error if the layer should carve but contains no tile.
+fn connectivity_from_layer(
+    filter_layer: &FilterLayer,
+    tile_metadata: &TilesetMetadata,
+) -> Result<Connectivity<TiledTile>, TisuError> {
+    let match_flips = FilterProperties::from(filter_layer).match_flips;
+    let class_tiles = |class: &String| {
+        TilePredicate {
+            class: Some(class.clone()),
+            ..Default::default()
+        }
+        .matching_tiles(tile_metadata, &match_flips)
+    };
+
+    let mut connectivity = Connectivity::new(vec![]);
+    for (name, value) in &filter_layer.properties {
+        // Invalid properties are reported by the filter file checker
+        _ = parse_connectivity_property(&mut connectivity, name, value, &class_tiles);
+    }
+    if connectivity.carve.is_some() {
+        let empty = TiledTile::default();
+        let carve = filter_layer.map.data().iter().find(|tile| *tile != &empty);
+        connectivity.carve = Some(carve.ok_or(TisuError::NotFound)?.clone());
+    }
+    Ok(connectivity)
+}
+
+/// Sets the region operation property with the given name (`region_op`,
+/// `neighborhood`, `min_size` or `max_size`) to the given value. Returns
+/// `false` if there's no region operation property with the name.
+///
+/// # Errors
+///
+/// Returns a description of the problem if the value has the wrong type or is
+/// invalid.
+fn parse_region_op_property(
+    region_op: &mut RegionOp<TiledTile>,
+    name: &str,
+    value: &tiled::PropertyValue,
+) -> Result<bool, String> {
+    match name {
+        "region_op" => region_op.kind = enum_property(name, value, "'replace' or 'keep_largest'")?,
+        "neighborhood" => region_op.neighborhood = neighborhood_property(name, value)?,
+        "min_size" => region_op.min_size = Some(count_property(name, value)? as usize),
+        "max_size" => region_op.max_size = Some(count_property(name, value)? as usize),
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// Sets the connectivity step property with the given name (`connectivity`,
+/// `neighborhood`, or the `walkable`, `breakable`, `start` and `goal` tile
+/// classes, whose tiles are returned by the given function) to the given
+/// value. With `connectivity = carve`, the carve tile is set to an empty tile,
+/// to be replaced by the tile of the layer. Returns `false` if there's no
+/// connectivity step property with the name.
+///
+/// # Errors
+///
+/// Returns a description of the problem if the value has the wrong type or is
+/// invalid.
+fn parse_connectivity_property(
+    connectivity: &mut Connectivity<TiledTile>,
+    name: &str,
+    value: &tiled::PropertyValue,
+    class_tiles: &dyn Fn(&String) -> Vec<TiledTile>,
+) -> Result<bool, String> {
+    match name {
+        "connectivity" => {
+            connectivity.carve = match string_property(name, value)?.as_str() {
+                "carve" => Some(TiledTile::default()),
+                "check" => None,
+                p => {
+                    return Err(format!(
+                        "'connectivity' must be 'carve' or 'check', found '{}'",
+                        p
+                    ))
+                }
             }
         }
-        _ => (),
+        "neighborhood" => connectivity.neighborhood = neighborhood_property(name, value)?,
+        "walkable" => connectivity.walkable = class_tiles(string_property(name, value)?),
+        "breakable" => connectivity.breakable = class_tiles(string_property(name, value)?),
+        "start" => connectivity.start = class_tiles(string_property(name, value)?),
+        "goal" => connectivity.goal = class_tiles(string_property(name, value)?),
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn neighborhood_property(name: &str, value: &tiled::PropertyValue) -> Result<Neighborhood, String> {
+    match value {
+        tiled::PropertyValue::IntValue(p) => Neighborhood::try_from(*p)
+            .map_err(|_| format!("'{}' must be 4 or 8, found {}", name, p)),
+        _ => Err(format!("'{}' must be an int", name)),
+    }
+}
+
+/// Parses the layer property with the given name the way the filter import
+/// does: as a filter property, a region operation or connectivity step
+/// property or the `example` property of example group layers. Returns
+/// `false` if the property is unknown.
+///
+/// # Errors
+///
+/// Returns a description of the problem if the value has the wrong type or is
+/// invalid.
+pub(crate) fn parse_layer_property(
+    name: &str,
+    value: &tiled::PropertyValue,
+) -> Result<bool, String> {
+    if name == "example" {
+        return bool_property(name, value).map(|_| true);
     }
+    let mut region_op = RegionOp::new(RegionOpKind::default(), vec![], vec![]);
+    Ok(FilterProperties::default().parse_property(name, value)?
+        || parse_region_op_property(&mut region_op, name, value)?
+        || parse_connectivity_property(&mut Connectivity::new(vec![]), name, value, &|_| vec![])?)
+}
+
+fn process_example_layer(
+    layer: &Layer<TiledTile>,
+    result: &mut Vec<FilterExample<TiledTile>>,
+) -> Result<(), TisuError> {
+    if let Some(children) = layer.children() {
+        if is_example_group(layer) {
+            result.push(FilterExample {
+                name: layer.name.clone(),
+                input: child_tiles(children, "input")?,
+                expected_output: child_tiles(children, "expected_output")?,
+            });
+        } else {
+            for child_layer in children {
+                process_example_layer(child_layer, result)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the paths of the filter files included by the given filter map.
+fn include_paths(tmx_map: &tiled::Map) -> Result<Vec<PathBuf>, TisuError> {
+    let mut names: Vec<_> = tmx_map
+        .properties
+        .keys()
+        .filter(|name| name.starts_with("include"))
+        .collect();
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| match &tmx_map.properties[name] {
+            tiled::PropertyValue::FileValue(path) | tiled::PropertyValue::StringValue(path) => {
+                Ok(PathBuf::from(path))
+            }
+            _ => Err(TisuError::InvalidArgument),
+        })
+        .collect()
+}
+
+/// Returns the metadata of the tiles of the tileset used by the given filter
+/// map.
+fn tileset_metadata(tmx_map: &tiled::Map) -> TilesetMetadata {
+    tmx_map
+        .tilesets()
+        .first()
+        .map(|tileset| tile_metadata::from_tileset(tileset))
+        .unwrap_or_default()
+}
+
+/// Makes the predicate tiles in the pattern of the given filter match the
+/// tiles that satisfy their predicates (see `TilePredicate`).
+fn set_predicate_alternatives(
+    filter: &mut Filter<TiledTile>,
+    tile_metadata: &TilesetMetadata,
+) -> Result<(), TisuError> {
+    let size = filter.pattern().size();
+    for x in 0..size.x {
+        for y in 0..size.y {
+            let point = Vector2::new(x, y);
+            let predicate = filter
+                .pattern()
+                .get(point)?
+                .index
+                .and_then(|index| tile_metadata.get(&index))
+                .and_then(TilePredicate::from_predicate_tile);
+            if let Some(predicate) = predicate {
+                let tiles =
+                    predicate.matching_tiles(tile_metadata, &filter.properties().match_flips);
+                filter.set_alternatives(point, tiles)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Makes the pattern of the given filter only match at the distances from the
+/// tile classes set in its `min_distance_to` and `max_distance_to` properties.
+/// A class without its distance is ignored.
+fn set_distance_constraints(filter: &mut Filter<TiledTile>, tile_metadata: &TilesetMetadata) {
+    let properties = filter.properties();
+    let class_tiles = |class: &String| {
+        TilePredicate {
+            class: Some(class.clone()),
+            ..Default::default()
+        }
+        .matching_tiles(tile_metadata, &properties.match_flips)
+    };
+    let mut constraints = vec![];
+    if let (Some(class), Some(min)) = (&properties.min_distance_to, properties.min_distance) {
+        constraints.push(DistanceConstraint {
+            tiles: class_tiles(class),
+            min: Some(min),
+            max: None,
+        });
+    }
+    if let (Some(class), Some(max)) = (&properties.max_distance_to, properties.max_distance) {
+        constraints.push(DistanceConstraint {
+            tiles: class_tiles(class),
+            min: None,
+            max: Some(max),
+        });
+    }
+    filter.set_distance_constraints(constraints);
 }
 
 fn override_properties(
@@ -63,23 +454,64 @@ fn override_properties(
 
 pub struct TiledFilterImporter;
 
-impl FilterImporter for TiledFilterImporter {
-    type TileType = TiledTile;
-
-    fn load(
+impl TiledFilterImporter {
+    /// Loads the filter examples from the given filter file. Filter examples are
+    /// group layers with the `example` property set to `true`, containing an
+    /// `input` and an `expected_output` tile layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be loaded or if an example group
+    /// layer is missing the input or the expected output layer.
+    pub fn load_examples(
         file: impl AsRef<std::path::Path>,
-    ) -> Result<Vec<FilterCollection<Self::TileType>>, TisuError> {
-        let load_result = TiledMapImporter::load(&file)?;
-        let layer_properties = load_layer_properties(&file)?;
+    ) -> Result<Vec<FilterExample<TiledTile>>, TisuError> {
+        let tmx_map = load_tmx_map(file)?;
+        let layered_map = TiledMapImporter::load_layered_map(&tmx_map, &mut HashMap::new())?;
 
-        if load_result.map_layers.len() != layer_properties.len() {
-            return Err(TisuError::Unexpected);
+        let mut result = vec![];
+        for layer in &layered_map.layers {
+            process_example_layer(layer, &mut result)?;
         }
+        Ok(result)
+    }
+
+    /// Loads the filter collections of the given file, along with the paths of
+    /// the filter files it includes. Included files are listed in map
+    /// properties whose names start with `include`, in the order of their
+    /// names. Objects of an object layer named like a filter layer are emitted
+    /// by the filters whose substitutes contain them. Predicate tiles in
+    /// patterns match the tiles of the tileset that satisfy their predicates,
+    /// and the `min_distance_to` and `max_distance_to` properties refer to
+    /// tile classes of the tileset. Group layers with a `region_op` property
+    /// are loaded as region operations (see `RegionOp`) and tile layers with
+    /// a `connectivity` property as connectivity steps (see `Connectivity`).
+    pub(crate) fn load_file(
+        file: impl AsRef<std::path::Path>,
+    ) -> Result<(Vec<FilterCollection<TiledTile>>, Vec<PathBuf>), TisuError> {
+        let tmx_map = load_tmx_map(file)?;
+        let filter_layers = load_filter_layers(&tmx_map)?;
+        let tile_metadata = tileset_metadata(&tmx_map);
 
-        let mut filter_collections = Vec::<FilterCollection<Self::TileType>>::new();
-        for (layer, properties) in load_result.map_layers.iter().zip(layer_properties.iter()) {
+        let mut filter_collections = Vec::<FilterCollection<TiledTile>>::new();
+        for filter_layer in &filter_layers {
+            let layer = &filter_layer.map;
+            let properties = FilterProperties::from(filter_layer);
             let mut filter_collection =
-                FilterCollection::<Self::TileType>::new_with_properties(&[], properties.clone());
+                FilterCollection::<TiledTile>::new_with_properties(&[], properties.clone());
+            filter_collection.name = filter_layer.name.clone();
+            if let Some(replacements) = &filter_layer.replacements {
+                filter_collection.region_op =
+                    Some(region_op_from_layer(filter_layer, replacements));
+                filter_collections.push(filter_collection);
+                continue;
+            }
+            if filter_layer.properties.contains_key("connectivity") {
+                filter_collection.connectivity =
+                    Some(connectivity_from_layer(filter_layer, &tile_metadata)?);
+                filter_collections.push(filter_collection);
+                continue;
+            }
             let segments = map_segmenter::extract_segments(layer, &TiledTile::default());
             if !segments.is_empty() {
                 let mut idx = 0;
@@ -98,19 +530,33 @@ impl FilterImporter for TiledFilterImporter {
                     let substitute_rect = segments[idx + 1];
                     let pattern = layer.extract_segment(pattern_rect)?;
                     let substitute = layer.extract_segment(substitute_rect)?;
-                    let filter = Filter::new_with_properties(
+                    let mut filter = Filter::new_with_properties(
                         pattern,
                         substitute,
                         wildcard.clone(),
                         properties.clone(),
                     )?;
+                    filter.set_field_orientation();
+                    filter.set_objects(substitute_objects(&filter_layer.objects, substitute_rect));
+                    set_predicate_alternatives(&mut filter, &tile_metadata)?;
+                    set_distance_constraints(&mut filter, &tile_metadata);
                     filter_collection.push(filter);
                     idx += 2;
                 }
             }
             filter_collections.push(filter_collection);
         }
-        Ok(filter_collections)
+        Ok((filter_collections, include_paths(&tmx_map)?))
+    }
+}
+
+impl FilterImporter for TiledFilterImporter {
+    type TileType = TiledTile;
+
+    fn load(
+        file: impl AsRef<std::path::Path>,
+    ) -> Result<Vec<FilterCollection<Self::TileType>>, TisuError> {
+        filter_loader::load_with_includes(file, FilterFormat::Tiled)
     }
 }
 
@@ -118,7 +564,7 @@ impl FilterImporter for TiledFilterImporter {
 mod tests {
     use tiled::PropertyValue;
 
-    use crate::{filter::PatternMatching, map::Map};
+    use crate::{distance::DistanceMetric, filter::PatternMatching, map::Map};
 
     use super::*;
 
@@ -163,17 +609,17 @@ mod tests {
     }
 
     #[test]
-    fn test_load_layer_properties() {
-        let result = load_layer_properties(
-            format!(
-                "{}/data/test_apply_filter_collection_pattern_matching/filter_collection.tmx",
-                env!("CARGO_MANIFEST_DIR"),
-            )
-            .as_str(),
-        );
+    fn test_load_filter_layers() {
+        let tmx_map = load_tmx_map(format!(
+            "{}/data/test_apply_filter_collection_pattern_matching/filter_collection.tmx",
+            env!("CARGO_MANIFEST_DIR"),
+        ))
+        .unwrap();
+
+        let result = load_filter_layers(&tmx_map);
 
         assert!(result.is_ok());
-        let result = result.unwrap();
+        let result: Vec<_> = result.unwrap().iter().map(FilterProperties::from).collect();
         assert_eq!(result.len(), 2);
         assert_eq!(
             result[0],
@@ -221,5 +667,201 @@ mod tests {
         assert_eq!(filters[2], filter3);
     }
 
+    #[test]
+    fn test_load_region_op() {
+        let filter_collections = TiledFilterImporter::load(
+            format!(
+                "{}/data/test_region_op/filter_collection.tmx",
+                env!("CARGO_MANIFEST_DIR"),
+            )
+            .as_str(),
+        );
+
+        assert!(filter_collections.is_ok());
+        let filter_collections = &filter_collections.unwrap();
+        assert_eq!(filter_collections.len(), 2);
+        assert_eq!(filter_collections[0].name, "Walls");
+        assert_eq!(filter_collections[0].region_op, None);
+        let islands = &filter_collections[1];
+        assert_eq!(islands.name, "Islands");
+        assert!(islands.filters.is_empty());
+        assert_eq!(
+            islands.properties.pattern_matching,
+            PatternMatching::Destination
+        );
+        let tile = |index| TiledTile {
+            index: Some(index),
+            ..Default::default()
+        };
+        assert_eq!(
+            islands.region_op,
+            Some(RegionOp {
+                neighborhood: Neighborhood::Eight,
+                max_size: Some(9),
+                ..RegionOp::new(
+                    RegionOpKind::Replace,
+                    vec![tile(0), tile(1)],
+                    vec![tile(3), tile(3), tile(4)]
+                )
+            })
+        );
+    }
+
+    #[test]
+    fn test_load_connectivity() {
+        let filter_collections = TiledFilterImporter::load(
+            format!(
+                "{}/data/test_connectivity/filter_collection.tmx",
+                env!("CARGO_MANIFEST_DIR"),
+            )
+            .as_str(),
+        );
+
+        assert!(filter_collections.is_ok());
+        let filter_collections = &filter_collections.unwrap();
+        assert_eq!(filter_collections.len(), 1);
+        assert!(filter_collections[0].filters.is_empty());
+        let connectivity = filter_collections[0].connectivity.as_ref().unwrap();
+        // Only unflipped tiles, as flips are matched exactly
+        let class_tiles = |ids: &[u32]| -> Vec<TiledTile> {
+            ids.iter()
+                .map(|id| TiledTile {
+                    index: Some(*id),
+                    ..Default::default()
+                })
+                .collect()
+        };
+        assert_eq!(connectivity.walkable, class_tiles(&[1]));
+        assert_eq!(connectivity.breakable, class_tiles(&[0, 2]));
+        assert!(connectivity.start.is_empty());
+        assert_eq!(
+            connectivity.carve,
+            Some(TiledTile {
+                index: Some(1),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_load_distance_constraints() {
+        let filter_collections = TiledFilterImporter::load(
+            format!(
+                "{}/data/test_distance/filter_collection.tmx",
+                env!("CARGO_MANIFEST_DIR"),
+            )
+            .as_str(),
+        );
+
+        assert!(filter_collections.is_ok());
+        let filter_collections = &filter_collections.unwrap();
+        assert_eq!(filter_collections[0].filters.len(), 1);
+        let filter = &filter_collections[0].filters[0];
+        assert_eq!(
+            filter.properties().distance_metric,
+            DistanceMetric::Chebyshev
+        );
+        let constraints = filter.distance_constraints();
+        assert_eq!(constraints.len(), 1);
+        assert_eq!(constraints[0].min, None);
+        assert_eq!(constraints[0].max, Some(1.0));
+        // Both wall tiles, unflipped as flips are matched exactly
+        assert_eq!(constraints[0].tiles.len(), 2);
+        assert!(constraints[0]
+            .tiles
+            .iter()
+            .all(|tile| tile.index == Some(0) || tile.index == Some(2)));
+    }
+
+    #[test]
+    fn test_load_examples() {
+        let file = format!(
+            "{}/data/test_filter_examples/filter_collection.tmx",
+            env!("CARGO_MANIFEST_DIR"),
+        );
+
+        let filter_collections = TiledFilterImporter::load(file.as_str());
+        let examples = TiledFilterImporter::load_examples(file.as_str());
+
+        assert!(filter_collections.is_ok());
+        assert_eq!(filter_collections.unwrap().len(), 1);
+        assert!(examples.is_ok());
+        let examples = examples.unwrap();
+        assert_eq!(examples.len(), 2);
+        assert_eq!(examples[0].name, "Passing");
+        assert_eq!(examples[0].input.size(), (5, 3).into());
+        assert_eq!(
+            examples[0].expected_output.get((1, 0).into()).unwrap(),
+            &TiledTile {
+                index: Some(2),
+                ..Default::default()
+            }
+        );
+        assert_eq!(examples[1].name, "Failing");
+    }
+
+    #[test]
+    fn test_load_objects() {
+        let filter_collections = TiledFilterImporter::load(
+            format!(
+                "{}/data/test_objects/filter_collection.tmx",
+                env!("CARGO_MANIFEST_DIR"),
+            )
+            .as_str(),
+        );
+
+        assert!(filter_collections.is_ok());
+        let filter_collections = filter_collections.unwrap();
+        assert_eq!(filter_collections.len(), 1);
+        assert_eq!(filter_collections[0].filters.len(), 1);
+        assert_eq!(
+            filter_collections[0].filters[0].objects(),
+            [MapObject {
+                name: String::from("Chest"),
+                class: String::from("chest"),
+                position: (0.5, 0.5),
+                properties: [(String::from("gold"), PropertyValue::IntValue(10))].into(),
+                ..Default::default()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_substitute_objects() {
+        let object = |x, y| MapObject {
+            position: (x, y),
+            ..Default::default()
+        };
+        let objects = [object(0.5, 0.5), object(2.0, 1.0), object(3.0, 1.5)];
+
+        let result = substitute_objects(&objects, (2, 1, 1, 1).try_into().unwrap());
+
+        assert_eq!(result, [object(0.0, 0.0)]);
+    }
+
+    #[test]
+    fn test_load_predicates() {
+        let filter_collections = TiledFilterImporter::load(
+            format!(
+                "{}/data/test_metadata/filter_collection.tmx",
+                env!("CARGO_MANIFEST_DIR"),
+            )
+            .as_str(),
+        );
+
+        assert!(filter_collections.is_ok());
+        let filter_collections = filter_collections.unwrap();
+        let filter = &filter_collections[0].filters[0];
+        assert_eq!(filter.alternatives().len(), 1);
+        let (position, tiles) = &filter.alternatives()[0];
+        assert_eq!(*position, (0, 0).into());
+        assert_eq!(tiles.len(), 2);
+
+        let input = create_tiled_map([[0, 1, 2]]);
+        let mut output = input.clone();
+        assert!(filter_collections[0].apply(&input, &mut output).is_ok());
+        assert_eq!(output, create_tiled_map([[5, 1, 5]]));
+    }
+
     // TODO: test_load_failure
 }
@@ -1,96 +1,523 @@
-use std::fs::File;
+use std::{fs::File, io::Write};
 
-use xml::{common::XmlVersion, writer::XmlEvent, EmitterConfig};
+use xml::{common::XmlVersion, writer::XmlEvent, EmitterConfig, EventWriter};
 
-use crate::{map_exporter::MapExporter, tiled_tile::TiledTile};
+use crate::{
+    layered_map::{Layer, LayerContent, LayeredMap},
+    map::Map,
+    map_exporter::MapExporter,
+    map_object::{MapObject, ObjectShape},
+    tiled_tile::TiledTile,
+    tisu_error::TisuError,
+    vector2::Vector2u,
+};
+
+fn write_event<'a, W: Write>(
+    writer: &mut EventWriter<W>,
+    event: impl Into<XmlEvent<'a>>,
+) -> Result<(), TisuError> {
+    writer.write(event).map_err(|_| TisuError::Unexpected)
+}
+
+/// Creates a TMX file writer and writes the document header, the starting
+/// 'map' element and the tileset reference.
+pub(crate) fn start_map(
+    file: impl AsRef<std::path::Path>,
+    map_size: Vector2u,
+    tile_size: Vector2u,
+    tileset_path: impl AsRef<std::path::Path>,
+    layer_count: u32,
+    object_count: u32,
+) -> Result<EventWriter<File>, TisuError> {
+    let target = File::create(file).map_err(|_| TisuError::InvalidArgument)?;
+    let mut writer = EmitterConfig::new()
+        .perform_indent(true)
+        .create_writer(target);
+
+    let event = XmlEvent::StartDocument {
+        version: XmlVersion::Version10,
+        encoding: "UTF-8".into(),
+        standalone: None,
+    };
+    write_event(&mut writer, event)?;
+
+    let map_width_str = map_size.x.to_string();
+    let map_height_str = map_size.y.to_string();
+    let tile_width_str = tile_size.x.to_string();
+    let tile_height_str = tile_size.y.to_string();
+    let next_layer_id_str = (layer_count + 1).to_string();
+    let next_object_id_str = (object_count + 1).to_string();
+    let event = XmlEvent::start_element("map")
+        .attr("version", "1.10")
+        .attr("tiledversion", "1.11.0")
+        .attr("orientation", "orthogonal")
+        .attr("renderorder", "right-down")
+        .attr("width", map_width_str.as_str())
+        .attr("height", map_height_str.as_str())
+        .attr("tilewidth", tile_width_str.as_str())
+        .attr("tileheight", tile_height_str.as_str())
+        .attr("infinite", "0")
+        .attr("nextlayerid", next_layer_id_str.as_str())
+        .attr("nextobjectid", next_object_id_str.as_str());
+    write_event(&mut writer, event)?;
+
+    let tileset_str = tileset_path.as_ref().display().to_string();
+    let event = XmlEvent::start_element("tileset")
+        .attr("firstgid", "1")
+        .attr("source", &tileset_str);
+    write_event(&mut writer, event)?;
+    write_event(&mut writer, XmlEvent::end_element())?;
+
+    Ok(writer)
+}
+
+/// Writes the ending 'map' element.
+pub(crate) fn end_map<W: Write>(writer: &mut EventWriter<W>) -> Result<(), TisuError> {
+    write_event(writer, XmlEvent::end_element())
+}
+
+/// Writes a 'properties' element containing the given properties. Nothing is
+/// written if there are no properties.
+///
+/// # Errors
+///
+/// Returns an error if a property has an unsupported type (only bool, color,
+/// file, float, int and string properties are supported).
+pub(crate) fn write_properties<W: Write>(
+    writer: &mut EventWriter<W>,
+    properties: &tiled::Properties,
+) -> Result<(), TisuError> {
+    if properties.is_empty() {
+        return Ok(());
+    }
+
+    // Sort the properties to get a stable output
+    let mut names: Vec<_> = properties.keys().collect();
+    names.sort();
+
+    write_event(writer, XmlEvent::start_element("properties"))?;
+    for name in names {
+        let (property_type, value) = match &properties[name] {
+            tiled::PropertyValue::BoolValue(v) => (Some("bool"), v.to_string()),
+            tiled::PropertyValue::ColorValue(v) => (
+                Some("color"),
+                format!("#{:02x}{:02x}{:02x}{:02x}", v.alpha, v.red, v.green, v.blue),
+            ),
+            tiled::PropertyValue::FileValue(v) => (Some("file"), v.clone()),
+            tiled::PropertyValue::FloatValue(v) => (Some("float"), v.to_string()),
+            tiled::PropertyValue::IntValue(v) => (Some("int"), v.to_string()),
+            tiled::PropertyValue::StringValue(v) => (None, v.clone()),
+            _ => return Err(TisuError::InvalidArgument),
+        };
+        let mut event = XmlEvent::start_element("property").attr("name", name.as_str());
+        if let Some(property_type) = property_type {
+            event = event.attr("type", property_type);
+        }
+        let event = event.attr("value", value.as_str());
+        write_event(writer, event)?;
+        write_event(writer, XmlEvent::end_element())?;
+    }
+    write_event(writer, XmlEvent::end_element())
+}
+
+/// Writes a 'layer' element containing the given map and layer properties.
+pub(crate) fn write_tile_layer<W: Write>(
+    writer: &mut EventWriter<W>,
+    id: u32,
+    name: &str,
+    map: &Map<TiledTile>,
+    properties: &tiled::Properties,
+) -> Result<(), TisuError> {
+    let attributes = [("id", id.to_string()), ("name", String::from(name))];
+    write_tile_layer_element(writer, &attributes, map, properties)
+}
+
+/// Writes a 'layer' element like `write_tile_layer`, with the given
+/// attributes (which include the ID and the name).
+fn write_tile_layer_element<W: Write>(
+    writer: &mut EventWriter<W>,
+    attributes: &[(&str, String)],
+    map: &Map<TiledTile>,
+    properties: &tiled::Properties,
+) -> Result<(), TisuError> {
+    let map_width_str = map.size().x.to_string();
+    let map_height_str = map.size().y.to_string();
+    let mut event = XmlEvent::start_element("layer");
+    for (attribute, value) in attributes {
+        event = event.attr(*attribute, value.as_str());
+    }
+    let event = event
+        .attr("width", map_width_str.as_str())
+        .attr("height", map_height_str.as_str());
+    write_event(writer, event)?;
+
+    write_properties(writer, properties)?;
+
+    let event = XmlEvent::start_element("data").attr("encoding", "csv");
+    write_event(writer, event)?;
+
+    let data: Vec<String> = map
+        .data()
+        .iter()
+        .map(|input: &TiledTile| input.to_string())
+        .collect();
+    let data_str = data.as_slice().join(", ");
+    write_event(writer, XmlEvent::characters(&data_str))?;
+
+    // End the 'data' and the 'layer' elements
+    write_event(writer, XmlEvent::end_element())?;
+    write_event(writer, XmlEvent::end_element())
+}
+
+/// Writes an 'objectgroup' element containing the given objects, numbered
+/// from the given first object ID.
+pub(crate) fn write_object_layer<W: Write>(
+    writer: &mut EventWriter<W>,
+    id: u32,
+    name: &str,
+    objects: &[MapObject],
+    tile_size: Vector2u,
+    first_object_id: u32,
+) -> Result<(), TisuError> {
+    let attributes = [("id", id.to_string()), ("name", String::from(name))];
+    write_object_layer_element(
+        writer,
+        &attributes,
+        &tiled::Properties::new(),
+        objects,
+        tile_size,
+        first_object_id,
+    )
+}
+
+/// Writes an 'objectgroup' element like `write_object_layer`, with the given
+/// attributes (which include the ID and the name) and layer properties.
+fn write_object_layer_element<W: Write>(
+    writer: &mut EventWriter<W>,
+    attributes: &[(&str, String)],
+    properties: &tiled::Properties,
+    objects: &[MapObject],
+    tile_size: Vector2u,
+    first_object_id: u32,
+) -> Result<(), TisuError> {
+    let mut event = XmlEvent::start_element("objectgroup");
+    for (attribute, value) in attributes {
+        event = event.attr(*attribute, value.as_str());
+    }
+    write_event(writer, event)?;
+    write_properties(writer, properties)?;
+
+    let tile_size = (tile_size.x as f32, tile_size.y as f32);
+    for (idx, object) in objects.iter().enumerate() {
+        let mut object_attributes = vec![("id", (first_object_id + idx as u32).to_string())];
+        if !object.name.is_empty() {
+            object_attributes.push(("name", object.name.clone()));
+        }
+        if !object.class.is_empty() {
+            object_attributes.push(("type", object.class.clone()));
+        }
+        object_attributes.push(("x", (object.position.0 * tile_size.0).to_string()));
+        object_attributes.push(("y", (object.position.1 * tile_size.1).to_string()));
+        if let ObjectShape::Rect { width, height } | ObjectShape::Ellipse { width, height } =
+            object.shape
+        {
+            object_attributes.push(("width", (width * tile_size.0).to_string()));
+            object_attributes.push(("height", (height * tile_size.1).to_string()));
+        }
+        if object.rotation != 0.0 {
+            object_attributes.push(("rotation", object.rotation.to_string()));
+        }
+        let mut event = XmlEvent::start_element("object");
+        for (attribute, value) in &object_attributes {
+            event = event.attr(*attribute, value.as_str());
+        }
+        write_event(writer, event)?;
+        write_properties(writer, &object.properties)?;
+        write_shape(writer, &object.shape, tile_size)?;
+        write_event(writer, XmlEvent::end_element())?;
+    }
+
+    write_event(writer, XmlEvent::end_element())
+}
+
+/// Writes the element describing the given object shape, with sizes in
+/// pixels. Rectangles are described by the attributes of their object alone.
+fn write_shape<W: Write>(
+    writer: &mut EventWriter<W>,
+    shape: &ObjectShape,
+    tile_size: (f32, f32),
+) -> Result<(), TisuError> {
+    let (element, points) = match shape {
+        ObjectShape::Point => ("point", None),
+        ObjectShape::Rect { .. } => return Ok(()),
+        ObjectShape::Ellipse { .. } => ("ellipse", None),
+        ObjectShape::Polygon { points } => ("polygon", Some(points)),
+        ObjectShape::Polyline { points } => ("polyline", Some(points)),
+    };
+    let points_str = points.map(|points| {
+        points
+            .iter()
+            .map(|(x, y)| format!("{},{}", x * tile_size.0, y * tile_size.1))
+            .collect::<Vec<_>>()
+            .join(" ")
+    });
+    let mut event = XmlEvent::start_element(element);
+    if let Some(points_str) = &points_str {
+        event = event.attr("points", points_str.as_str());
+    }
+    write_event(writer, event)?;
+    write_event(writer, XmlEvent::end_element())
+}
+
+/// Returns the attributes of the given layer with the given ID: the ID, the
+/// name, and the offset, opacity and visibility if they differ from their
+/// defaults.
+fn layer_attributes(id: u32, layer: &Layer<TiledTile>) -> Vec<(&'static str, String)> {
+    let mut result = vec![("id", id.to_string()), ("name", layer.name.clone())];
+    if !layer.visible {
+        result.push(("visible", String::from("0")));
+    }
+    if layer.opacity != 1.0 {
+        result.push(("opacity", layer.opacity.to_string()));
+    }
+    if layer.offset.0 != 0.0 {
+        result.push(("offsetx", layer.offset.0.to_string()));
+    }
+    if layer.offset.1 != 0.0 {
+        result.push(("offsety", layer.offset.1.to_string()));
+    }
+    result
+}
+
+/// Writes the given layers (and the layers of groups), numbering layers and
+/// objects from the given next IDs.
+fn write_layers<W: Write>(
+    writer: &mut EventWriter<W>,
+    layers: &[Layer<TiledTile>],
+    tile_size: Vector2u,
+    next_layer_id: &mut u32,
+    next_object_id: &mut u32,
+) -> Result<(), TisuError> {
+    for layer in layers {
+        let id = *next_layer_id;
+        *next_layer_id += 1;
+        let attributes = layer_attributes(id, layer);
+        match &layer.content {
+            LayerContent::Tiles(map) => {
+                write_tile_layer_element(writer, &attributes, map, &layer.properties)?
+            }
+            LayerContent::Objects(objects) => {
+                write_object_layer_element(
+                    writer,
+                    &attributes,
+                    &layer.properties,
+                    objects,
+                    tile_size,
+                    *next_object_id,
+                )?;
+                *next_object_id += objects.len() as u32;
+            }
+            LayerContent::Group(children) => {
+                let mut event = XmlEvent::start_element("group");
+                for (attribute, value) in &attributes {
+                    event = event.attr(*attribute, value.as_str());
+                }
+                write_event(writer, event)?;
+                write_properties(writer, &layer.properties)?;
+                write_layers(writer, children, tile_size, next_layer_id, next_object_id)?;
+                write_event(writer, XmlEvent::end_element())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the number of layers (groups included) and objects of the given
+/// layers.
+fn count_layers(layers: &[Layer<TiledTile>]) -> (u32, u32) {
+    layers
+        .iter()
+        .fold((0, 0), |(layer_count, object_count), layer| {
+            let (child_layers, objects) = match &layer.content {
+                LayerContent::Tiles(_) => (0, 0),
+                LayerContent::Objects(objects) => (0, objects.len() as u32),
+                LayerContent::Group(children) => count_layers(children),
+            };
+            (layer_count + 1 + child_layers, object_count + objects)
+        })
+}
 
 pub struct TiledMapExporter {}
 
+impl TiledMapExporter {
+    /// Saves the given map as the only tile layer of a file like `save`, along
+    /// with the given objects, which are written to an object layer above the
+    /// tile layer (if there are any).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written or if an object has a
+    /// property of an unsupported type.
+    pub fn save_with_objects(
+        file: impl AsRef<std::path::Path>,
+        map: &Map<TiledTile>,
+        objects: &[MapObject],
+        tile_size: Vector2u,
+        tileset_path: impl AsRef<std::path::Path>,
+    ) -> Result<(), TisuError> {
+        let mut layered_map = LayeredMap::from_map("Tile Layer 1", map.clone());
+        if !objects.is_empty() {
+            layered_map.layers.push(Layer::new(
+                "Objects",
+                LayerContent::Objects(objects.to_vec()),
+            ));
+        }
+        Self::save(file, &layered_map, tile_size, tileset_path)
+    }
+}
+
 impl MapExporter for TiledMapExporter {
     type TileType = TiledTile;
 
-    // TODO: Test
+    /// Saves the given layered map, with its layer hierarchy, names, offsets,
+    /// opacities, visibilities and properties. Objects keep their shape and
+    /// rotation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written or if a layer or an
+    /// object has a property of an unsupported type.
     fn save(
         file: impl AsRef<std::path::Path>,
-        map: &crate::map::Map<Self::TileType>,
-        tile_size: crate::vector2::Vector2u,
+        map: &LayeredMap<Self::TileType>,
+        tile_size: Vector2u,
         tileset_path: impl AsRef<std::path::Path>,
-    ) -> Result<(), crate::tisu_error::TisuError> {
-        let target = File::create(file).expect("Failed to create file");
-        let mut writer = EmitterConfig::new()
-            .perform_indent(true)
-            .create_writer(target);
-
-        let event = XmlEvent::StartDocument {
-            version: XmlVersion::Version10,
-            encoding: "UTF-8".into(),
-            standalone: None,
+    ) -> Result<(), TisuError> {
+        let (layer_count, object_count) = count_layers(&map.layers);
+        let mut writer = start_map(
+            file,
+            map.size,
+            tile_size,
+            tileset_path,
+            layer_count,
+            object_count,
+        )?;
+        write_layers(&mut writer, &map.layers, tile_size, &mut 1, &mut 1)?;
+        end_map(&mut writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{map_importer::MapImporter, tiled_map_importer::TiledMapImporter};
+
+    use super::*;
+
+    #[test]
+    fn test_save() {
+        let input = TiledMapImporter::load(format!(
+            "{}/data/test_layers/input.tmx",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let file = std::env::temp_dir().join(format!(
+            "tisu_test_tiled_map_exporter_save_{}.tmx",
+            std::process::id()
+        ));
+
+        let result =
+            TiledMapExporter::save(&file, &input.map, (16, 16).into(), &input.tileset_path);
+
+        assert!(result.is_ok());
+        let output = TiledMapImporter::load(&file).unwrap();
+        _ = std::fs::remove_file(&file);
+        assert_eq!(output.map, input.map);
+    }
+
+    #[test]
+    fn test_save_shapes() {
+        let object = |shape, rotation| MapObject {
+            position: (1.0, 0.5),
+            shape,
+            rotation,
+            ..Default::default()
         };
-        writer
-            .write(event)
-            .expect("Failed to write XML document header");
-
-        let map_width_str = map.size().x.to_string();
-        let map_height_str = map.size().y.to_string();
-        let tile_width_str = tile_size.x.to_string();
-        let tile_height_str = tile_size.y.to_string();
-        let event = XmlEvent::start_element("map")
-            .attr("version", "1.10")
-            .attr("tiledversion", "1.11.0")
-            .attr("orientation", "orthogonal")
-            .attr("renderorder", "right-down")
-            .attr("width", map_width_str.as_str())
-            .attr("height", map_height_str.as_str())
-            .attr("tilewidth", tile_width_str.as_str())
-            .attr("tileheight", tile_height_str.as_str())
-            .attr("infinite", "0")
-            .attr("nextlayerid", "2")
-            .attr("nextobjectid", "1");
-        writer.write(event).expect("Failed to start 'map' element");
-
-        let tileset_str = tileset_path.as_ref().display().to_string();
-        let event = XmlEvent::start_element("tileset")
-            .attr("firstgid", "1")
-            .attr("source", &tileset_str);
-        writer
-            .write(event)
-            .expect("Failed to start 'tileset' element");
-
-        let event = XmlEvent::end_element();
-        writer
-            .write(event)
-            .expect("Failed to end 'tileset' element");
-
-        let event = XmlEvent::start_element("layer")
-            .attr("id", "1")
-            .attr("name", "Tile Layer 1")
-            .attr("width", map_width_str.as_str())
-            .attr("height", map_height_str.as_str());
-        writer
-            .write(event)
-            .expect("Failed to start 'layer' element");
-
-        let event = XmlEvent::start_element("data").attr("encoding", "csv");
-        writer.write(event).expect("Failed to start 'data' element");
-
-        let data: Vec<String> = map
-            .data()
-            .iter()
-            .map(|input: &Self::TileType| input.to_string())
-            .collect();
-        let data_str = data.as_slice().join(", ");
-        let event = XmlEvent::characters(&data_str);
-        writer.write(event).expect("Failed to write data");
+        let points = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.5)];
+        let objects = [
+            object(ObjectShape::Point, 0.0),
+            object(
+                ObjectShape::Rect {
+                    width: 2.0,
+                    height: 1.0,
+                },
+                45.0,
+            ),
+            object(
+                ObjectShape::Ellipse {
+                    width: 0.5,
+                    height: 1.5,
+                },
+                0.0,
+            ),
+            object(
+                ObjectShape::Polygon {
+                    points: points.clone(),
+                },
+                0.0,
+            ),
+            object(ObjectShape::Polyline { points }, -90.0),
+        ];
+        let file = std::env::temp_dir().join(format!(
+            "tisu_test_tiled_map_exporter_save_shapes_{}.tmx",
+            std::process::id()
+        ));
 
-        let event = XmlEvent::end_element();
-        writer.write(event).expect("Failed to end 'data' element");
+        let result = TiledMapExporter::save_with_objects(
+            &file,
+            &Map::new((2, 2).into()),
+            &objects,
+            (16, 8).into(),
+            format!("{}/data/tileset.tsx", env!("CARGO_MANIFEST_DIR")),
+        );
+
+        assert!(result.is_ok());
+        let output = TiledMapImporter::load(&file).unwrap();
+        _ = std::fs::remove_file(&file);
+        assert_eq!(
+            output.map.layer("Objects").unwrap().content,
+            LayerContent::Objects(objects.to_vec())
+        );
+    }
 
-        let event = XmlEvent::end_element();
-        writer.write(event).expect("Failed to end 'layer' element");
+    #[test]
+    fn test_save_with_objects() {
+        let map = Map::<TiledTile>::new((2, 2).into());
+        let objects = [MapObject {
+            name: String::from("Chest"),
+            position: (1.5, 0.5),
+            ..Default::default()
+        }];
+        let file = std::env::temp_dir().join(format!(
+            "tisu_test_tiled_map_exporter_save_with_objects_{}.tmx",
+            std::process::id()
+        ));
 
-        let event = XmlEvent::end_element();
-        writer.write(event).expect("Failed to end 'map' element");
+        let result = TiledMapExporter::save_with_objects(
+            &file,
+            &map,
+            &objects,
+            (16, 16).into(),
+            format!("{}/data/tileset.tsx", env!("CARGO_MANIFEST_DIR")),
+        );
 
-        Ok(())
+        assert!(result.is_ok());
+        let output = TiledMapImporter::load(&file).unwrap();
+        _ = std::fs::remove_file(&file);
+        assert_eq!(output.map.paths(), ["Tile Layer 1", "Objects"]);
+        assert_eq!(output.map.top_tile_layer(), Some(&map));
+        assert_eq!(
+            output.map.layer("Objects").unwrap().content,
+            LayerContent::Objects(objects.to_vec())
+        );
     }
 }
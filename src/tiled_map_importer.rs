@@ -1,10 +1,16 @@
+use std::collections::HashMap;
+
 use tiled::Loader;
 
 use crate::{
+    layered_map::{Layer, LayerContent, LayeredMap},
     map::Map,
     map_importer::{LoadResult, MapImporter},
+    map_object::MapObject,
+    region_mask, tile_metadata,
     tiled_tile::TiledTile,
     tisu_error::TisuError,
+    vector2::Vector2u,
 };
 
 pub struct TiledMapImporter {}
@@ -37,23 +43,145 @@ impl TiledMapImporter {
         Ok(map)
     }
 
-    fn process_layer(layer: &tiled::Layer, result: &mut Vec<Map<TiledTile>>) {
-        match layer.layer_type() {
+    /// Converts the given layer, adding the regions it defines. Image layers
+    /// and infinite tile layers aren't supported and are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a tile layer can't be converted.
+    fn process_layer(
+        layer: &tiled::Layer,
+        map_size: Vector2u,
+        tile_size: (f32, f32),
+        regions: &mut HashMap<String, Map<bool>>,
+    ) -> Result<Option<Layer<TiledTile>>, TisuError> {
+        let content = match layer.layer_type() {
             tiled::LayerType::Tiles(tiled::TileLayer::Finite(finite_tile_layer)) => {
-                if let Ok(map) = Self::load_finite_tile_layer(&finite_tile_layer) {
-                    result.push(map);
-                }
+                let map = Self::load_finite_tile_layer(&finite_tile_layer)?;
+                let mask = region_mask::from_fields(&map, &TiledTile::default());
+                add_region(regions, &layer.name, &mask);
+                LayerContent::Tiles(map)
             }
-            tiled::LayerType::Group(group) => {
-                for layer in group.layers().collect::<Vec<_>>().iter().rev() {
-                    Self::process_layer(layer, result);
+            tiled::LayerType::Objects(object_layer) => {
+                let mut layer_mask = Map::new(map_size);
+                for object in object_layer.objects() {
+                    if let Some(outline) = object_outline(&object, tile_size) {
+                        let mask = region_mask::from_polygon(map_size, &outline);
+                        add_region(regions, &object.name, &mask);
+                        _ = region_mask::union(&mut layer_mask, &mask);
+                    }
                 }
+                add_region(regions, &layer.name, &layer_mask);
+                LayerContent::Objects(
+                    object_layer
+                        .objects()
+                        .map(|object| MapObject::from(&object))
+                        .collect(),
+                )
             }
-            _ => (),
+            tiled::LayerType::Group(group) => LayerContent::Group(Self::process_layers(
+                group.layers(),
+                map_size,
+                tile_size,
+                regions,
+            )?),
+            _ => return Ok(None),
+        };
+        Ok(Some(Layer {
+            name: layer.name.clone(),
+            offset: (layer.offset_x, layer.offset_y),
+            opacity: layer.opacity,
+            visible: layer.visible,
+            properties: layer.properties.clone(),
+            content,
+        }))
+    }
+
+    /// Converts the given layers like `process_layer`, skipping the ones that
+    /// aren't supported.
+    fn process_layers<'map>(
+        layers: impl Iterator<Item = tiled::Layer<'map>>,
+        map_size: Vector2u,
+        tile_size: (f32, f32),
+        regions: &mut HashMap<String, Map<bool>>,
+    ) -> Result<Vec<Layer<TiledTile>>, TisuError> {
+        let mut result = vec![];
+        for layer in layers {
+            result.extend(Self::process_layer(&layer, map_size, tile_size, regions)?);
         }
+        Ok(result)
+    }
+
+    /// Converts the layers of the given Tiled map into a layered map, adding
+    /// the regions they define.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a tile layer can't be converted.
+    pub(crate) fn load_layered_map(
+        tmx_map: &tiled::Map,
+        regions: &mut HashMap<String, Map<bool>>,
+    ) -> Result<LayeredMap<TiledTile>, TisuError> {
+        let map_size = (tmx_map.width, tmx_map.height).into();
+        let tile_size = (tmx_map.tile_width as f32, tmx_map.tile_height as f32);
+        let layers = Self::process_layers(tmx_map.layers(), map_size, tile_size, regions)?;
+        Ok(LayeredMap {
+            size: map_size,
+            layers,
+        })
+    }
+}
+
+/// Adds the given mask to the region with the given name (regions with the
+/// same name are merged). Unnamed regions are ignored.
+fn add_region(regions: &mut HashMap<String, Map<bool>>, name: &str, mask: &Map<bool>) {
+    if name.is_empty() {
+        return;
+    }
+    match regions.get_mut(name) {
+        Some(region) => _ = region_mask::union(region, mask),
+        None => _ = regions.insert(String::from(name), mask.clone()),
     }
 }
 
+/// Returns the outline of the given object as a polygon in fields, or `None`
+/// if the object doesn't cover an area. Ellipses are approximated by polygons.
+fn object_outline(object: &tiled::ObjectData, tile_size: (f32, f32)) -> Option<Vec<(f32, f32)>> {
+    let points = match &object.shape {
+        tiled::ObjectShape::Rect { width, height } => {
+            vec![(0.0, 0.0), (*width, 0.0), (*width, *height), (0.0, *height)]
+        }
+        tiled::ObjectShape::Ellipse { width, height } => (0..ELLIPSE_POINTS)
+            .map(|idx| {
+                let angle = idx as f32 * std::f32::consts::TAU / ELLIPSE_POINTS as f32;
+                (
+                    width / 2.0 * (1.0 + angle.cos()),
+                    height / 2.0 * (1.0 + angle.sin()),
+                )
+            })
+            .collect(),
+        tiled::ObjectShape::Polygon { points } => points.clone(),
+        _ => return None,
+    };
+
+    // Objects are rotated clockwise around their position
+    let (sin, cos) = object.rotation.to_radians().sin_cos();
+    Some(
+        points
+            .into_iter()
+            .map(|(x, y)| {
+                (
+                    (object.x + x * cos - y * sin) / tile_size.0,
+                    (object.y + x * sin + y * cos) / tile_size.1,
+                )
+            })
+            .collect(),
+    )
+}
+
+/// Number of points of the polygons approximating ellipse objects
+const ELLIPSE_POINTS: u32 = 32;
+
 impl MapImporter for TiledMapImporter {
     type TileType = TiledTile;
 
@@ -63,20 +191,22 @@ impl MapImporter for TiledMapImporter {
             .load_tmx_map(file)
             .map_err(|_| TisuError::InvalidArgument)?;
 
-        let mut result = LoadResult::<Self::TileType> {
-            map_layers: vec![],
+        let mut regions = HashMap::new();
+        let map = Self::load_layered_map(&tmx_map, &mut regions)?;
+        let result = LoadResult::<Self::TileType> {
+            map,
             tileset_path: tmx_map.tilesets()[0].source.clone(),
+            regions,
+            tile_metadata: tile_metadata::from_tileset(&tmx_map.tilesets()[0]),
         };
-        // Collect the layers into a Vec to traverse it in reverse order
-        for layer in tmx_map.layers().collect::<Vec<_>>().iter().rev() {
-            Self::process_layer(layer, &mut result.map_layers);
-        }
         Ok(result)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::map_object::ObjectShape;
+
     use super::*;
 
     #[test]
@@ -87,14 +217,13 @@ mod tests {
 
         assert!(result.is_ok());
         let load_result = result.unwrap();
-        assert_eq!(load_result.map_layers.len(), 1);
-        assert_eq!(load_result.map_layers[0].size(), (3, 3).into());
-        assert_eq!(
-            load_result.map_layers[0].get((0, 0).into()).unwrap(),
-            &TiledTile::default()
-        );
+        assert_eq!(load_result.map.size, (3, 3).into());
+        assert_eq!(load_result.map.layers.len(), 1);
+        let map = load_result.map.top_tile_layer().unwrap();
+        assert_eq!(map.size(), (3, 3).into());
+        assert_eq!(map.get((0, 0).into()).unwrap(), &TiledTile::default());
         assert_eq!(
-            load_result.map_layers[0].get((1, 1).into()).unwrap(),
+            map.get((1, 1).into()).unwrap(),
             &TiledTile {
                 index: Some(3),
                 ..Default::default()
@@ -102,5 +231,104 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_load_regions() {
+        let result = TiledMapImporter::load(format!(
+            "{}/data/test_regions/input.tmx",
+            env!("CARGO_MANIFEST_DIR")
+        ));
+
+        assert!(result.is_ok());
+        let load_result = result.unwrap();
+        assert_eq!(load_result.map.tile_layers().len(), 2);
+        let mut names: Vec<_> = load_result.regions.keys().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(names, ["Dungeon", "Ground", "Lake", "Spawn", "Zones"]);
+        assert_eq!(
+            load_result.regions["Dungeon"],
+            Map::from_data([
+                [false, false, false, false, true, true],
+                [false, false, false, false, true, true],
+                [false, false, false, false, false, false],
+                [false, false, false, false, false, false],
+            ])
+            .unwrap()
+        );
+        assert_eq!(
+            load_result.regions["Zones"],
+            Map::from_data([
+                [true, true, false, false, false, false],
+                [true, true, false, false, false, false],
+                [false, false, false, true, false, false],
+                [false, false, false, false, false, false],
+            ])
+            .unwrap()
+        );
+        assert_eq!(
+            load_result.map.layer("Zones").unwrap().content,
+            LayerContent::Objects(vec![
+                MapObject {
+                    name: String::from("Spawn"),
+                    shape: ObjectShape::Rect {
+                        width: 2.0,
+                        height: 2.0
+                    },
+                    ..Default::default()
+                },
+                MapObject {
+                    name: String::from("Lake"),
+                    position: (2.0, 2.0),
+                    shape: ObjectShape::Polygon {
+                        points: vec![(0.0, 0.0), (2.0, 0.0), (2.0, 1.5)]
+                    },
+                    ..Default::default()
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_load_layers() {
+        let result = TiledMapImporter::load(format!(
+            "{}/data/test_layers/input.tmx",
+            env!("CARGO_MANIFEST_DIR")
+        ));
+
+        assert!(result.is_ok());
+        let layered_map = result.unwrap().map;
+        assert_eq!(
+            layered_map.paths(),
+            [
+                "Ground",
+                "Dungeon",
+                "Dungeon/Walls",
+                "Dungeon/Spawns",
+                "Decorations"
+            ]
+        );
+        assert_eq!(
+            layered_map.layer("Ground").unwrap().properties["biome"],
+            tiled::PropertyValue::StringValue(String::from("forest"))
+        );
+        assert_eq!(layered_map.layer("Dungeon").unwrap().offset, (8.0, -4.0));
+        assert_eq!(layered_map.layer("Dungeon/Walls").unwrap().opacity, 0.5);
+        let spawns = layered_map.layer("Dungeon/Spawns").unwrap();
+        assert!(!spawns.visible);
+        assert_eq!(
+            spawns.content,
+            LayerContent::Objects(vec![MapObject {
+                name: String::from("Player"),
+                class: String::from("spawn"),
+                position: (1.5, 0.5),
+                ..Default::default()
+            }])
+        );
+        // Filters are applied to the topmost tile layer
+        assert_eq!(
+            layered_map.top_tile_layer(),
+            layered_map.layer("Decorations").unwrap().tiles()
+        );
+    }
+
     // TODO: test_load_failure
 }
@@ -1,16 +1,26 @@
 use std::fmt::Display;
 
+use crate::filter::FieldOrientation;
+
 const FLIPPED_HORIZONTALLY_FLAG: u32 = 0x80000000;
 const FLIPPED_VERTICALLY_FLAG: u32 = 0x40000000;
 const FLIPPED_DIAGONALLY_FLAG: u32 = 0x20000000;
 const ALL_FLIP_FLAGS: u32 =
     FLIPPED_HORIZONTALLY_FLAG | FLIPPED_VERTICALLY_FLAG | FLIPPED_DIAGONALLY_FLAG;
 
-#[derive(Clone, Debug, Default, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct TiledTile {
     pub index: Option<u32>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "std::ops::Not::not"))]
     pub flip_h: bool,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "std::ops::Not::not"))]
     pub flip_v: bool,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "std::ops::Not::not"))]
     pub flip_d: bool,
 }
 
@@ -61,3 +71,18 @@ impl From<u32> for TiledTile {
         }
     }
 }
+
+impl FieldOrientation for TiledTile {
+    fn eq_ignoring_orientation(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+
+    fn with_orientation_of(&self, other: &Self) -> Self {
+        Self {
+            index: self.index,
+            flip_h: other.flip_h,
+            flip_v: other.flip_v,
+            flip_d: other.flip_d,
+        }
+    }
+}
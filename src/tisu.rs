@@ -1,19 +1,154 @@
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
+use notify::{RecursiveMode, Watcher};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use tiled::Loader;
-use tisu::filter_importer::FilterImporter;
+use tisu::apply_stats::FilterCollectionStats;
+use tisu::filter::{ApplyCallback, ApplyMode, ApplyOptions, FilterCollection};
+use tisu::filter_example::FilterExample;
+use tisu::filter_exporter::FilterExporter;
+use tisu::filter_loader::{load_filters, load_filters_with_files};
+use tisu::layered_map::{Layer, LayerContent, LayeredMap};
+use tisu::map::Map;
 use tisu::map_exporter::MapExporter;
-use tisu::map_importer::MapImporter;
+use tisu::map_importer::{LoadResult, MapImporter};
+use tisu::map_object::MapObject;
+#[cfg(feature = "serde")]
+use tisu::pipeline::Pipeline;
+use tisu::region_mask;
+use tisu::sequence::apply_sequence;
+#[cfg(feature = "serde")]
+use tisu::structured_filter_exporter::StructuredFilterExporter;
+use tisu::text_filter_exporter::TextFilterExporter;
+use tisu::tile_index::TileIndex;
+use tisu::tiled_filter_checker::{self, Severity};
+use tisu::tiled_filter_exporter::TiledFilterExporter;
 use tisu::tiled_filter_importer::TiledFilterImporter;
 use tisu::tiled_map_exporter::TiledMapExporter;
 use tisu::tiled_map_importer::TiledMapImporter;
+use tisu::tiled_tile::TiledTile;
 use tisu::tisu_error::TisuError;
 use tisu::vector2::Vector2u;
+use tisu::wfc::{OverlappingModel, DEFAULT_MAX_BACKTRACKS};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct CmdLineArgs {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Applies filters to a map
+    Apply(ApplyArgs),
+    /// Checks a filters file for problems
+    Check(CheckArgs),
+    /// Runs filter examples and compares the results to the expected output
+    Test(TestArgs),
+    /// Converts a filters file to a different format
+    Convert(ConvertArgs),
+    /// Applies filters to a map whenever the map, the filters or their
+    /// tilesets change
+    Watch(WatchArgs),
+    /// Generates a map from the patterns of an example map (Wave Function
+    /// Collapse) and optionally applies filters to it
+    Generate(GenerateArgs),
+}
+
+#[derive(Args)]
+struct ApplyArgs {
+    /// Output file path
+    #[arg(short, long, default_value = "output.tmx")]
+    output: PathBuf,
+    /// Input file path
+    #[arg(short, long, required_unless_present = "maps", conflicts_with = "maps")]
+    input: Option<PathBuf>,
+    /// Input maps to process in a batch (files, directories or glob patterns)
+    #[arg(requires = "out_dir", conflicts_with_all = ["trace", "step"])]
+    maps: Vec<PathBuf>,
+    /// Directory to write the maps processed in a batch to
+    #[arg(long)]
+    out_dir: Option<PathBuf>,
+    /// Number of threads used to process maps in a batch (defaults to the
+    /// available parallelism)
+    #[arg(short, long)]
+    jobs: Option<usize>,
+    /// Filters file path
+    #[cfg_attr(not(feature = "serde"), arg(short, long, required = true))]
+    #[cfg_attr(
+        feature = "serde",
+        arg(short, long, required_unless_present = "pipeline")
+    )]
+    filters: Option<PathBuf>,
+    /// Pipeline manifest path, used instead of a filters file
+    #[cfg(feature = "serde")]
+    #[arg(short, long, conflicts_with = "filters")]
+    pipeline: Option<PathBuf>,
+    /// Enables the pipeline steps with the given tag (can be repeated)
+    #[cfg(feature = "serde")]
+    #[arg(long, requires = "pipeline")]
+    tag: Vec<String>,
+    /// Directory to write intermediate maps to
+    #[arg(long)]
+    trace: Option<PathBuf>,
+    /// Print intermediate maps and wait for Enter before continuing
+    #[arg(long)]
+    step: bool,
+    /// Trace/step after each filter instead of after each filter collection
+    #[arg(long)]
+    per_filter: bool,
+    /// Seed for the random number generator
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Writes statistics of the applied filters (tested positions, matches,
+    /// changed tiles and time) to the given JSON file, for each map of a batch
+    #[cfg(feature = "serde")]
+    #[arg(long)]
+    report: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct CheckArgs {
+    /// Filters file path
+    filters: PathBuf,
+}
+
+#[derive(Args)]
+struct TestArgs {
+    /// Filters file containing example groups, or a directory of test cases
+    /// (each containing input.tmx, expected_output.tmx and optionally
+    /// filter_collection.tmx)
+    path: PathBuf,
+    /// Filters file path, used for test cases without filter_collection.tmx
+    #[arg(short, long)]
+    filters: Option<PathBuf>,
+    /// Seed for the random number generator
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+#[derive(Args)]
+struct ConvertArgs {
+    /// Input filters file path
+    input: PathBuf,
+    /// Output filters file path
+    output: PathBuf,
+    /// Tileset used when writing a .tmx file (defaults to the tileset of the
+    /// input file)
+    #[arg(short, long)]
+    tileset: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct WatchArgs {
     /// Output file path
     #[arg(short, long, default_value = "output.tmx")]
     output: PathBuf,
@@ -23,6 +158,52 @@ struct CmdLineArgs {
     /// Filters file path
     #[arg(short, long)]
     filters: PathBuf,
+    /// Seed for the random number generator
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+#[derive(Args)]
+struct GenerateArgs {
+    /// Output file path
+    #[arg(short, long, default_value = "output.tmx")]
+    output: PathBuf,
+    /// Example map file path
+    #[arg(short, long)]
+    example: PathBuf,
+    /// Width and height of the patterns learned from the example map
+    #[arg(short = 'n', long, default_value_t = 3)]
+    pattern_size: u32,
+    /// Width of the generated map
+    #[arg(long, required_unless_present = "input", conflicts_with = "input")]
+    width: Option<u32>,
+    /// Height of the generated map
+    #[arg(long, required_unless_present = "input", conflicts_with = "input")]
+    height: Option<u32>,
+    /// Map whose non-empty tiles are kept in the generated map (which has the
+    /// size of this map)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// Filters file path, applied to the generated map. Filter collections
+    /// restricted to a region need an input map, whose regions are used
+    #[arg(short, long)]
+    filters: Option<PathBuf>,
+    /// Maximum number of times generation backtracks before giving up
+    #[arg(long, default_value_t = DEFAULT_MAX_BACKTRACKS)]
+    max_backtracks: usize,
+    /// Seed for the random number generator
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+/// Loads the filters to apply, either from the filters file or from the
+/// pipeline manifest.
+fn load_apply_filters(args: &ApplyArgs) -> Result<Vec<FilterCollection<TiledTile>>, TisuError> {
+    #[cfg(feature = "serde")]
+    if let Some(pipeline) = &args.pipeline {
+        return Pipeline::load(pipeline)?.load_filters(&args.tag);
+    }
+    load_filters(args.filters.as_ref().ok_or(TisuError::InvalidArgument)?)
 }
 
 fn load_tile_size(file: impl AsRef<Path>) -> Result<Vector2u, TisuError> {
@@ -33,21 +214,808 @@ fn load_tile_size(file: impl AsRef<Path>) -> Result<Vector2u, TisuError> {
     Ok((tsx_tileset.tile_width, tsx_tileset.tile_height).into())
 }
 
-fn main() {
-    let args = CmdLineArgs::parse();
+fn create_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_os_rng(),
+    }
+}
+
+/// Records the intermediate maps produced while applying filters.
+struct Tracer<'a> {
+    args: &'a ApplyArgs,
+    tile_size: Vector2u,
+    tileset_path: &'a Path,
+    previous: Map<TiledTile>,
+    count: usize,
+    stepping: bool,
+}
+
+impl<'a> Tracer<'a> {
+    fn new(
+        args: &'a ApplyArgs,
+        tile_size: Vector2u,
+        tileset_path: &'a Path,
+        input: &Map<TiledTile>,
+    ) -> Self {
+        if let Some(trace_dir) = &args.trace {
+            std::fs::create_dir_all(trace_dir).expect("Failed to create trace directory");
+        }
+        Self {
+            args,
+            tile_size,
+            tileset_path,
+            previous: input.clone(),
+            count: 0,
+            stepping: args.step,
+        }
+    }
+
+    fn snapshot(&mut self, label: &str, map: &Map<TiledTile>) {
+        self.count += 1;
+
+        if let Some(trace_dir) = &self.args.trace {
+            let file = trace_dir.join(format!("{:03}_{}.tmx", self.count, label));
+            TiledMapExporter::save_with_objects(file, map, &[], self.tile_size, self.tileset_path)
+                .expect("Failed to save intermediate map");
+        }
+
+        if self.stepping {
+            println!("[{}] {}", self.count, label);
+            print!(
+                "{}",
+                map.to_highlighted_string(&self.previous)
+                    .expect("Failed to render map")
+            );
+            print!("Press Enter to continue, 'q' to run to completion: ");
+            _ = std::io::stdout().flush();
+            let mut line = String::new();
+            _ = std::io::stdin().lock().read_line(&mut line);
+            if line.trim() == "q" {
+                self.stepping = false;
+            }
+        }
+
+        self.previous = map.clone();
+    }
+}
+
+/// Returns the mask of the region of the input map that the given filter
+/// collection is restricted to, if any.
+fn region_mask(
+    filter_collection: &FilterCollection<TiledTile>,
+    load_result: &LoadResult<TiledTile>,
+) -> Result<Option<Map<bool>>, TisuError> {
+    filter_collection
+        .properties
+        .region
+        .as_ref()
+        .map(|region| region_mask::resolve(&load_result.regions, region))
+        .transpose()
+}
+
+/// Saves the given input map with its top tile layer replaced by the given
+/// output, adding the given objects in an object layer above all the layers
+/// (if there are any). The other layers of the input map are kept.
+fn save_output(
+    file: &Path,
+    input: &LayeredMap<TiledTile>,
+    output: Map<TiledTile>,
+    objects: Vec<MapObject>,
+    tile_size: Vector2u,
+    tileset_path: &Path,
+) -> Result<(), TisuError> {
+    let mut layered_map = input.clone();
+    *layered_map
+        .top_tile_layer_mut()
+        .ok_or(TisuError::NotFound)? = output;
+    if !objects.is_empty() {
+        layered_map
+            .layers
+            .push(Layer::new("Objects", LayerContent::Objects(objects)));
+    }
+    TiledMapExporter::save(file, &layered_map, tile_size, tileset_path)
+}
+
+/// Statistics of applying filter collections to a map, written by `apply
+/// --report`.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct Report<'a> {
+    /// Wall-clock time spent applying all the filter collections, in seconds
+    #[serde(serialize_with = "tisu::apply_stats::serialize_seconds")]
+    duration: Duration,
+    filter_collections: Vec<CollectionReport<'a>>,
+}
+
+/// Reports of the maps processed in a batch, written by `apply --report`.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct BatchReport<'a> {
+    maps: Vec<MapReport<'a>>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct MapReport<'a> {
+    map: String,
+    /// Reason why the map failed (if it did)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// Statistics of the map (if it succeeded)
+    #[serde(flatten)]
+    report: Option<Report<'a>>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct CollectionReport<'a> {
+    name: &'a str,
+    /// Number of times the filter collection was applied (see `until`)
+    runs: usize,
+    #[serde(flatten)]
+    stats: FilterCollectionStats,
+}
+
+#[cfg(feature = "serde")]
+impl<'a> Report<'a> {
+    fn new(filter_collections: &'a [FilterCollection<TiledTile>]) -> Self {
+        Self {
+            duration: Duration::ZERO,
+            filter_collections: filter_collections
+                .iter()
+                .map(|filter_collection| CollectionReport {
+                    name: &filter_collection.name,
+                    runs: 0,
+                    stats: FilterCollectionStats::new(filter_collection.filters.len()),
+                })
+                .collect(),
+        }
+    }
+
+    fn add_run(&mut self, collection_idx: usize, stats: &FilterCollectionStats) {
+        let collection_report = &mut self.filter_collections[collection_idx];
+        collection_report.runs += 1;
+        collection_report.stats += stats;
+    }
+}
+
+/// Writes the given report to the given JSON file.
+#[cfg(feature = "serde")]
+fn write_report(file: &Path, report: &impl serde::Serialize) {
+    let file = std::fs::File::create(file).expect("Failed to create report");
+    serde_json::to_writer_pretty(file, report).expect("Failed to write report");
+}
+
+/// Applies the given filter collection, restricted to the given region mask if
+/// there is one, and adds the objects emitted by its filters to the given
+/// objects. Pattern matches are found with the given index of the input map.
+fn apply_filter_collection(
+    filter_collection: &FilterCollection<TiledTile>,
+    input: &Map<TiledTile>,
+    index: &TileIndex<TiledTile>,
+    destination: &mut Map<TiledTile>,
+    mask: Option<&Map<bool>>,
+    objects: &mut Vec<MapObject>,
+    rng: &mut StdRng,
+) -> Result<FilterCollectionStats, TisuError> {
+    let options = &mut ApplyOptions {
+        mask,
+        objects: Some(objects),
+        index: Some(index),
+        ..ApplyOptions::new(rng)
+    };
+    filter_collection.apply_with(input, destination, options)
+}
+
+/// Statistics of each run of a filter collection (by index), and the time
+/// spent applying all the filter collections
+type MapRuns = (Vec<(usize, FilterCollectionStats)>, Duration);
+
+/// Applies the filters to the map of the given input file and saves the
+/// result to the given output file. Returns the statistics of the runs.
+fn apply_map(
+    filter_collections: &[FilterCollection<TiledTile>],
+    input_file: &Path,
+    output_file: &Path,
+    rng: &mut StdRng,
+) -> Result<MapRuns, TisuError> {
+    let load_result = TiledMapImporter::load(input_file)?;
+    let tile_size = load_tile_size(&load_result.tileset_path)?;
+    let input = load_result
+        .map
+        .top_tile_layer()
+        .ok_or(TisuError::NotFound)?;
+    let index = TileIndex::new(input);
+    let mut new_map = input.clone();
+    let mut objects = vec![];
+    let mut runs = vec![];
+    let start = Instant::now();
+    apply_sequence(
+        filter_collections,
+        &mut new_map,
+        |collection_idx, filter_collection, new_map| {
+            let mask = region_mask(filter_collection, &load_result)?;
+            let stats = apply_filter_collection(
+                filter_collection,
+                input,
+                &index,
+                new_map,
+                mask.as_ref(),
+                &mut objects,
+                rng,
+            )?;
+            let changes = stats.changes();
+            runs.push((collection_idx, stats));
+            Ok(changes)
+        },
+    )?;
+    let duration = start.elapsed();
+    save_output(
+        output_file,
+        &load_result.map,
+        new_map,
+        objects,
+        tile_size,
+        &load_result.tileset_path,
+    )?;
+    Ok((runs, duration))
+}
 
-    let load_result = TiledMapImporter::load(&args.input).expect("Failed to load map");
-    let filter_collections =
-        TiledFilterImporter::load(&args.filters).expect("Failed to load filters");
-    let mut new_map = load_result.map_layers[0].clone();
+/// Expands the given map arguments (files, directories or glob patterns) to a
+/// list of map files. Directories are expanded to the `.tmx` files they
+/// contain.
+fn collect_maps(maps: &[PathBuf]) -> Result<Vec<PathBuf>, TisuError> {
+    let mut result = vec![];
+    for map in maps {
+        let mut files: Vec<PathBuf> = if map.is_dir() {
+            std::fs::read_dir(map)
+                .map_err(|_| TisuError::InvalidArgument)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|extension| extension == "tmx"))
+                .collect()
+        } else if map.exists() {
+            vec![map.clone()]
+        } else {
+            let pattern = map.to_str().ok_or(TisuError::InvalidArgument)?;
+            glob::glob(pattern)
+                .map_err(|_| TisuError::InvalidArgument)?
+                .filter_map(Result::ok)
+                .collect()
+        };
+        if files.is_empty() {
+            return Err(TisuError::NotFound);
+        }
+        files.sort();
+        result.extend(files);
+    }
+    Ok(result)
+}
+
+/// Applies the filters to all the given maps, distributing them among worker
+/// threads, and prints a summary.
+fn apply_batch(args: &ApplyArgs, filter_collections: &[FilterCollection<TiledTile>]) -> ExitCode {
+    let maps = collect_maps(&args.maps).expect("Failed to find maps");
+    let out_dir = args.out_dir.as_ref().expect("Missing output directory");
+    std::fs::create_dir_all(out_dir).expect("Failed to create output directory");
+    let mut file_names = HashSet::new();
+    for map in &maps {
+        if !file_names.insert(map.file_name()) {
+            eprintln!(
+                "Multiple maps named {:?} would be written to the output directory",
+                map.file_name().unwrap_or_default()
+            );
+            return ExitCode::FAILURE;
+        }
+    }
 
-    for filter_collection in &filter_collections {
-        filter_collection
-            .apply(&load_result.map_layers[0], &mut new_map)
-            .expect("Failed to apply filters");
+    let jobs = args
+        .jobs
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()))
+        .clamp(1, maps.len().max(1));
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, Result<MapRuns, TisuError>)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = vec![];
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(map) = maps.get(idx) else {
+                            break;
+                        };
+                        // Derive a seed per map, so the results don't depend on
+                        // the order in which the maps are processed
+                        let mut rng =
+                            create_rng(args.seed.map(|seed| seed.wrapping_add(idx as u64)));
+                        let output_file = out_dir.join(map.file_name().unwrap_or_default());
+                        results.push((
+                            idx,
+                            apply_map(filter_collections, map, &output_file, &mut rng),
+                        ));
+                    }
+                    results
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("Worker thread panicked"))
+            .collect()
+    });
+    results.sort_by_key(|(idx, _)| *idx);
+
+    #[cfg(feature = "serde")]
+    if let Some(report_file) = &args.report {
+        let maps = results
+            .iter()
+            .map(|(idx, result)| MapReport {
+                map: maps[*idx].display().to_string(),
+                error: result.as_ref().err().map(ToString::to_string),
+                report: result.as_ref().ok().map(|(runs, duration)| {
+                    let mut report = Report::new(filter_collections);
+                    for (collection_idx, stats) in runs {
+                        report.add_run(*collection_idx, stats);
+                    }
+                    report.duration = *duration;
+                    report
+                }),
+            })
+            .collect();
+        write_report(report_file, &BatchReport { maps });
+    }
+
+    let mut failed = 0;
+    for (idx, result) in &results {
+        match result {
+            Ok(_) => println!("ok      {}", maps[*idx].display()),
+            Err(error) => {
+                failed += 1;
+                println!("FAILED  {}: {}", maps[*idx].display(), error);
+            }
+        }
+    }
+    println!("{} succeeded, {} failed", results.len() - failed, failed);
+    if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
     }
+}
+
+fn apply(args: &ApplyArgs) -> ExitCode {
+    let filter_collections = load_apply_filters(args).expect("Failed to load filters");
+    let Some(input_file) = &args.input else {
+        return apply_batch(args, &filter_collections);
+    };
 
+    let load_result = TiledMapImporter::load(input_file).expect("Failed to load map");
     let tile_size = load_tile_size(&load_result.tileset_path).expect("Failed to load tileset");
-    TiledMapExporter::save(&args.output, &new_map, tile_size, &load_result.tileset_path)
-        .expect("Failed to save map");
+    let input = load_result
+        .map
+        .top_tile_layer()
+        .expect("Map has no tile layer");
+    let index = TileIndex::new(input);
+    let mut new_map = input.clone();
+    let mut objects = vec![];
+
+    let mut rng = create_rng(args.seed);
+    let mut tracer = Tracer::new(args, tile_size, &load_result.tileset_path, input);
+    let tracing = args.trace.is_some() || args.step;
+    #[cfg(feature = "serde")]
+    let mut report = Report::new(&filter_collections);
+    #[cfg(feature = "serde")]
+    let start = Instant::now();
+
+    apply_sequence(
+        &filter_collections,
+        &mut new_map,
+        |collection_idx, filter_collection, new_map| {
+            let mask = region_mask(filter_collection, &load_result)?;
+            // Filter collections in markov mode, region operations and
+            // connectivity steps are traced as a whole
+            let per_filter = tracing
+                && args.per_filter
+                && filter_collection.properties.mode == ApplyMode::All
+                && filter_collection.region_op.is_none()
+                && filter_collection.connectivity.is_none();
+            let mut trace_filter = |filter_idx, map: &Map<TiledTile>| {
+                tracer.snapshot(
+                    &format!("collection_{}_filter_{}", collection_idx, filter_idx),
+                    map,
+                )
+            };
+            let options = &mut ApplyOptions {
+                mask: mask.as_ref(),
+                objects: Some(&mut objects),
+                index: Some(&index),
+                callback: per_filter.then_some(&mut trace_filter as &mut ApplyCallback<_>),
+                ..ApplyOptions::new(&mut rng)
+            };
+            let stats = filter_collection.apply_with(input, new_map, options)?;
+            if tracing && !per_filter {
+                tracer.snapshot(&format!("collection_{}", collection_idx), new_map);
+            }
+            #[cfg(feature = "serde")]
+            report.add_run(collection_idx, &stats);
+            Ok(stats.changes())
+        },
+    )
+    .expect("Failed to apply filters");
+
+    #[cfg(feature = "serde")]
+    if let Some(report_file) = &args.report {
+        report.duration = start.elapsed();
+        write_report(report_file, &report);
+    }
+
+    save_output(
+        &args.output,
+        &load_result.map,
+        new_map,
+        objects,
+        tile_size,
+        &load_result.tileset_path,
+    )
+    .expect("Failed to save map");
+    ExitCode::SUCCESS
+}
+
+fn generate(args: &GenerateArgs) -> ExitCode {
+    let example = TiledMapImporter::load(&args.example).expect("Failed to load example map");
+    let tile_size = load_tile_size(&example.tileset_path).expect("Failed to load tileset");
+    let example_map = example
+        .map
+        .top_tile_layer()
+        .expect("Example map has no tile layer");
+    let mut model =
+        OverlappingModel::new(example_map, args.pattern_size).expect("Failed to learn patterns");
+    model.set_max_backtracks(args.max_backtracks);
+
+    let filter_collections = match &args.filters {
+        Some(filters) => load_filters(filters).expect("Failed to load filters"),
+        None => vec![],
+    };
+    // Regions are read from the input map, as the generated map has its size
+    let input = args
+        .input
+        .as_ref()
+        .map(|input_file| TiledMapImporter::load(input_file).expect("Failed to load map"));
+    if input.is_none() {
+        if let Some(filter_collection) = filter_collections
+            .iter()
+            .find(|filter_collection| filter_collection.properties.region.is_some())
+        {
+            eprintln!(
+                "Filter collection '{}' is restricted to a region, which needs an input map",
+                filter_collection.name
+            );
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let mut rng = create_rng(args.seed);
+    let generated = match &input {
+        Some(load_result) => {
+            let input = load_result
+                .map
+                .top_tile_layer()
+                .expect("Map has no tile layer");
+            model.generate_constrained(input, &TiledTile::default(), &mut rng)
+        }
+        None => {
+            let size = Vector2u::new(args.width.unwrap_or(1), args.height.unwrap_or(1));
+            model.generate(size, &mut rng)
+        }
+    }
+    .expect("Failed to generate map");
+
+    let index = TileIndex::new(&generated);
+    let mut new_map = generated.clone();
+    let mut objects = vec![];
+    apply_sequence(
+        &filter_collections,
+        &mut new_map,
+        |_, filter_collection, new_map| {
+            let mask = match &input {
+                Some(load_result) => region_mask(filter_collection, load_result)?,
+                None => None,
+            };
+            apply_filter_collection(
+                filter_collection,
+                &generated,
+                &index,
+                new_map,
+                mask.as_ref(),
+                &mut objects,
+                &mut rng,
+            )
+            .map(|stats| stats.changes())
+        },
+    )
+    .expect("Failed to apply filters");
+
+    TiledMapExporter::save_with_objects(
+        &args.output,
+        &new_map,
+        &objects,
+        tile_size,
+        &example.tileset_path,
+    )
+    .expect("Failed to save map");
+    ExitCode::SUCCESS
+}
+
+fn check(args: &CheckArgs) -> ExitCode {
+    let diagnostics = tiled_filter_checker::check(&args.filters).expect("Failed to load filters");
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+
+    if diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error)
+    {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Loads the top tile layer of the given map file.
+fn load_top_tile_layer(file: &Path) -> Result<Map<TiledTile>, TisuError> {
+    TiledMapImporter::load(file)?
+        .map
+        .top_tile_layer()
+        .cloned()
+        .ok_or(TisuError::NotFound)
+}
+
+/// Loads the examples and filters of a test case directory.
+fn load_test_case(
+    dir: &Path,
+    filters: Option<&PathBuf>,
+) -> Result<(FilterExample<TiledTile>, PathBuf), TisuError> {
+    let filters_file = dir.join("filter_collection.tmx");
+    let filters_file = if filters_file.exists() {
+        filters_file
+    } else {
+        filters.ok_or(TisuError::NotFound)?.clone()
+    };
+    let example = FilterExample {
+        name: dir.display().to_string(),
+        input: load_top_tile_layer(&dir.join("input.tmx"))?,
+        expected_output: load_top_tile_layer(&dir.join("expected_output.tmx"))?,
+    };
+    Ok((example, filters_file))
+}
+
+fn is_test_case(dir: &Path) -> bool {
+    dir.join("input.tmx").exists() && dir.join("expected_output.tmx").exists()
+}
+
+/// Collects the examples to run, each paired with its filters file.
+fn collect_examples(args: &TestArgs) -> Vec<(FilterExample<TiledTile>, PathBuf)> {
+    if args.path.is_file() {
+        return TiledFilterImporter::load_examples(&args.path)
+            .expect("Failed to load examples")
+            .into_iter()
+            .map(|example| (example, args.path.clone()))
+            .collect();
+    }
+
+    let mut dirs = vec![];
+    if is_test_case(&args.path) {
+        dirs.push(args.path.clone());
+    } else {
+        for entry in std::fs::read_dir(&args.path).expect("Failed to read test directory") {
+            let path = entry.expect("Failed to read test directory").path();
+            if path.is_dir() && is_test_case(&path) {
+                dirs.push(path);
+            }
+        }
+        dirs.sort();
+    }
+
+    dirs.iter()
+        .map(|dir| load_test_case(dir, args.filters.as_ref()).expect("Failed to load test case"))
+        .collect()
+}
+
+fn test(args: &TestArgs) -> ExitCode {
+    let mut passed = 0;
+    let mut failed = 0;
+    for (example, filters_file) in collect_examples(args) {
+        let filter_collections = load_filters(&filters_file).expect("Failed to load filters");
+        let mut rng = StdRng::seed_from_u64(args.seed);
+        let result = match example.run(&filter_collections, &mut rng) {
+            Ok(result) => result,
+            Err(err) => {
+                println!("FAIL {}: {}", example.name, err);
+                failed += 1;
+                continue;
+            }
+        };
+
+        if result.passed() {
+            println!("PASS {}", example.name);
+            passed += 1;
+        } else {
+            println!("FAIL {}", example.name);
+            for mismatch in &result.mismatches {
+                println!(
+                    "  ({}, {}): expected {}, got {}",
+                    mismatch.position.x, mismatch.position.y, mismatch.expected, mismatch.actual
+                );
+            }
+            print!(
+                "{}",
+                result
+                    .output
+                    .to_highlighted_string(&example.expected_output)
+                    .expect("Failed to render map")
+            );
+            failed += 1;
+        }
+    }
+
+    println!("{} passed, {} failed", passed, failed);
+    if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn convert(args: &ConvertArgs) {
+    let filter_collections = load_filters(&args.input).expect("Failed to load filters");
+    match args
+        .output
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("tmx") => {
+            let tileset_path = match &args.tileset {
+                Some(tileset_path) => tileset_path.canonicalize().expect("Failed to find tileset"),
+                None => {
+                    TiledMapImporter::load(&args.input)
+                        .expect("Failed to load the input tileset, use --tileset")
+                        .tileset_path
+                }
+            };
+            let tile_size = load_tile_size(&tileset_path).expect("Failed to load tileset");
+            TiledFilterExporter::save(&args.output, &filter_collections, tile_size, &tileset_path)
+                .expect("Failed to save filters");
+        }
+        Some("txt") => {
+            TextFilterExporter::save(&args.output, &filter_collections, Vector2u::default(), "")
+                .expect("Failed to save filters")
+        }
+        #[cfg(feature = "serde")]
+        Some("json") | Some("toml") | Some("yaml") | Some("yml") => StructuredFilterExporter::save(
+            &args.output,
+            &filter_collections,
+            Vector2u::default(),
+            "",
+        )
+        .expect("Failed to save filters"),
+        _ => panic!("Unsupported output file format"),
+    }
+}
+
+/// Returns the path used to identify the given file in file watcher events: the
+/// canonical path of its directory joined with its file name (the file itself
+/// may not exist while it's being replaced).
+fn watched_path(file: &Path) -> PathBuf {
+    let directory = match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    match (directory.canonicalize(), file.file_name()) {
+        (Ok(directory), Some(file_name)) => directory.join(file_name),
+        _ => file.to_path_buf(),
+    }
+}
+
+/// Returns the paths of the external tilesets referenced by the given TMX file.
+fn tileset_files(file: &Path) -> Vec<PathBuf> {
+    let mut loader = Loader::new();
+    loader
+        .load_tmx_map(file)
+        .map(|tmx_map| {
+            tmx_map
+                .tilesets()
+                .iter()
+                .map(|tileset| tileset.source.clone())
+                .filter(|source| {
+                    source
+                        .extension()
+                        .is_some_and(|extension| extension == "tsx")
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Applies the filters once, adding the files the result depends on to the
+/// given list (even if applying the filters fails).
+fn watch_run(args: &WatchArgs, files: &mut Vec<PathBuf>) -> Result<(), TisuError> {
+    files.push(args.input.clone());
+    files.push(args.filters.clone());
+    files.extend(tileset_files(&args.input));
+
+    let (filter_collections, filter_files) = load_filters_with_files(&args.filters)?;
+    for filter_file in filter_files {
+        if filter_file
+            .extension()
+            .is_some_and(|extension| extension == "tmx")
+        {
+            files.extend(tileset_files(&filter_file));
+        }
+        files.push(filter_file);
+    }
+
+    let mut rng = create_rng(args.seed);
+    apply_map(&filter_collections, &args.input, &args.output, &mut rng).map(|_| ())
+}
+
+fn watch(args: &WatchArgs) -> ExitCode {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).expect("Failed to create file watcher");
+    let mut watched_directories = HashSet::new();
+
+    loop {
+        let mut files = vec![];
+        match watch_run(args, &mut files) {
+            Ok(()) => println!("Saved {}", args.output.display()),
+            Err(error) => println!("error: {}", error),
+        }
+        let files: HashSet<PathBuf> = files.iter().map(|file| watched_path(file)).collect();
+
+        // Watch the directories containing the files rather than the files
+        // themselves, so files replaced on save keep being watched
+        for file in &files {
+            if let Some(directory) = file.parent() {
+                if !watched_directories.contains(directory)
+                    && watcher
+                        .watch(directory, RecursiveMode::NonRecursive)
+                        .is_ok()
+                {
+                    watched_directories.insert(directory.to_path_buf());
+                }
+            }
+        }
+        println!("Watching for changes...");
+
+        loop {
+            let Ok(event) = receiver.recv() else {
+                return ExitCode::FAILURE;
+            };
+            let Ok(event) = event else {
+                continue;
+            };
+            if !event.kind.is_access() && event.paths.iter().any(|path| files.contains(path)) {
+                break;
+            }
+        }
+        // Let the editor finish writing before running again
+        std::thread::sleep(Duration::from_millis(100));
+        while receiver.try_recv().is_ok() {}
+    }
+}
+
+fn main() -> ExitCode {
+    let args = CmdLineArgs::parse();
+
+    match &args.command {
+        Command::Apply(apply_args) => apply(apply_args),
+        Command::Check(check_args) => check(check_args),
+        Command::Test(test_args) => test(test_args),
+        Command::Convert(convert_args) => {
+            convert(convert_args);
+            ExitCode::SUCCESS
+        }
+        Command::Watch(watch_args) => watch(watch_args),
+        Command::Generate(generate_args) => generate(generate_args),
+    }
 }
@@ -5,6 +5,7 @@ use std::{
 
 /// A generic 2d vector
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vector2<T> {
     /// X coordinate of the vector
     pub x: T,
@@ -53,6 +54,25 @@ impl<T> Vector2<T> {
     }
 }
 
+impl Vector2u {
+    /// Adds the given vector, returning `None` if a coordinate overflows.
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        Some(Vector2::new(
+            self.x.checked_add(rhs.x)?,
+            self.y.checked_add(rhs.y)?,
+        ))
+    }
+
+    /// Multiplies the coordinates by the coordinates of the given vector,
+    /// returning `None` if a coordinate overflows.
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        Some(Vector2::new(
+            self.x.checked_mul(rhs.x)?,
+            self.y.checked_mul(rhs.y)?,
+        ))
+    }
+}
+
 impl<T> From<(T, T)> for Vector2<T>
 where
     T: PartialOrd + std::convert::From<u16>,
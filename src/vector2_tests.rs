@@ -1,4 +1,4 @@
-use crate::vector2::Vector2;
+use crate::vector2::{Vector2, Vector2u};
 
 #[test]
 fn test_constructor() {
@@ -36,6 +36,22 @@ fn test_sub() {
     assert_eq!(result, (-18, 18).into());
 }
 
+#[test]
+fn test_checked_add_and_mul() {
+    let vector = Vector2u::new(3, u32::MAX / 2);
+
+    assert_eq!(
+        vector.checked_add((1, 1).into()),
+        Some((4, u32::MAX / 2 + 1).into())
+    );
+    assert_eq!(vector.checked_add((0, u32::MAX).into()), None);
+    assert_eq!(
+        vector.checked_mul((2, 2).into()),
+        Some((6, u32::MAX - 1).into())
+    );
+    assert_eq!(vector.checked_mul((1, 3).into()), None);
+}
+
 #[test]
 fn test_one() {
     let one_i32 = Vector2::<i32>::one();
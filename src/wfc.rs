@@ -0,0 +1,461 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use rand::Rng;
+
+use crate::map::Map;
+use crate::tisu_error::TisuError;
+use crate::vector2::Vector2u;
+
+/// Offsets of the neighbors of a wave position
+const DIRECTIONS: [(i32, i32); 4] = [(-1, 0), (0, -1), (1, 0), (0, 1)];
+
+/// Default maximum number of times generation backtracks before giving up
+pub const DEFAULT_MAX_BACKTRACKS: usize = 1000;
+
+/// A set of pattern indices
+type Bitset = Vec<u64>;
+
+fn bitset_contains(bitset: &Bitset, idx: usize) -> bool {
+    bitset[idx / 64] & (1 << (idx % 64)) != 0
+}
+
+fn bitset_insert(bitset: &mut Bitset, idx: usize) {
+    bitset[idx / 64] |= 1 << (idx % 64);
+}
+
+fn bitset_remove(bitset: &mut Bitset, idx: usize) {
+    bitset[idx / 64] &= !(1 << (idx % 64));
+}
+
+/// Checks if pattern `q` placed at the given offset from pattern `p` agrees
+/// with it on the overlapping fields.
+fn patterns_agree<T: PartialEq>(p: &Map<T>, q: &Map<T>, offset: (i32, i32)) -> bool {
+    let n = p.size().x as i32;
+    let (dx, dy) = offset;
+    for x in dx.max(0)..(dx + n).min(n) {
+        for y in dy.max(0)..(dy + n).min(n) {
+            let p_field = p.get(Vector2u::new(x as u32, y as u32));
+            let q_field = q.get(Vector2u::new((x - dx) as u32, (y - dy) as u32));
+            if p_field != q_field {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// An overlapping model for Wave Function Collapse: the NxN patterns of an
+/// example map, which are combined into new maps where every NxN area is one
+/// of the patterns.
+#[derive(Clone, PartialEq, Debug)]
+pub struct OverlappingModel<T> {
+    /// Width and height of the patterns
+    pattern_size: u32,
+    /// Distinct patterns of the example map
+    patterns: Vec<Map<T>>,
+    /// Number of occurrences of each pattern in the example map
+    weights: Vec<u32>,
+    /// Patterns that can be placed next to each pattern, for each direction
+    compatible: [Vec<Bitset>; 4],
+    max_backtracks: usize,
+}
+
+impl<T> OverlappingModel<T> {
+    /// Creates a model from the patterns of the given size found in the given
+    /// example map.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pattern size is 0 or if the example is smaller
+    /// than a pattern.
+    pub fn new(example: &Map<T>, pattern_size: u32) -> Result<Self, TisuError>
+    where
+        T: Clone + Default + Eq + Hash,
+    {
+        if pattern_size == 0 {
+            return Err(TisuError::InvalidArgument);
+        }
+        let size = example.size();
+        if size.x < pattern_size || size.y < pattern_size {
+            return Err(TisuError::InvalidMapSize);
+        }
+
+        let mut patterns = vec![];
+        let mut weights = vec![];
+        let mut indices: HashMap<Vec<T>, usize> = HashMap::new();
+        for y in 0..=size.y - pattern_size {
+            for x in 0..=size.x - pattern_size {
+                let rect = (x, y, pattern_size, pattern_size).try_into()?;
+                let pattern = example.extract_segment(rect)?;
+                match indices.get(pattern.data()) {
+                    Some(idx) => weights[*idx] += 1,
+                    None => {
+                        indices.insert(pattern.data().to_vec(), patterns.len());
+                        patterns.push(pattern);
+                        weights.push(1);
+                    }
+                }
+            }
+        }
+
+        let words = patterns.len().div_ceil(64);
+        let compatible = DIRECTIONS.map(|offset| {
+            patterns
+                .iter()
+                .map(|p| {
+                    let mut bitset = vec![0; words];
+                    for (idx, q) in patterns.iter().enumerate() {
+                        if patterns_agree(p, q, offset) {
+                            bitset_insert(&mut bitset, idx);
+                        }
+                    }
+                    bitset
+                })
+                .collect()
+        });
+
+        Ok(Self {
+            pattern_size,
+            patterns,
+            weights,
+            compatible,
+            max_backtracks: DEFAULT_MAX_BACKTRACKS,
+        })
+    }
+
+    /// Returns the width and height of the patterns.
+    pub fn pattern_size(&self) -> u32 {
+        self.pattern_size
+    }
+
+    /// Returns the distinct patterns of the example map.
+    pub fn patterns(&self) -> &[Map<T>] {
+        &self.patterns
+    }
+
+    /// Returns the number of occurrences of each pattern in the example map.
+    pub fn weights(&self) -> &[u32] {
+        &self.weights
+    }
+
+    /// Sets the maximum number of times generation undoes a choice after
+    /// running into a contradiction before giving up.
+    pub fn set_max_backtracks(&mut self, max_backtracks: usize) {
+        self.max_backtracks = max_backtracks;
+    }
+
+    /// Generates a map of the given size, using the given random number
+    /// generator.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the size is smaller than a pattern or if no map
+    /// could be generated within the maximum number of backtracks.
+    pub fn generate<R>(&self, size: Vector2u, rng: &mut R) -> Result<Map<T>, TisuError>
+    where
+        T: Clone + Default + PartialEq,
+        R: Rng + ?Sized,
+    {
+        self.generate_in(size, None, rng)
+    }
+
+    /// Generates a map of the size of the given constraints map, where the
+    /// fields of the constraints map that differ from the given free value
+    /// (e.g. the wildcard or an empty tile) are kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the constraints map is smaller than a pattern or if
+    /// no map satisfying the constraints could be generated within the
+    /// maximum number of backtracks.
+    pub fn generate_constrained<R>(
+        &self,
+        constraints: &Map<T>,
+        free: &T,
+        rng: &mut R,
+    ) -> Result<Map<T>, TisuError>
+    where
+        T: Clone + Default + PartialEq,
+        R: Rng + ?Sized,
+    {
+        self.generate_in(constraints.size(), Some((constraints, free)), rng)
+    }
+
+    fn generate_in<R>(
+        &self,
+        size: Vector2u,
+        constraints: Option<(&Map<T>, &T)>,
+        rng: &mut R,
+    ) -> Result<Map<T>, TisuError>
+    where
+        T: Clone + Default + PartialEq,
+        R: Rng + ?Sized,
+    {
+        if size.x < self.pattern_size || size.y < self.pattern_size {
+            return Err(TisuError::InvalidMapSize);
+        }
+
+        let mut wave = Wave::new(
+            self,
+            size - Vector2u::new(self.pattern_size - 1, self.pattern_size - 1),
+        );
+        if let Some((constraints, free)) = constraints {
+            wave.constrain(constraints, free);
+        }
+        if !wave.propagate() {
+            return Err(TisuError::NotFound);
+        }
+
+        // Choices made so far, with the trail length before each choice
+        let mut choices: Vec<(usize, usize, usize)> = vec![];
+        let mut backtracks = 0;
+        while let Some(position) = wave.select_position(rng) {
+            let pattern = wave.select_pattern(position, rng);
+            choices.push((wave.trail.len(), position, pattern));
+            wave.collapse(position, pattern);
+            let mut consistent = wave.propagate();
+            while !consistent {
+                // Undo the last choice and rule it out instead
+                let (trail_len, position, pattern) = choices.pop().ok_or(TisuError::NotFound)?;
+                backtracks += 1;
+                if backtracks > self.max_backtracks {
+                    return Err(TisuError::NotFound);
+                }
+                wave.undo(trail_len);
+                wave.ban(position, pattern);
+                consistent = wave.propagate();
+            }
+        }
+
+        wave.output(size)
+    }
+}
+
+/// The state of a generation: the patterns that are still possible at each
+/// wave position (the top left corner of a pattern in the output).
+struct Wave<'a, T> {
+    model: &'a OverlappingModel<T>,
+    size: Vector2u,
+    possible: Vec<Bitset>,
+    counts: Vec<usize>,
+    sums_of_weights: Vec<f64>,
+    sums_of_weight_log_weights: Vec<f64>,
+    /// Bans in the order they were made, so they can be undone
+    trail: Vec<(usize, usize)>,
+    /// Positions whose bans haven't been propagated yet
+    pending: Vec<usize>,
+}
+
+impl<'a, T> Wave<'a, T> {
+    fn new(model: &'a OverlappingModel<T>, size: Vector2u) -> Self {
+        let cells = (size.x * size.y) as usize;
+        let pattern_count = model.patterns.len();
+        let mut all = vec![0; pattern_count.div_ceil(64)];
+        for idx in 0..pattern_count {
+            bitset_insert(&mut all, idx);
+        }
+        let sum_of_weights: f64 = model.weights.iter().map(|w| *w as f64).sum();
+        let sum_of_weight_log_weights: f64 = model
+            .weights
+            .iter()
+            .map(|w| *w as f64 * (*w as f64).ln())
+            .sum();
+        Self {
+            model,
+            size,
+            possible: vec![all; cells],
+            counts: vec![pattern_count; cells],
+            sums_of_weights: vec![sum_of_weights; cells],
+            sums_of_weight_log_weights: vec![sum_of_weight_log_weights; cells],
+            trail: vec![],
+            pending: vec![],
+        }
+    }
+
+    fn ban(&mut self, position: usize, pattern: usize) {
+        if !bitset_contains(&self.possible[position], pattern) {
+            return;
+        }
+        let weight = self.model.weights[pattern] as f64;
+        bitset_remove(&mut self.possible[position], pattern);
+        self.counts[position] -= 1;
+        self.sums_of_weights[position] -= weight;
+        self.sums_of_weight_log_weights[position] -= weight * weight.ln();
+        self.trail.push((position, pattern));
+        self.pending.push(position);
+    }
+
+    /// Undoes the bans made after the trail had the given length.
+    fn undo(&mut self, trail_len: usize) {
+        while self.trail.len() > trail_len {
+            let Some((position, pattern)) = self.trail.pop() else {
+                break;
+            };
+            let weight = self.model.weights[pattern] as f64;
+            bitset_insert(&mut self.possible[position], pattern);
+            self.counts[position] += 1;
+            self.sums_of_weights[position] += weight;
+            self.sums_of_weight_log_weights[position] += weight * weight.ln();
+        }
+        self.pending.clear();
+    }
+
+    /// Bans the patterns that disagree with the fixed fields of the given
+    /// constraints map.
+    fn constrain(&mut self, constraints: &Map<T>, free: &T)
+    where
+        T: PartialEq,
+    {
+        let n = self.model.pattern_size;
+        for position in 0..self.possible.len() {
+            let origin = self.point(position);
+            for (idx, pattern) in self.model.patterns.iter().enumerate() {
+                let disagrees = (0..n).any(|x| {
+                    (0..n).any(|y| {
+                        let point = Vector2u::new(x, y);
+                        match constraints.get(origin + point) {
+                            Ok(field) if field != free => pattern.get(point) != Ok(field),
+                            _ => false,
+                        }
+                    })
+                });
+                if disagrees {
+                    self.ban(position, idx);
+                }
+            }
+        }
+    }
+
+    /// Bans all the patterns but the given one at the given position.
+    fn collapse(&mut self, position: usize, pattern: usize) {
+        for idx in 0..self.model.patterns.len() {
+            if idx != pattern {
+                self.ban(position, idx);
+            }
+        }
+    }
+
+    /// Bans the patterns that can't be placed next to the possible patterns
+    /// of their neighbors. Returns false if a position has no possible
+    /// patterns left.
+    fn propagate(&mut self) -> bool {
+        if self.counts.contains(&0) {
+            self.pending.clear();
+            return false;
+        }
+        while let Some(position) = self.pending.pop() {
+            let point = self.point(position);
+            for (direction, offset) in DIRECTIONS.iter().enumerate() {
+                let x = point.x as i32 + offset.0;
+                let y = point.y as i32 + offset.1;
+                if x < 0 || y < 0 || x >= self.size.x as i32 || y >= self.size.y as i32 {
+                    continue;
+                }
+                let neighbor = self.position(Vector2u::new(x as u32, y as u32));
+
+                let mut allowed = vec![0; self.possible[position].len()];
+                for idx in self.possible_patterns(position) {
+                    for (word, compatible) in allowed
+                        .iter_mut()
+                        .zip(&self.model.compatible[direction][idx])
+                    {
+                        *word |= compatible;
+                    }
+                }
+                for idx in self.possible_patterns(neighbor) {
+                    if !bitset_contains(&allowed, idx) {
+                        self.ban(neighbor, idx);
+                    }
+                }
+                if self.counts[neighbor] == 0 {
+                    self.pending.clear();
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn possible_patterns(&self, position: usize) -> Vec<usize> {
+        (0..self.model.patterns.len())
+            .filter(|idx| bitset_contains(&self.possible[position], *idx))
+            .collect()
+    }
+
+    /// Returns the undecided position with the lowest entropy (ties are
+    /// broken randomly), or `None` if all positions are decided.
+    fn select_position<R>(&self, rng: &mut R) -> Option<usize>
+    where
+        R: Rng + ?Sized,
+    {
+        let mut result = None;
+        let mut min_entropy = f64::MAX;
+        for position in 0..self.counts.len() {
+            if self.counts[position] <= 1 {
+                continue;
+            }
+            let sum = self.sums_of_weights[position];
+            let entropy = sum.ln() - self.sums_of_weight_log_weights[position] / sum
+                + rng.random_range(0.0..1e-6);
+            if entropy < min_entropy {
+                min_entropy = entropy;
+                result = Some(position);
+            }
+        }
+        result
+    }
+
+    /// Picks one of the possible patterns at the given position, weighted by
+    /// their number of occurrences in the example.
+    fn select_pattern<R>(&self, position: usize, rng: &mut R) -> usize
+    where
+        R: Rng + ?Sized,
+    {
+        let patterns = self.possible_patterns(position);
+        let total: u32 = patterns.iter().map(|idx| self.model.weights[*idx]).sum();
+        let mut roll = rng.random_range(0..total);
+        for idx in &patterns {
+            let weight = self.model.weights[*idx];
+            if roll < weight {
+                return *idx;
+            }
+            roll -= weight;
+        }
+        patterns[patterns.len() - 1]
+    }
+
+    fn point(&self, position: usize) -> Vector2u {
+        let width = self.size.x as usize;
+        Vector2u::new((position % width) as u32, (position / width) as u32)
+    }
+
+    /// Returns the index of the given point in the pattern lists (see
+    /// `point`).
+    fn position(&self, point: Vector2u) -> usize {
+        point.y as usize * self.size.x as usize + point.x as usize
+    }
+
+    /// Returns the map made of the decided patterns. Fields near the right and
+    /// bottom edges are taken from the patterns of the last wave positions.
+    fn output(&self, size: Vector2u) -> Result<Map<T>, TisuError>
+    where
+        T: Clone + Default,
+    {
+        let mut map = Map::new(size);
+        for x in 0..size.x {
+            for y in 0..size.y {
+                let origin = Vector2u::new(x.min(self.size.x - 1), y.min(self.size.y - 1));
+                let position = self.position(origin);
+                let pattern = self
+                    .possible_patterns(position)
+                    .first()
+                    .copied()
+                    .ok_or(TisuError::Unexpected)?;
+                let field = self.model.patterns[pattern].get(Vector2u::new(x, y) - origin)?;
+                map.set((x, y).into(), field.clone())?;
+            }
+        }
+        Ok(map)
+    }
+}
@@ -0,0 +1,136 @@
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{map::Map, tisu_error::TisuError, vector2::Vector2u, wfc::OverlappingModel};
+
+fn create_example() -> Map<u32> {
+    // Rooms of floor (1) surrounded by walls (0)
+    Map::from_data([
+        [0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 1, 1, 0, 0, 1, 0, 0],
+        [0, 1, 1, 0, 0, 1, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 1, 0, 0, 1, 1, 1, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0],
+    ])
+    .unwrap()
+}
+
+fn contains_only_patterns(map: &Map<u32>, model: &OverlappingModel<u32>) -> bool {
+    let n = model.pattern_size();
+    (0..=map.size().x - n).all(|x| {
+        (0..=map.size().y - n).all(|y| {
+            let window = map
+                .extract_segment((x, y, n, n).try_into().unwrap())
+                .unwrap();
+            model.patterns().contains(&window)
+        })
+    })
+}
+
+#[test]
+fn test_new() {
+    let example = Map::from_data([[0, 1, 0, 1], [0, 1, 0, 1]]).unwrap();
+
+    let model = OverlappingModel::new(&example, 2).unwrap();
+
+    assert_eq!(
+        model.patterns(),
+        [
+            Map::from_data([[0, 1], [0, 1]]).unwrap(),
+            Map::from_data([[1, 0], [1, 0]]).unwrap(),
+        ]
+    );
+    assert_eq!(model.weights(), [2, 1]);
+}
+
+#[test]
+fn test_new_failure() {
+    let example = create_example();
+
+    assert_eq!(
+        OverlappingModel::new(&example, 0),
+        Err(TisuError::InvalidArgument)
+    );
+    assert_eq!(
+        OverlappingModel::new(&example, 9),
+        Err(TisuError::InvalidMapSize)
+    );
+}
+
+#[test]
+fn test_generate() {
+    let model = OverlappingModel::new(&create_example(), 3).unwrap();
+
+    let result = model
+        .generate(Vector2u::new(12, 10), &mut StdRng::seed_from_u64(5))
+        .unwrap();
+
+    assert_eq!(result.size(), Vector2u::new(12, 10));
+    assert!(contains_only_patterns(&result, &model));
+}
+
+#[test]
+fn test_generate_stripes() {
+    let example = Map::from_data([[0, 1, 0, 1], [0, 1, 0, 1]]).unwrap();
+    let model = OverlappingModel::new(&example, 2).unwrap();
+
+    let result = model
+        .generate(Vector2u::new(5, 3), &mut StdRng::seed_from_u64(1))
+        .unwrap();
+
+    let first = *result.get((0, 0).into()).unwrap();
+    let row = [first, 1 - first, first, 1 - first, first];
+    assert_eq!(result, Map::from_data([row, row, row]).unwrap());
+}
+
+#[test]
+fn test_generate_seeded() {
+    let model = OverlappingModel::new(&create_example(), 2).unwrap();
+
+    let first = model.generate(Vector2u::new(8, 8), &mut StdRng::seed_from_u64(42));
+    let second = model.generate(Vector2u::new(8, 8), &mut StdRng::seed_from_u64(42));
+
+    assert_eq!(first, second);
+}
+
+#[test]
+fn test_generate_failure() {
+    let model = OverlappingModel::new(&create_example(), 3).unwrap();
+
+    assert_eq!(
+        model.generate(Vector2u::new(2, 10), &mut StdRng::seed_from_u64(0)),
+        Err(TisuError::InvalidMapSize)
+    );
+}
+
+#[test]
+fn test_generate_constrained() {
+    let model = OverlappingModel::new(&create_example(), 3).unwrap();
+    // 2 is free, the floor field must be kept
+    let mut constraints = Map::from_data([[2; 8]; 8]).unwrap();
+    constraints.set((4, 4).into(), 1).unwrap();
+    constraints.set((0, 0).into(), 0).unwrap();
+
+    for seed in 0..5 {
+        let result = model
+            .generate_constrained(&constraints, &2, &mut StdRng::seed_from_u64(seed))
+            .unwrap();
+
+        assert_eq!(result.get((4, 4).into()), Ok(&1));
+        assert_eq!(result.get((0, 0).into()), Ok(&0));
+        assert!(contains_only_patterns(&result, &model));
+    }
+}
+
+#[test]
+fn test_generate_constrained_failure() {
+    let model = OverlappingModel::new(&create_example(), 2).unwrap();
+    // The example has no 2x2 area with floor on a diagonal only
+    let constraints = Map::from_data([[2, 2, 2], [2, 1, 0], [2, 0, 1]]).unwrap();
+
+    let result = model.generate_constrained(&constraints, &2, &mut StdRng::seed_from_u64(0));
+
+    assert_eq!(result, Err(TisuError::NotFound));
+}